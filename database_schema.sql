@@ -9,12 +9,22 @@ CREATE TABLE users (
     email VARCHAR(255) UNIQUE NOT NULL COMMENT '邮箱地址',
     password_hash VARCHAR(255) NOT NULL COMMENT '密码哈希',
     is_verified BOOLEAN DEFAULT FALSE COMMENT '邮箱是否已验证',
-    verification_token VARCHAR(255) COMMENT '邮箱验证令牌',
+    verification_token VARCHAR(255) COMMENT '邮箱验证令牌（SHA-256摘要）',
+    verification_expires_at TIMESTAMP NULL COMMENT '邮箱验证令牌过期时间',
+    pending_email VARCHAR(255) COMMENT '待确认的新邮箱地址',
     last_selected_account_book_id BIGINT COMMENT '上次选择的账本ID',
+    password_reset_token VARCHAR(255) COMMENT '密码重置令牌（SHA-256摘要）',
+    password_reset_expires_at TIMESTAMP NULL COMMENT '密码重置令牌过期时间',
+    password_changed_at TIMESTAMP NULL COMMENT '最近一次修改密码时间',
+    totp_secret VARCHAR(64) COMMENT '两步验证TOTP密钥（Base32）',
+    totp_enabled BOOLEAN DEFAULT FALSE COMMENT '是否启用两步验证',
+    totp_last_step BIGINT NULL COMMENT '最近一次通过校验的TOTP时间步，防止验证码重放',
+    deletion_scheduled_at TIMESTAMP NULL COMMENT '计划彻底删除账户的时间（注销冷静期）',
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
     updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
     INDEX idx_email (email),
-    INDEX idx_verification_token (verification_token)
+    INDEX idx_verification_token (verification_token),
+    INDEX idx_password_reset_token (password_reset_token)
 ) ENGINE=InnoDB COMMENT='用户表';
 
 -- 账本表
@@ -25,6 +35,7 @@ CREATE TABLE account_books (
     description TEXT COMMENT '账本描述',
     currency VARCHAR(3) DEFAULT 'CNY' COMMENT '货币类型',
     cycle_start_day INT DEFAULT 1 COMMENT '月度周期起始日(1-31)',
+    closed_through DATE NULL COMMENT '已结账截止日期（含），该日期及之前的交易不能修改',
     is_active BOOLEAN DEFAULT TRUE COMMENT '是否激活',
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
     updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
@@ -33,6 +44,52 @@ CREATE TABLE account_books (
     INDEX idx_name (name)
 ) ENGINE=InnoDB COMMENT='账本表';
 
+-- 账本成员表（所有者、编辑者、查看者）
+CREATE TABLE account_book_members (
+    account_book_id BIGINT NOT NULL COMMENT '账本ID',
+    user_id BIGINT NOT NULL COMMENT '用户ID',
+    role ENUM('owner', 'editor', 'viewer') NOT NULL COMMENT '角色：所有者/编辑者/查看者',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '加入时间',
+    PRIMARY KEY (account_book_id, user_id),
+    FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    INDEX idx_user_id (user_id)
+) ENGINE=InnoDB COMMENT='账本成员表';
+
+-- 账本邀请表（邮件邀请链接，一次性使用）
+CREATE TABLE account_book_invitations (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '邀请ID',
+    account_book_id BIGINT NOT NULL COMMENT '账本ID',
+    email VARCHAR(255) NOT NULL COMMENT '受邀邮箱',
+    role ENUM('owner', 'editor', 'viewer') NOT NULL COMMENT '加入后的角色',
+    token_hash CHAR(64) NOT NULL COMMENT '邀请令牌SHA-256摘要',
+    invited_by BIGINT NULL COMMENT '邀请人（用户ID）',
+    expires_at TIMESTAMP NOT NULL COMMENT '过期时间',
+    accepted_at TIMESTAMP NULL COMMENT '接受时间',
+    accepted_by BIGINT NULL COMMENT '接受邀请的用户ID',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
+    FOREIGN KEY (invited_by) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (accepted_by) REFERENCES users(id) ON DELETE SET NULL,
+    INDEX idx_account_book_id (account_book_id),
+    INDEX idx_email (email),
+    UNIQUE KEY uk_token_hash (token_hash)
+) ENGINE=InnoDB COMMENT='账本邀请表';
+
+-- 账本结账记录表（每次结账和重新开放各记录一行）
+CREATE TABLE account_book_period_closings (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '记录ID',
+    account_book_id BIGINT NOT NULL COMMENT '账本ID',
+    user_id BIGINT NULL COMMENT '操作人（用户ID）',
+    action ENUM('close', 'reopen') NOT NULL COMMENT '操作：结账/重新开放',
+    closed_through DATE NULL COMMENT '操作后的结账截止日期',
+    previous_closed_through DATE NULL COMMENT '操作前的结账截止日期',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '操作时间',
+    FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL,
+    INDEX idx_account_book_id (account_book_id)
+) ENGINE=InnoDB COMMENT='账本结账记录表';
+
 -- 分类表
 CREATE TABLE categories (
     id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '分类ID',
@@ -50,59 +107,294 @@ CREATE TABLE categories (
     UNIQUE KEY uk_account_book_name (account_book_id, name)
 ) ENGINE=InnoDB COMMENT='分类表';
 
+-- 用户自定义分类模板表（内置模板定义在 src/utils/category_templates.rs）
+CREATE TABLE category_templates (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '模板ID',
+    user_id BIGINT NOT NULL COMMENT '用户ID',
+    name VARCHAR(50) NOT NULL COMMENT '模板名称',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE KEY uk_user_name (user_id, name)
+) ENGINE=InnoDB COMMENT='分类模板表';
+
+-- 分类模板条目表
+CREATE TABLE category_template_items (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '条目ID',
+    template_id BIGINT NOT NULL COMMENT '模板ID',
+    name VARCHAR(100) NOT NULL COMMENT '分类名称',
+    type ENUM('income', 'expense') NOT NULL COMMENT '分类类型：收入/支出',
+    icon VARCHAR(50) COMMENT '图标名称',
+    color VARCHAR(7) COMMENT '颜色代码',
+    sort_order INT DEFAULT 0 COMMENT '排序顺序',
+    FOREIGN KEY (template_id) REFERENCES category_templates(id) ON DELETE CASCADE,
+    INDEX idx_template_id (template_id)
+) ENGINE=InnoDB COMMENT='分类模板条目表';
+
+-- 资金账户表（银行卡、支付宝、现金等，金额为账本货币）
+CREATE TABLE wallets (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '账户ID',
+    account_book_id BIGINT NOT NULL COMMENT '账本ID',
+    name VARCHAR(100) NOT NULL COMMENT '账户名称',
+    type ENUM('cash', 'bank', 'ewallet', 'credit_card', 'loan', 'other') NOT NULL DEFAULT 'cash' COMMENT '账户类型：现金/银行卡/电子钱包/信用卡/贷款/其他，信用卡和贷款为负债',
+    color VARCHAR(7) DEFAULT '#34c759' COMMENT '颜色代码',
+    opening_balance DECIMAL(15,2) NOT NULL DEFAULT 0 COMMENT '期初余额（账本货币）',
+    sort_order INT DEFAULT 0 COMMENT '排序顺序',
+    is_active BOOLEAN DEFAULT TRUE COMMENT '是否激活',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
+    INDEX idx_account_book_id (account_book_id),
+    UNIQUE KEY uk_account_book_name (account_book_id, name)
+) ENGINE=InnoDB COMMENT='资金账户表';
+
+-- 账户余额快照表（某日的实际日终余额，之后的余额以最近一次快照为起点计算）
+CREATE TABLE wallet_snapshots (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '快照ID',
+    wallet_id BIGINT NOT NULL COMMENT '资金账户ID',
+    snapshot_date DATE NOT NULL COMMENT '快照日期',
+    balance DECIMAL(15,2) NOT NULL COMMENT '当日日终余额（账本货币）',
+    note VARCHAR(200) COMMENT '备注',
+    created_by BIGINT COMMENT '记录人（用户ID）',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (wallet_id) REFERENCES wallets(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL,
+    UNIQUE KEY uk_wallet_date (wallet_id, snapshot_date)
+) ENGINE=InnoDB COMMENT='账户余额快照表';
+
 -- 交易记录表
 CREATE TABLE transactions (
     id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '交易ID',
     account_book_id BIGINT NOT NULL COMMENT '账本ID',
     category_id BIGINT NOT NULL COMMENT '分类ID',
-    amount DECIMAL(15,2) NOT NULL COMMENT '金额',
+    wallet_id BIGINT NULL COMMENT '资金账户ID，为空表示未指定账户',
+    amount DECIMAL(15,2) NOT NULL COMMENT '金额（账本货币）',
+    original_currency VARCHAR(3) NOT NULL COMMENT '原始货币代码',
+    original_amount DECIMAL(15,2) NOT NULL COMMENT '原始金额',
+    exchange_rate DECIMAL(18,8) NOT NULL DEFAULT 1 COMMENT '折算汇率（1单位原始货币兑换的账本货币）',
     type ENUM('income', 'expense') NOT NULL COMMENT '交易类型：收入/支出',
     description TEXT COMMENT '交易描述',
     transaction_date DATE NOT NULL COMMENT '交易日期',
-    tags VARCHAR(500) COMMENT '标签（逗号分隔）',
+    created_by BIGINT COMMENT '记录人（用户ID）',
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
     updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
     FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
     FOREIGN KEY (category_id) REFERENCES categories(id),
+    FOREIGN KEY (wallet_id) REFERENCES wallets(id) ON DELETE SET NULL,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL,
     INDEX idx_account_book_id (account_book_id),
     INDEX idx_category_id (category_id),
+    INDEX idx_wallet_id (wallet_id),
     INDEX idx_transaction_date (transaction_date),
     INDEX idx_type (type),
     INDEX idx_created_at (created_at)
 ) ENGINE=InnoDB COMMENT='交易记录表';
 
+-- 标签表（同一账本内名称唯一）
+CREATE TABLE tags (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '标签ID',
+    account_book_id BIGINT NOT NULL COMMENT '账本ID',
+    name VARCHAR(50) NOT NULL COMMENT '标签名称',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
+    UNIQUE KEY uk_account_book_name (account_book_id, name)
+) ENGINE=InnoDB COMMENT='标签表';
+
+-- 交易标签关联表
+CREATE TABLE transaction_tags (
+    transaction_id BIGINT NOT NULL COMMENT '交易ID',
+    tag_id BIGINT NOT NULL COMMENT '标签ID',
+    PRIMARY KEY (transaction_id, tag_id),
+    FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE,
+    INDEX idx_tag_id (tag_id)
+) ENGINE=InnoDB COMMENT='交易标签关联表';
+
+-- 交易拆分明细表（一笔交易按多个分类记账，各行合计等于交易金额）
+CREATE TABLE transaction_splits (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '拆分ID',
+    transaction_id BIGINT NOT NULL COMMENT '交易ID',
+    category_id BIGINT NOT NULL COMMENT '分类ID',
+    amount DECIMAL(15,2) NOT NULL COMMENT '金额（账本货币）',
+    original_amount DECIMAL(15,2) NOT NULL COMMENT '原始金额（与交易相同的货币）',
+    note VARCHAR(200) NULL COMMENT '备注',
+    sort_order INT NOT NULL DEFAULT 0 COMMENT '排序',
+    FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories(id),
+    INDEX idx_transaction_id (transaction_id),
+    INDEX idx_category_id (category_id)
+) ENGINE=InnoDB COMMENT='交易拆分明细表';
+
+-- 转账表（资金账户之间转账，可以跨账本，不计入收支统计；一端账本彻底删除后另一端保留）
+CREATE TABLE transfers (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '转账ID',
+    from_account_book_id BIGINT NULL COMMENT '转出账本ID，账本彻底删除后为空',
+    from_wallet_id BIGINT NULL COMMENT '转出账户ID，账本彻底删除后为空',
+    to_account_book_id BIGINT NULL COMMENT '转入账本ID，账本彻底删除后为空',
+    to_wallet_id BIGINT NULL COMMENT '转入账户ID，账本彻底删除后为空',
+    amount DECIMAL(15,2) NOT NULL COMMENT '转出金额（转出账本货币）',
+    to_amount DECIMAL(15,2) NOT NULL COMMENT '转入金额（转入账本货币）',
+    description TEXT COMMENT '转账说明',
+    transfer_date DATE NOT NULL COMMENT '转账日期',
+    created_by BIGINT COMMENT '记录人（用户ID）',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
+    FOREIGN KEY (from_account_book_id) REFERENCES account_books(id) ON DELETE SET NULL,
+    FOREIGN KEY (from_wallet_id) REFERENCES wallets(id) ON DELETE SET NULL,
+    FOREIGN KEY (to_account_book_id) REFERENCES account_books(id) ON DELETE SET NULL,
+    FOREIGN KEY (to_wallet_id) REFERENCES wallets(id) ON DELETE SET NULL,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL,
+    INDEX idx_from_account_book_id (from_account_book_id),
+    INDEX idx_to_account_book_id (to_account_book_id),
+    INDEX idx_from_wallet_id (from_wallet_id),
+    INDEX idx_to_wallet_id (to_wallet_id),
+    INDEX idx_transfer_date (transfer_date)
+) ENGINE=InnoDB COMMENT='转账表';
+
+-- 重复记账规则表（按周期自动生成交易）
+CREATE TABLE recurring_transactions (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '规则ID',
+    account_book_id BIGINT NOT NULL COMMENT '账本ID',
+    category_id BIGINT NOT NULL COMMENT '分类ID',
+    wallet_id BIGINT NULL COMMENT '资金账户ID，为空表示未指定账户',
+    type ENUM('income', 'expense') NOT NULL COMMENT '交易类型：收入/支出',
+    original_currency VARCHAR(3) NOT NULL COMMENT '原始货币代码',
+    original_amount DECIMAL(15,2) NOT NULL COMMENT '原始金额',
+    exchange_rate DECIMAL(18,8) NULL COMMENT '手动汇率，为空表示按生成日期导入的汇率折算',
+    description TEXT COMMENT '交易描述',
+    tags VARCHAR(500) NULL COMMENT '生成交易的标签，逗号分隔',
+    frequency ENUM('daily', 'weekly', 'monthly', 'yearly', 'every_n_days') NOT NULL COMMENT '重复周期：每天/每周/每月/每年/每隔N天',
+    interval_days INT NOT NULL DEFAULT 1 COMMENT '每隔N天的间隔天数',
+    start_date DATE NOT NULL COMMENT '起始日期，每次的日期都以此为基准计算',
+    end_date DATE NULL COMMENT '结束日期（含）',
+    max_occurrences INT NULL COMMENT '最多生成的次数',
+    next_sequence INT NOT NULL DEFAULT 0 COMMENT '下一次待处理的序号（从0开始）',
+    next_date DATE NULL COMMENT '下一次待处理的日期，为空表示规则已结束',
+    is_active BOOLEAN DEFAULT TRUE COMMENT '是否启用，暂停后恢复会补生成',
+    created_by BIGINT COMMENT '创建人（用户ID），生成的交易记为此人',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
+    FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories(id),
+    FOREIGN KEY (wallet_id) REFERENCES wallets(id) ON DELETE SET NULL,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL,
+    INDEX idx_account_book_id (account_book_id),
+    INDEX idx_active_next_date (is_active, next_date)
+) ENGINE=InnoDB COMMENT='重复记账规则表';
+
+-- 重复记账的每一次（已生成或已跳过），按序号唯一保证不会重复生成
+CREATE TABLE recurring_occurrences (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '记录ID',
+    recurring_transaction_id BIGINT NOT NULL COMMENT '规则ID',
+    sequence INT NOT NULL COMMENT '序号（从0开始）',
+    occurrence_date DATE NOT NULL COMMENT '该次的日期',
+    status ENUM('created', 'skipped', 'blocked') NOT NULL COMMENT '状态：已生成/已跳过/所在期间已结账未生成',
+    transaction_id BIGINT NULL COMMENT '生成的交易ID，交易删除后为空',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (recurring_transaction_id) REFERENCES recurring_transactions(id) ON DELETE CASCADE,
+    FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE SET NULL,
+    UNIQUE KEY uk_rule_sequence (recurring_transaction_id, sequence)
+) ENGINE=InnoDB COMMENT='重复记账生成记录表';
+
+-- 汇率表（用户从CSV导入，记账时按交易日期查找最近的汇率）
+CREATE TABLE exchange_rates (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '汇率ID',
+    user_id BIGINT NOT NULL COMMENT '用户ID',
+    base_currency VARCHAR(3) NOT NULL COMMENT '基础货币',
+    quote_currency VARCHAR(3) NOT NULL COMMENT '报价货币',
+    rate DECIMAL(18,8) NOT NULL COMMENT '1单位基础货币兑换的报价货币',
+    rate_date DATE NOT NULL COMMENT '汇率日期',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE KEY uk_user_pair_date (user_id, base_currency, quote_currency, rate_date)
+) ENGINE=InnoDB COMMENT='汇率表';
+
 -- 会话表（用于用户登录状态管理）
 CREATE TABLE sessions (
     id VARCHAR(128) PRIMARY KEY COMMENT '会话ID',
     user_id BIGINT NOT NULL COMMENT '用户ID',
+    user_agent VARCHAR(500) COMMENT '客户端User-Agent',
+    ip_address VARCHAR(45) COMMENT '登录IP地址',
     expires_at TIMESTAMP NOT NULL COMMENT '过期时间',
+    last_seen_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '最后活跃时间',
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
     FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
     INDEX idx_user_id (user_id),
     INDEX idx_expires_at (expires_at)
 ) ENGINE=InnoDB COMMENT='用户会话表';
 
--- 插入默认分类数据（支出类别）
-INSERT INTO categories (account_book_id, name, type, icon, color, sort_order) VALUES 
--- 注意：这里的account_book_id需要在创建账本后动态插入，这里仅作为示例
--- (1, '餐饮美食', 'expense', 'restaurant', '#ff6b6b', 1),
--- (1, '交通出行', 'expense', 'car', '#4ecdc4', 2),
--- (1, '购物消费', 'expense', 'shopping', '#45b7d1', 3),
--- (1, '娱乐休闲', 'expense', 'game', '#f7b731', 4),
--- (1, '医疗健康', 'expense', 'medical', '#5f27cd', 5),
--- (1, '学习教育', 'expense', 'book', '#00d2d3', 6),
--- (1, '住房租金', 'expense', 'home', '#ff9ff3', 7),
--- (1, '水电燃气', 'expense', 'utility', '#ff3838', 8),
--- (1, '通讯网费', 'expense', 'phone', '#17c0eb', 9),
--- (1, '其他支出', 'expense', 'other', '#a4b0be', 10);
-
--- 插入默认分类数据（收入类别）
--- INSERT INTO categories (account_book_id, name, type, icon, color, sort_order) VALUES 
--- (1, '工资收入', 'income', 'salary', '#26de81', 1),
--- (1, '兼职收入', 'income', 'work', '#4b7bec', 2),
--- (1, '投资理财', 'income', 'investment', '#fd79a8', 3),
--- (1, '礼金红包', 'income', 'gift', '#fdcb6e', 4),
--- (1, '其他收入', 'income', 'other', '#6c5ce7', 5);
+-- 两步验证恢复码表
+CREATE TABLE recovery_codes (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '恢复码ID',
+    user_id BIGINT NOT NULL COMMENT '用户ID',
+    code_hash CHAR(64) NOT NULL COMMENT '恢复码SHA-256摘要',
+    used_at TIMESTAMP NULL COMMENT '使用时间',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    INDEX idx_user_id (user_id),
+    UNIQUE KEY uk_user_code (user_id, code_hash)
+) ENGINE=InnoDB COMMENT='两步验证恢复码表';
+
+-- 个人访问令牌表（用于脚本和第三方工具调用）
+CREATE TABLE api_tokens (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '令牌ID',
+    user_id BIGINT NOT NULL COMMENT '用户ID',
+    name VARCHAR(100) NOT NULL COMMENT '令牌名称',
+    token_prefix VARCHAR(16) NOT NULL COMMENT '令牌前缀（用于识别）',
+    token_hash CHAR(64) NOT NULL COMMENT '令牌SHA-256摘要',
+    scope ENUM('read', 'write') NOT NULL DEFAULT 'read' COMMENT '权限范围：只读/读写',
+    account_book_id BIGINT NULL COMMENT '限定的账本ID，为空表示全部账本',
+    last_used_at TIMESTAMP NULL COMMENT '最近使用时间',
+    expires_at TIMESTAMP NULL COMMENT '过期时间，为空表示永不过期',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (account_book_id) REFERENCES account_books(id) ON DELETE CASCADE,
+    INDEX idx_user_id (user_id),
+    UNIQUE KEY uk_token_hash (token_hash)
+) ENGINE=InnoDB COMMENT='个人访问令牌表';
+
+-- 外部身份关联表（OpenID Connect 登录）
+CREATE TABLE user_identities (
+    id BIGINT PRIMARY KEY AUTO_INCREMENT COMMENT '关联ID',
+    user_id BIGINT NOT NULL COMMENT '用户ID',
+    provider VARCHAR(50) NOT NULL COMMENT '身份提供方标识',
+    subject VARCHAR(255) NOT NULL COMMENT '身份提供方中的用户标识（sub）',
+    email VARCHAR(255) COMMENT '身份提供方返回的邮箱',
+    last_login_at TIMESTAMP NULL COMMENT '最近登录时间',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    INDEX idx_user_id (user_id),
+    UNIQUE KEY uk_provider_subject (provider, subject)
+) ENGINE=InnoDB COMMENT='外部身份关联表';
+
+-- 用户偏好设置表（未保存过偏好的用户使用默认值）
+CREATE TABLE user_preferences (
+    user_id BIGINT PRIMARY KEY COMMENT '用户ID',
+    display_name VARCHAR(100) COMMENT '显示名称',
+    timezone VARCHAR(64) NOT NULL DEFAULT 'Asia/Shanghai' COMMENT '时区（IANA名称）',
+    locale VARCHAR(10) NOT NULL DEFAULT 'zh-CN' COMMENT '语言区域',
+    default_currency VARCHAR(10) NOT NULL DEFAULT 'CNY' COMMENT '新建账本的默认货币',
+    date_format VARCHAR(32) NOT NULL DEFAULT '%Y-%m-%d' COMMENT '日期显示格式',
+    week_start TINYINT NOT NULL DEFAULT 1 COMMENT '每周起始日（0=周日，1=周一，6=周六）',
+    default_account_book_id BIGINT COMMENT '默认打开的账本ID',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '更新时间',
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (default_account_book_id) REFERENCES account_books(id) ON DELETE SET NULL
+) ENGINE=InnoDB COMMENT='用户偏好设置表';
+
+-- 登录限流表（防暴力破解，按操作类型和账户/IP分别计数）
+CREATE TABLE auth_throttles (
+    action VARCHAR(50) NOT NULL COMMENT '操作类型',
+    throttle_key VARCHAR(255) NOT NULL COMMENT '计数键（邮箱或IP）',
+    failure_count INT NOT NULL DEFAULT 0 COMMENT '失败次数',
+    locked_until TIMESTAMP NULL COMMENT '锁定截止时间',
+    last_failure_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '最近一次失败时间',
+    PRIMARY KEY (action, throttle_key),
+    INDEX idx_last_failure_at (last_failure_at)
+) ENGINE=InnoDB COMMENT='登录限流表';
+
+-- 默认分类不再在此插入，新建账本时按所选分类模板预置（见 src/utils/category_templates.rs）
 
 -- 创建数据库视图 - 月度统计
 CREATE VIEW monthly_stats AS
@@ -152,4 +444,125 @@ DELIMITER ;
 -- 数据库升级脚本（为现有数据库添加新字段）
 -- 如果是新安装，上面的CREATE TABLE语句已包含此字段
 -- 如果是升级现有数据库，请执行下面的ALTER语句
--- ALTER TABLE users ADD COLUMN last_selected_account_book_id BIGINT COMMENT '上次选择的账本ID' AFTER verification_token;
\ No newline at end of file
+-- ALTER TABLE users ADD COLUMN last_selected_account_book_id BIGINT COMMENT '上次选择的账本ID' AFTER verification_token;
+
+-- 密码重置功能
+-- ALTER TABLE users ADD COLUMN password_reset_token VARCHAR(255) COMMENT '密码重置令牌' AFTER last_selected_account_book_id;
+-- ALTER TABLE users ADD COLUMN password_reset_expires_at TIMESTAMP NULL COMMENT '密码重置令牌过期时间' AFTER password_reset_token;
+-- ALTER TABLE users ADD COLUMN password_changed_at TIMESTAMP NULL COMMENT '最近一次修改密码时间' AFTER password_reset_expires_at;
+-- ALTER TABLE users ADD INDEX idx_password_reset_token (password_reset_token);
+
+-- 服务端会话
+-- ALTER TABLE sessions ADD COLUMN user_agent VARCHAR(500) COMMENT '客户端User-Agent' AFTER user_id;
+-- ALTER TABLE sessions ADD COLUMN ip_address VARCHAR(45) COMMENT '登录IP地址' AFTER user_agent;
+-- ALTER TABLE sessions ADD COLUMN last_seen_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP COMMENT '最后活跃时间' AFTER expires_at;
+
+-- 两步验证
+-- ALTER TABLE users ADD COLUMN totp_secret VARCHAR(64) COMMENT '两步验证TOTP密钥（Base32）' AFTER password_changed_at;
+-- ALTER TABLE users ADD COLUMN totp_enabled BOOLEAN DEFAULT FALSE COMMENT '是否启用两步验证' AFTER totp_secret;
+-- 并执行上方 CREATE TABLE recovery_codes 语句
+
+-- 个人访问令牌
+-- 执行上方 CREATE TABLE api_tokens 语句
+
+-- 登录限流
+-- 执行上方 CREATE TABLE auth_throttles 语句
+
+-- OpenID Connect 登录
+-- 执行上方 CREATE TABLE user_identities 语句
+
+-- 修改邮箱
+-- ALTER TABLE users ADD COLUMN pending_email VARCHAR(255) COMMENT '待确认的新邮箱地址' AFTER verification_token;
+
+-- 个人偏好设置
+-- 执行上方 CREATE TABLE user_preferences 语句
+
+-- 账户注销
+-- ALTER TABLE users ADD COLUMN deletion_scheduled_at TIMESTAMP NULL COMMENT '计划彻底删除账户的时间（注销冷静期）' AFTER totp_enabled;
+
+-- 邮件令牌改为只保存摘要并设置有效期
+-- ALTER TABLE users ADD COLUMN verification_expires_at TIMESTAMP NULL COMMENT '邮箱验证令牌过期时间' AFTER verification_token;
+-- 升级前发出的明文令牌无法再匹配，清除后用户可在验证页面重新发送
+-- UPDATE users SET verification_token = NULL, pending_email = NULL WHERE verification_token IS NOT NULL;
+-- UPDATE users SET password_reset_token = NULL, password_reset_expires_at = NULL WHERE password_reset_token IS NOT NULL;
+
+-- 共享账本
+-- 执行上方 CREATE TABLE account_book_members 语句，并把现有账本的创建者设为所有者
+-- INSERT INTO account_book_members (account_book_id, user_id, role) SELECT id, user_id, 'owner' FROM account_books;
+-- ALTER TABLE transactions ADD COLUMN created_by BIGINT COMMENT '记录人（用户ID）' AFTER tags;
+-- ALTER TABLE transactions ADD FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL;
+-- UPDATE transactions t JOIN account_books ab ON t.account_book_id = ab.id SET t.created_by = ab.user_id;
+
+-- 账本邀请
+-- 执行上方 CREATE TABLE account_book_invitations 语句
+
+-- 分类模板
+-- 执行上方 CREATE TABLE category_templates 和 CREATE TABLE category_template_items 语句
+
+-- 多币种交易
+-- ALTER TABLE transactions ADD COLUMN original_currency VARCHAR(3) NULL COMMENT '原始货币代码' AFTER amount;
+-- ALTER TABLE transactions ADD COLUMN original_amount DECIMAL(15,2) NULL COMMENT '原始金额' AFTER original_currency;
+-- ALTER TABLE transactions ADD COLUMN exchange_rate DECIMAL(18,8) NOT NULL DEFAULT 1 COMMENT '折算汇率（1单位原始货币兑换的账本货币）' AFTER original_amount;
+-- UPDATE transactions t JOIN account_books ab ON t.account_book_id = ab.id SET t.original_currency = ab.currency, t.original_amount = t.amount;
+-- ALTER TABLE transactions MODIFY original_currency VARCHAR(3) NOT NULL COMMENT '原始货币代码';
+-- ALTER TABLE transactions MODIFY original_amount DECIMAL(15,2) NOT NULL COMMENT '原始金额';
+-- ALTER TABLE transactions MODIFY amount DECIMAL(15,2) NOT NULL COMMENT '金额（账本货币）';
+-- 并执行上方 CREATE TABLE exchange_rates 语句
+
+-- 结账锁定
+-- ALTER TABLE account_books ADD COLUMN closed_through DATE NULL COMMENT '已结账截止日期（含），该日期及之前的交易不能修改' AFTER cycle_start_day;
+-- 并执行上方 CREATE TABLE account_book_period_closings 语句
+
+-- 资金账户
+-- 执行上方 CREATE TABLE wallets 语句
+-- ALTER TABLE transactions ADD COLUMN wallet_id BIGINT NULL COMMENT '资金账户ID，为空表示未指定账户' AFTER category_id;
+-- ALTER TABLE transactions ADD FOREIGN KEY (wallet_id) REFERENCES wallets(id) ON DELETE SET NULL;
+-- ALTER TABLE transactions ADD INDEX idx_wallet_id (wallet_id);
+
+-- 转账
+-- 执行上方 CREATE TABLE transfers 语句
+
+-- 负债账户和余额快照
+-- ALTER TABLE wallets MODIFY type ENUM('cash', 'bank', 'ewallet', 'credit_card', 'loan', 'other') NOT NULL DEFAULT 'cash' COMMENT '账户类型：现金/银行卡/电子钱包/信用卡/贷款/其他，信用卡和贷款为负债';
+-- 并执行上方 CREATE TABLE wallet_snapshots 语句
+
+-- 标签
+-- 执行上方 CREATE TABLE tags 和 CREATE TABLE transaction_tags 语句，把原来逗号分隔的标签拆分迁移后删除旧字段
+-- CREATE TEMPORARY TABLE tag_migration AS
+--     SELECT t.id as transaction_id, t.account_book_id, LEFT(TRIM(j.name), 50) as name
+--     FROM transactions t,
+--          JSON_TABLE(
+--              CONCAT('["', REPLACE(REPLACE(REPLACE(REPLACE(t.tags, '\\', ''), '"', ''), '，', ','), ',', '","'), '"]'),
+--              '$[*]' COLUMNS (name VARCHAR(500) PATH '$')
+--          ) j
+--     WHERE t.tags IS NOT NULL AND TRIM(j.name) <> '';
+-- INSERT IGNORE INTO tags (account_book_id, name) SELECT DISTINCT account_book_id, name FROM tag_migration;
+-- INSERT IGNORE INTO transaction_tags (transaction_id, tag_id)
+--     SELECT m.transaction_id, g.id FROM tag_migration m JOIN tags g ON g.account_book_id = m.account_book_id AND g.name = m.name;
+-- DROP TEMPORARY TABLE tag_migration;
+-- ALTER TABLE transactions DROP COLUMN tags;
+
+-- 拆分交易
+-- 执行上方 CREATE TABLE transaction_splits 语句
+
+-- 重复记账
+-- 执行上方 CREATE TABLE recurring_transactions 和 CREATE TABLE recurring_occurrences 语句
+
+-- 彻底删除账本时保留跨账本转账的另一端（外键名以 SHOW CREATE TABLE transfers 为准）
+-- ALTER TABLE transfers DROP FOREIGN KEY transfers_ibfk_1, DROP FOREIGN KEY transfers_ibfk_2,
+--     DROP FOREIGN KEY transfers_ibfk_3, DROP FOREIGN KEY transfers_ibfk_4;
+-- ALTER TABLE transfers
+--     MODIFY from_account_book_id BIGINT NULL COMMENT '转出账本ID，账本彻底删除后为空',
+--     MODIFY from_wallet_id BIGINT NULL COMMENT '转出账户ID，账本彻底删除后为空',
+--     MODIFY to_account_book_id BIGINT NULL COMMENT '转入账本ID，账本彻底删除后为空',
+--     MODIFY to_wallet_id BIGINT NULL COMMENT '转入账户ID，账本彻底删除后为空',
+--     ADD FOREIGN KEY (from_account_book_id) REFERENCES account_books(id) ON DELETE SET NULL,
+--     ADD FOREIGN KEY (from_wallet_id) REFERENCES wallets(id) ON DELETE SET NULL,
+--     ADD FOREIGN KEY (to_account_book_id) REFERENCES account_books(id) ON DELETE SET NULL,
+--     ADD FOREIGN KEY (to_wallet_id) REFERENCES wallets(id) ON DELETE SET NULL;
+
+-- 两步验证码防重放
+-- ALTER TABLE users ADD COLUMN totp_last_step BIGINT NULL COMMENT '最近一次通过校验的TOTP时间步，防止验证码重放' AFTER totp_enabled;
+
+-- 重复记账落在已结账期间的次数单独标记
+-- ALTER TABLE recurring_occurrences MODIFY status ENUM('created', 'skipped', 'blocked') NOT NULL COMMENT '状态：已生成/已跳过/所在期间已结账未生成';
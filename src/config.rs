@@ -1,3 +1,6 @@
+use std::net::IpAddr;
+
+use jsonwebtoken::Algorithm;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -9,6 +12,24 @@ pub struct AppConfig {
     pub smtp: SmtpConfig,
     pub app_url: String,
     pub session_secret: String,
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    // 可信的反向代理地址，只有来自这些地址的请求才读取 X-Real-IP / X-Forwarded-For
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+// OpenID Connect 身份提供方配置
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProviderConfig {
+    pub id: String,
+    pub display_name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scopes: String,
+    // 找不到同邮箱用户时是否自动注册
+    pub allow_signup: bool,
+    // 接受的ID Token签名算法，不在列表中的一律拒绝
+    pub algorithms: Vec<Algorithm>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,9 +83,81 @@ impl AppConfig {
             smtp,
             app_url,
             session_secret,
+            oidc_providers: Self::oidc_providers_from_env(),
+            trusted_proxies: Self::trusted_proxies_from_env(),
         })
     }
+
+    // TRUSTED_PROXIES=127.0.0.1,::1，未设置时不信任任何转发头，直接使用连接的对端地址
+    fn trusted_proxies_from_env() -> Vec<IpAddr> {
+        let proxies = std::env::var("TRUSTED_PROXIES").unwrap_or_default();
+
+        proxies
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .filter_map(|proxy| match proxy.parse::<IpAddr>() {
+                Ok(ip) => Some(ip),
+                Err(_) => {
+                    tracing::warn!("Invalid address {} in TRUSTED_PROXIES, skipped", proxy);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // OIDC_PROVIDERS=company,google，每个提供方读取 OIDC_<ID>_* 变量，缺少必填项的提供方会被忽略
+    fn oidc_providers_from_env() -> Vec<OidcProviderConfig> {
+        let ids = std::env::var("OIDC_PROVIDERS").unwrap_or_default();
+
+        ids.split(',')
+            .map(|id| id.trim().to_lowercase())
+            .filter(|id| !id.is_empty())
+            .filter_map(|id| {
+                let prefix = format!("OIDC_{}_", id.to_uppercase().replace('-', "_"));
+                let var = |name: &str| {
+                    std::env::var(format!("{}{}", prefix, name))
+                        .ok()
+                        .filter(|value| !value.trim().is_empty())
+                };
+
+                let (Some(issuer), Some(client_id)) = (var("ISSUER"), var("CLIENT_ID")) else {
+                    tracing::warn!("OIDC provider {} is missing {}ISSUER or {}CLIENT_ID, skipped", id, prefix, prefix);
+                    return None;
+                };
+
+                Some(OidcProviderConfig {
+                    display_name: var("DISPLAY_NAME").unwrap_or_else(|| id.clone()),
+                    issuer: issuer.trim_end_matches('/').to_string(),
+                    client_id,
+                    client_secret: var("CLIENT_SECRET"),
+                    scopes: var("SCOPES").unwrap_or_else(|| "openid email profile".to_string()),
+                    allow_signup: var("ALLOW_SIGNUP")
+                        .map(|value| value != "false" && value != "0")
+                        .unwrap_or(true),
+                    algorithms: Self::oidc_algorithms(&id, var("ALGORITHMS").as_deref().unwrap_or("RS256")),
+                    id,
+                })
+            })
+            .collect()
+    }
     
+    // OIDC_<ID>_ALGORITHMS=RS256,ES256；ID Token 由公钥验证，HMAC 算法不允许配置
+    fn oidc_algorithms(id: &str, value: &str) -> Vec<Algorithm> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| match name.parse::<Algorithm>() {
+                Ok(Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) | Err(_) => {
+                    tracing::warn!("Unsupported ID token algorithm {} for OIDC provider {}, skipped", name, id);
+                    None
+                }
+                Ok(algorithm) => Some(algorithm),
+            })
+            .collect()
+    }
+
     pub fn bind_address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
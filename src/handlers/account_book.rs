@@ -6,26 +6,68 @@ use axum::{
 use askama::Template;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 
 use crate::middleware::{AppState, CurrentUser};
-use crate::models::{AccountBook, CreateAccountBook};
-use crate::utils::AccountBookForm;
+use crate::models::{AccountBook, BookRole, CategoryTemplate, CreateAccountBook, DuplicateTransactions, PeriodClosing, Transaction, UserPreferences};
+use crate::services::AccountService;
+use crate::utils::{AccountBookForm, ClosePeriodForm, DuplicateAccountBookForm, PurgeAccountBookForm, ReopenPeriodForm, BUILTIN_CATEGORY_TEMPLATES, DEFAULT_CATEGORY_TEMPLATE};
 
 #[derive(Template)]
 #[template(path = "account_books/new.html")]
 struct NewAccountBookTemplate {
+    user: CurrentUser,
+    default_currency: String,
+    builtin_templates: Vec<CategoryTemplateOption>,
+    user_templates: Vec<CategoryTemplateOption>,
+    default_template: String,
     error: String,
 }
 
+pub struct CategoryTemplateOption {
+    pub value: String,
+    pub label: String,
+    pub description: String,
+}
+
 #[derive(Deserialize)]
 pub struct AccountBookQuery {
     error: Option<String>,
     success: Option<String>,
 }
 
-pub async fn show_new(_user: CurrentUser, Query(query): Query<AccountBookQuery>) -> Html<String> {
+pub async fn show_new(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<AccountBookQuery>,
+) -> Html<String> {
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+
+    let builtin_templates = BUILTIN_CATEGORY_TEMPLATES
+        .iter()
+        .map(|template| CategoryTemplateOption {
+            value: format!("builtin:{}", template.key),
+            label: template.name.to_string(),
+            description: template.description.to_string(),
+        })
+        .collect();
+    let user_templates = CategoryTemplate::find_by_user(&app_state.db_pool, user.id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|template| CategoryTemplateOption {
+            value: format!("user:{}", template.id),
+            label: template.name,
+            description: String::new(),
+        })
+        .collect();
+
     let template = NewAccountBookTemplate {
+        user,
+        default_currency: preferences.default_currency,
+        builtin_templates,
+        user_templates,
+        default_template: format!("builtin:{}", DEFAULT_CATEGORY_TEMPLATE),
         error: query.error.unwrap_or_default(),
     };
     Html(template.render().unwrap())
@@ -55,12 +97,25 @@ pub async fn create(
             urlencoding::encode(&final_error)));
     }
 
+    let categories = match AccountService::resolve_category_template(
+        &app_state.db_pool,
+        user.id,
+        &form.category_template,
+    ).await {
+        Ok(categories) => categories,
+        Err(e) => {
+            return Redirect::to(&format!("/account-books/new?error={}",
+                urlencoding::encode(&e.to_string())));
+        }
+    };
+
     let create_book = CreateAccountBook {
         user_id: user.id,
         name: form.name,
         description: form.description,
         currency: form.currency,
         cycle_start_day: form.cycle_start_day,
+        categories,
     };
 
     match AccountBook::create(&app_state.db_pool, create_book).await {
@@ -78,9 +133,12 @@ pub async fn list(
     State(app_state): State<AppState>,
     Query(query): Query<AccountBookQuery>,
 ) -> Result<Html<String>, Redirect> {
-    match AccountBook::find_by_user(&app_state.db_pool, user.id).await {
+    match AccountBook::find_by_user_with_role(&app_state.db_pool, user.id).await {
         Ok(books) => {
-            let books_display: Vec<AccountBookDisplay> = books.into_iter().map(AccountBookDisplay::from).collect();
+            let books_display: Vec<AccountBookDisplay> = books
+                .into_iter()
+                .map(|(book, role)| AccountBookDisplay::from(book).with_role(role))
+                .collect();
             let template = AccountBookListTemplate {
                 user,
                 books: books_display,
@@ -101,9 +159,23 @@ pub struct AccountBookDisplay {
     pub description: String,
     pub currency: String,
     pub cycle_start_day: i32,
+    pub closed_through: Option<NaiveDate>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    // 当前用户在账本中的角色，未加载时为空
+    pub role_label: String,
+    pub can_edit: bool,
+    pub is_owner: bool,
+}
+
+impl AccountBookDisplay {
+    pub fn with_role(mut self, role: BookRole) -> Self {
+        self.role_label = role.label().to_string();
+        self.can_edit = role.can_edit();
+        self.is_owner = role.is_owner();
+        self
+    }
 }
 
 impl From<AccountBook> for AccountBookDisplay {
@@ -115,9 +187,13 @@ impl From<AccountBook> for AccountBookDisplay {
             description: book.description.unwrap_or_default(),
             currency: book.currency,
             cycle_start_day: book.cycle_start_day,
+            closed_through: book.closed_through,
             is_active: book.is_active,
             created_at: book.created_at,
             updated_at: book.updated_at,
+            role_label: String::new(),
+            can_edit: false,
+            is_owner: false,
         }
     }
 }
@@ -143,6 +219,15 @@ struct AccountBookDetailTemplate {
     error: String,
 }
 
+#[derive(Template)]
+#[template(path = "account_books/archived.html")]
+struct ArchivedAccountBooksTemplate {
+    user: CurrentUser,
+    books: Vec<AccountBookDisplay>,
+    error: String,
+    success: String,
+}
+
 #[derive(Template)]
 #[template(path = "account_books/edit.html")]
 struct EditAccountBookTemplate {
@@ -151,6 +236,33 @@ struct EditAccountBookTemplate {
     error: String,
 }
 
+#[derive(Template)]
+#[template(path = "account_books/duplicate.html")]
+struct DuplicateAccountBookTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    default_name: String,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "account_books/periods.html")]
+struct AccountBookPeriodsTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    closings: Vec<PeriodClosingDisplay>,
+    success: String,
+    error: String,
+}
+
+pub struct PeriodClosingDisplay {
+    pub is_close: bool,
+    pub closed_through: String,
+    pub previous_closed_through: String,
+    pub user_name: String,
+    pub created_at: String,
+}
+
 #[derive(Deserialize)]
 pub struct UpdateAccountBook {
     pub name: String,
@@ -166,8 +278,8 @@ pub async fn detail(
     State(app_state): State<AppState>,
     Query(query): Query<AccountBookQuery>,
 ) -> Result<Html<String>, Redirect> {
-    match AccountBook::find_by_id(&app_state.db_pool, id, user.id).await {
-        Ok(Some(book)) => {
+    match AccountBook::find_with_role(&app_state.db_pool, id, user.id).await {
+        Ok(Some((book, role))) => {
             // 获取统计信息
             let transaction_count = sqlx::query_scalar::<_, i64>(
                 "SELECT COUNT(*) FROM transactions WHERE account_book_id = ?"
@@ -195,7 +307,7 @@ pub async fn detail(
 
             let template = AccountBookDetailTemplate {
                 user,
-                book: AccountBookDisplay::from(book),
+                book: AccountBookDisplay::from(book).with_role(role),
                 transaction_count,
                 total_income: income_result.unwrap_or(rust_decimal::Decimal::ZERO),
                 total_expense: expense_result.unwrap_or(rust_decimal::Decimal::ZERO),
@@ -216,7 +328,8 @@ pub async fn show_edit(
     State(app_state): State<AppState>,
     Query(query): Query<AccountBookQuery>,
 ) -> Result<Html<String>, Redirect> {
-    match AccountBook::find_by_id(&app_state.db_pool, id, user.id).await {
+    // 仅所有者可以修改账本信息
+    match AccountBook::find_with_min_role(&app_state.db_pool, id, user.id, BookRole::Owner).await {
         Ok(Some(book)) => {
             let template = EditAccountBookTemplate {
                 user,
@@ -225,7 +338,7 @@ pub async fn show_edit(
             };
             Ok(Html(template.render().unwrap()))
         }
-        Ok(None) => Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Ok(None) => Err(Redirect::to("/account-books?error=账本不存在或仅所有者可以编辑")),
         Err(_) => Err(Redirect::to("/account-books?error=加载账本失败")),
     }
 }
@@ -246,9 +359,16 @@ pub async fn update(
         return Redirect::to(&format!("/account-books/{}/edit?error=月度周期起始日必须在1-31之间", id));
     }
 
-    // 检查账本是否存在且属于当前用户
-    match AccountBook::find_by_id(&app_state.db_pool, id, user.id).await {
-        Ok(Some(_)) => {
+    // 仅所有者可以修改或删除账本
+    match AccountBook::find_with_min_role(&app_state.db_pool, id, user.id, BookRole::Owner).await {
+        Ok(Some(book)) => {
+            // 交易金额按账本货币保存，已有交易记录时不能再修改货币
+            if book.currency != form.currency
+                && !matches!(Transaction::exists_in_account_book(&app_state.db_pool, id).await, Ok(false))
+            {
+                return Redirect::to(&format!("/account-books/{}/edit?error=账本已有交易记录，不能修改货币", id));
+            }
+
             // 更新账本
             match AccountBook::update(
                 &app_state.db_pool,
@@ -267,23 +387,85 @@ pub async fn update(
                 }
             }
         }
-        Ok(None) => Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Ok(None) => Redirect::to("/account-books?error=账本不存在或仅所有者可以操作"),
         Err(_) => Redirect::to("/account-books?error=操作失败"),
     }
 }
 
-// 删除账本
-pub async fn delete(
+// 显示复制账本页面，任意成员都可以把账本复制为自己的新账本
+pub async fn show_duplicate(
     user: CurrentUser,
     Path(id): Path<i64>,
     State(app_state): State<AppState>,
+    Query(query): Query<AccountBookQuery>,
+) -> Result<Html<String>, Redirect> {
+    match AccountBook::find_with_role(&app_state.db_pool, id, user.id).await {
+        Ok(Some((book, role))) => {
+            let template = DuplicateAccountBookTemplate {
+                user,
+                default_name: format!("{} 副本", book.name),
+                book: AccountBookDisplay::from(book).with_role(role),
+                error: query.error.unwrap_or_default(),
+            };
+            Ok(Html(template.render().unwrap()))
+        }
+        Ok(None) => Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => Err(Redirect::to("/account-books?error=加载账本失败")),
+    }
+}
+
+// 复制账本的设置和分类，可选复制全部或指定日期范围内的交易记录
+pub async fn duplicate(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<DuplicateAccountBookForm>,
 ) -> Redirect {
-    // 检查账本是否存在且属于当前用户
+    let url = format!("/account-books/{}/duplicate", id);
+
+    if form.validate().is_err() || form.name.trim().is_empty() {
+        return Redirect::to(&format!("{}?error=账本名称长度必须在1-100字符之间", url));
+    }
+
+    let transactions = match form.transactions.as_str() {
+        "none" => DuplicateTransactions::None,
+        "all" => DuplicateTransactions::All,
+        "range" => {
+            let parse = |date: &Option<String>| {
+                date.as_deref().and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            };
+            match (parse(&form.start_date), parse(&form.end_date)) {
+                (Some(start), Some(end)) if start <= end => DuplicateTransactions::Range(start, end),
+                (Some(_), Some(_)) => return Redirect::to(&format!("{}?error=开始日期不能晚于结束日期", url)),
+                _ => return Redirect::to(&format!("{}?error=请选择要复制的交易日期范围", url)),
+            }
+        }
+        _ => return Redirect::to(&format!("{}?error=无效的复制选项", url)),
+    };
+
     match AccountBook::find_by_id(&app_state.db_pool, id, user.id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Err(_) => return Redirect::to("/account-books?error=加载账本失败"),
+    }
+
+    match AccountBook::duplicate(&app_state.db_pool, id, user.id, form.name.trim(), transactions).await {
+        Ok(new_id) => Redirect::to(&format!("/account-books/{}?success=账本复制成功！", new_id)),
+        Err(e) => Redirect::to(&format!("{}?error={}", url, urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 归档账本
+pub async fn archive(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    // 仅所有者可以修改或归档账本
+    match AccountBook::find_with_min_role(&app_state.db_pool, id, user.id, BookRole::Owner).await {
         Ok(Some(_)) => {
-            // 删除账本（软删除）
-            match AccountBook::delete(&app_state.db_pool, id, user.id).await {
-                Ok(_) => Redirect::to("/account-books?success=账本删除成功！"),
+            match AccountBook::archive(&app_state.db_pool, id, user.id).await {
+                Ok(_) => Redirect::to("/account-books?success=账本已归档，可在已归档账本中恢复"),
                 Err(e) => {
                     let error_msg = e.to_string();
                     Redirect::to(&format!("/account-books?error={}", 
@@ -291,7 +473,160 @@ pub async fn delete(
                 }
             }
         }
-        Ok(None) => Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Ok(None) => Redirect::to("/account-books?error=账本不存在或仅所有者可以操作"),
         Err(_) => Redirect::to("/account-books?error=操作失败"),
     }
-}
\ No newline at end of file
+}
+
+// 已归档账本列表
+pub async fn archived(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<AccountBookQuery>,
+) -> Result<Html<String>, Redirect> {
+    match AccountBook::find_archived_by_user_with_role(&app_state.db_pool, user.id).await {
+        Ok(books) => {
+            let template = ArchivedAccountBooksTemplate {
+                user,
+                books: books
+                    .into_iter()
+                    .map(|(book, role)| AccountBookDisplay::from(book).with_role(role))
+                    .collect(),
+                error: query.error.unwrap_or_default(),
+                success: query.success.unwrap_or_default(),
+            };
+            Ok(Html(template.render().unwrap()))
+        }
+        Err(_) => Err(Redirect::to("/account-books?error=无法加载已归档账本")),
+    }
+}
+
+// 恢复已归档的账本
+pub async fn restore(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    match AccountBook::find_with_role_including_archived(&app_state.db_pool, id, user.id).await {
+        Ok(Some((_, role))) if role.is_owner() => {
+            match AccountBook::restore(&app_state.db_pool, id, user.id).await {
+                Ok(_) => Redirect::to(&format!("/account-books/{}?success=账本已恢复", id)),
+                Err(_) => Redirect::to("/account-books/archived?error=恢复账本失败"),
+            }
+        }
+        Ok(_) => Redirect::to("/account-books/archived?error=账本不存在或仅所有者可以操作"),
+        Err(_) => Redirect::to("/account-books/archived?error=操作失败"),
+    }
+}
+
+// 永久删除已归档的账本，需要输入账本名称确认
+pub async fn purge(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<PurgeAccountBookForm>,
+) -> Redirect {
+    let book = match AccountBook::find_with_role_including_archived(&app_state.db_pool, id, user.id).await {
+        Ok(Some((book, role))) if role.is_owner() => book,
+        Ok(_) => return Redirect::to("/account-books/archived?error=账本不存在或仅所有者可以操作"),
+        Err(_) => return Redirect::to("/account-books/archived?error=操作失败"),
+    };
+
+    if book.is_active {
+        return Redirect::to("/account-books/archived?error=请先归档账本再永久删除");
+    }
+
+    if form.confirm_name.trim() != book.name {
+        return Redirect::to("/account-books/archived?error=输入的账本名称不一致，未删除");
+    }
+
+    match AccountBook::purge(&app_state.db_pool, id, user.id).await {
+        Ok(true) => Redirect::to("/account-books/archived?success=账本及其分类和交易记录已永久删除"),
+        Ok(false) => Redirect::to("/account-books/archived?error=账本不存在或仅所有者可以操作"),
+        Err(_) => Redirect::to("/account-books/archived?error=永久删除账本失败"),
+    }
+}
+
+// 结账状态和操作记录，任意成员可以查看，仅所有者可以结账或重新开放
+pub async fn periods(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+    Query(query): Query<AccountBookQuery>,
+) -> Result<Html<String>, Redirect> {
+    let book = match AccountBook::find_with_role(&app_state.db_pool, id, user.id).await {
+        Ok(Some((book, role))) => AccountBookDisplay::from(book).with_role(role),
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => return Err(Redirect::to("/account-books?error=加载账本失败")),
+    };
+
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let format_date = |date: Option<NaiveDate>| date.map(|date| preferences.format_date(date)).unwrap_or_default();
+    let closings = PeriodClosing::find_by_account_book(&app_state.db_pool, id, 50)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| PeriodClosingDisplay {
+            is_close: row.closing.action == "close",
+            closed_through: format_date(row.closing.closed_through),
+            previous_closed_through: format_date(row.closing.previous_closed_through),
+            user_name: row.user_name.unwrap_or_else(|| "已注销用户".to_string()),
+            created_at: row.closing.created_at.with_timezone(&preferences.tz()).format("%Y-%m-%d %H:%M").to_string(),
+        })
+        .collect();
+
+    let template = AccountBookPeriodsTemplate {
+        user,
+        book,
+        closings,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 结账：截止日期及之前的交易不能再新增、修改或删除
+pub async fn close_period(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<ClosePeriodForm>,
+) -> Redirect {
+    let url = format!("/account-books/{}/periods", id);
+
+    let closed_through = match NaiveDate::parse_from_str(form.closed_through.trim(), "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => return Redirect::to(&format!("{}?error=请选择结账截止日期", url)),
+    };
+
+    match PeriodClosing::close(&app_state.db_pool, id, user.id, closed_through).await {
+        Ok(_) => Redirect::to(&format!("{}?success=已结账至 {}", url, closed_through.format("%Y-%m-%d"))),
+        Err(e) => Redirect::to(&format!("{}?error={}", url, urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 重新开放：把结账截止日期提前，或完全取消结账
+pub async fn reopen_period(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<ReopenPeriodForm>,
+) -> Redirect {
+    let url = format!("/account-books/{}/periods", id);
+
+    let closed_through = match form.closed_through.as_deref().map(str::trim).filter(|date| !date.is_empty()) {
+        Some(date) => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(date) => Some(date),
+            Err(_) => return Redirect::to(&format!("{}?error=日期格式错误", url)),
+        },
+        None => None,
+    };
+
+    match PeriodClosing::reopen(&app_state.db_pool, id, user.id, closed_through).await {
+        Ok(_) => match closed_through {
+            Some(date) => Redirect::to(&format!("{}?success=已重新开放 {} 之后的期间", url, date.format("%Y-%m-%d"))),
+            None => Redirect::to(&format!("{}?success=已取消结账，全部期间重新开放", url)),
+        },
+        Err(e) => Redirect::to(&format!("{}?error={}", url, urlencoding::encode(&e.to_string()))),
+    }
+}
@@ -0,0 +1,352 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, Redirect},
+    Form,
+};
+use askama::Template;
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::handlers::account_book::AccountBookDisplay;
+use crate::middleware::{AppState, CurrentUser, OptionalCurrentUser};
+use crate::models::{AccountBook, AccountBookInvitation, AccountBookMember, BookRole, User};
+use crate::services::INVITATION_TTL_DAYS;
+use crate::utils::{InviteMemberForm, UpdateMemberRoleForm};
+
+#[derive(Template)]
+#[template(path = "account_books/members.html")]
+struct MembersTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    members: Vec<MemberDisplay>,
+    invitations: Vec<InvitationDisplay>,
+    role_options: Vec<RoleOption>,
+    invitation_ttl_days: i64,
+    success: String,
+    error: String,
+}
+
+// 邀请链接页面，未登录用户也可以打开
+#[derive(Template)]
+#[template(path = "account_books/invitation.html")]
+struct InvitationTemplate {
+    token: String,
+    // 邀请无效、已使用或已过期时的提示
+    error: String,
+    book_id: i64,
+    book_name: String,
+    inviter_name: String,
+    role_label: String,
+    invited_email: String,
+    expires_at: String,
+    // 未登录时为空
+    current_email: String,
+    already_member: bool,
+}
+
+pub struct MemberDisplay {
+    pub user_id: i64,
+    pub email: String,
+    pub display_name: String,
+    pub role: String,
+    pub role_label: String,
+    pub joined_at: String,
+    pub is_self: bool,
+}
+
+pub struct InvitationDisplay {
+    pub id: i64,
+    pub email: String,
+    pub role_label: String,
+    pub expires_at: String,
+}
+
+pub struct RoleOption {
+    pub value: &'static str,
+    pub label: &'static str,
+}
+
+#[derive(Deserialize)]
+pub struct MembersQuery {
+    success: Option<String>,
+    error: Option<String>,
+}
+
+fn role_options() -> Vec<RoleOption> {
+    [BookRole::Viewer, BookRole::Editor, BookRole::Owner]
+        .into_iter()
+        .map(|role| RoleOption { value: role.as_str(), label: role.label() })
+        .collect()
+}
+
+fn members_url(account_book_id: i64) -> String {
+    format!("/account-books/{}/members", account_book_id)
+}
+
+// 仅所有者可以管理成员
+async fn require_owner(app_state: &AppState, account_book_id: i64, user_id: i64) -> Result<AccountBook, Redirect> {
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user_id, BookRole::Owner).await {
+        Ok(Some(book)) => Ok(book),
+        Ok(None) => Err(Redirect::to(&format!("{}?error=仅账本所有者可以管理成员", members_url(account_book_id)))),
+        Err(_) => Err(Redirect::to("/account-books?error=操作失败")),
+    }
+}
+
+// 账本成员列表，所有成员可见
+pub async fn list(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Query(query): Query<MembersQuery>,
+) -> Result<Html<String>, Redirect> {
+    let (book, role) = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some(pair)) => pair,
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => return Err(Redirect::to("/account-books?error=加载账本失败")),
+    };
+
+    let members = AccountBookMember::find_by_account_book(&app_state.db_pool, account_book_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|member| MemberDisplay {
+            user_id: member.user_id,
+            role_label: member.role().label().to_string(),
+            role: member.role,
+            display_name: member.display_name.filter(|name| !name.trim().is_empty()).unwrap_or_default(),
+            email: member.email,
+            joined_at: member.created_at.format("%Y-%m-%d").to_string(),
+            is_self: member.user_id == user.id,
+        })
+        .collect();
+
+    // 待接受的邀请只对所有者可见
+    let invitations = if role.is_owner() {
+        AccountBookInvitation::find_pending_by_account_book(&app_state.db_pool, account_book_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|invitation| InvitationDisplay {
+                id: invitation.id,
+                role_label: invitation.role().label().to_string(),
+                email: invitation.email,
+                expires_at: invitation.expires_at.format("%Y-%m-%d %H:%M").to_string(),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let template = MembersTemplate {
+        user,
+        book: AccountBookDisplay::from(book).with_role(role),
+        members,
+        invitations,
+        role_options: role_options(),
+        invitation_ttl_days: INVITATION_TTL_DAYS,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+// 发送邮件邀请，已注册和未注册的邮箱都可以邀请
+pub async fn invite(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<InviteMemberForm>,
+) -> Redirect {
+    let account_book = match require_owner(&app_state, account_book_id, user.id).await {
+        Ok(account_book) => account_book,
+        Err(redirect) => return redirect,
+    };
+
+    let url = members_url(account_book_id);
+
+    if form.validate().is_err() {
+        return Redirect::to(&format!("{}?error=请输入有效的邮箱地址", url));
+    }
+
+    let role = match BookRole::parse(&form.role) {
+        Some(role) => role,
+        None => return Redirect::to(&format!("{}?error=无效的成员角色", url)),
+    };
+
+    let inviter = match User::find_by_id(&app_state.db_pool, user.id).await {
+        Ok(Some(inviter)) => inviter,
+        _ => return Redirect::to(&format!("{}?error=操作失败", url)),
+    };
+
+    match app_state
+        .auth_service
+        .invite_to_account_book(&app_state.db_pool, &account_book, &inviter, &form.email, role)
+        .await
+    {
+        Ok(()) => Redirect::to(&format!("{}?success=邀请邮件已发送", url)),
+        Err(e) => Redirect::to(&format!("{}?error={}", url, urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 撤销尚未接受的邀请，邀请链接随即失效
+pub async fn revoke_invitation(
+    user: CurrentUser,
+    Path((account_book_id, invitation_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    if let Err(redirect) = require_owner(&app_state, account_book_id, user.id).await {
+        return redirect;
+    }
+
+    let url = members_url(account_book_id);
+
+    match AccountBookInvitation::revoke(&app_state.db_pool, account_book_id, invitation_id).await {
+        Ok(true) => Redirect::to(&format!("{}?success=邀请已撤销", url)),
+        Ok(false) => Redirect::to(&format!("{}?error=邀请不存在或已被接受", url)),
+        Err(_) => Redirect::to(&format!("{}?error=撤销邀请失败", url)),
+    }
+}
+
+// 打开邀请链接：已登录用户确认后加入，未登录用户先登录或注册
+pub async fn show_invitation(
+    OptionalCurrentUser(user): OptionalCurrentUser,
+    Path(token): Path<String>,
+    State(app_state): State<AppState>,
+) -> Html<String> {
+    let mut template = InvitationTemplate {
+        token: token.clone(),
+        error: String::new(),
+        book_id: 0,
+        book_name: String::new(),
+        inviter_name: String::new(),
+        role_label: String::new(),
+        invited_email: String::new(),
+        expires_at: String::new(),
+        current_email: user.as_ref().map(|user| user.email.clone()).unwrap_or_default(),
+        already_member: false,
+    };
+
+    match app_state.auth_service.find_invitation(&app_state.db_pool, &token).await {
+        Ok(found) => {
+            let invitation = found.invitation;
+            if invitation.accepted_at.is_some() {
+                template.error = "该邀请已被使用".to_string();
+            } else if !invitation.is_pending() {
+                template.error = "邀请链接已过期，请联系账本所有者重新邀请".to_string();
+            }
+
+            if let Some(user) = &user {
+                template.already_member = matches!(
+                    AccountBookMember::find_role(&app_state.db_pool, invitation.account_book_id, user.id).await,
+                    Ok(Some(_))
+                );
+            }
+
+            template.book_id = invitation.account_book_id;
+            template.book_name = found.book_name;
+            template.inviter_name = found.inviter_name.unwrap_or_default();
+            template.role_label = invitation.role().label().to_string();
+            template.expires_at = invitation.expires_at.format("%Y-%m-%d %H:%M").to_string();
+            template.invited_email = invitation.email;
+        }
+        Err(e) => template.error = e.to_string(),
+    }
+
+    Html(template.render().unwrap())
+}
+
+pub async fn accept_invitation(
+    user: CurrentUser,
+    Path(token): Path<String>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    match app_state
+        .auth_service
+        .accept_invitation(&app_state.db_pool, &token, user.id, &user.email)
+        .await
+    {
+        Ok(account_book_id) => Redirect::to(&format!("/account-books/{}?success=已加入账本", account_book_id)),
+        Err(e) => Redirect::to(&format!("/account-books?error={}", urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 修改成员角色，账本至少保留一位所有者
+pub async fn update_role(
+    user: CurrentUser,
+    Path((account_book_id, member_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Form(form): Form<UpdateMemberRoleForm>,
+) -> Redirect {
+    if let Err(redirect) = require_owner(&app_state, account_book_id, user.id).await {
+        return redirect;
+    }
+
+    let url = members_url(account_book_id);
+
+    let role = match BookRole::parse(&form.role) {
+        Some(role) => role,
+        None => return Redirect::to(&format!("{}?error=无效的成员角色", url)),
+    };
+
+    let current_role = match AccountBookMember::find_role(&app_state.db_pool, account_book_id, member_id).await {
+        Ok(Some(current_role)) => current_role,
+        Ok(None) => return Redirect::to(&format!("{}?error=成员不存在", url)),
+        Err(_) => return Redirect::to(&format!("{}?error=操作失败", url)),
+    };
+
+    if current_role.is_owner() && !role.is_owner() && is_last_owner(&app_state, account_book_id).await {
+        return Redirect::to(&format!("{}?error=账本至少需要保留一位所有者", url));
+    }
+
+    match AccountBookMember::update_role(&app_state.db_pool, account_book_id, member_id, role).await {
+        Ok(_) => Redirect::to(&format!("{}?success=成员角色已更新", url)),
+        Err(_) => Redirect::to(&format!("{}?error=更新成员角色失败", url)),
+    }
+}
+
+// 所有者可以移除成员，任何成员都可以退出账本
+pub async fn remove(
+    user: CurrentUser,
+    Path((account_book_id, member_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let url = members_url(account_book_id);
+    let leaving = member_id == user.id;
+
+    if !leaving {
+        if let Err(redirect) = require_owner(&app_state, account_book_id, user.id).await {
+            return redirect;
+        }
+    }
+
+    let current_role = match AccountBookMember::find_role(&app_state.db_pool, account_book_id, member_id).await {
+        Ok(Some(current_role)) => current_role,
+        Ok(None) if leaving => return Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Ok(None) => return Redirect::to(&format!("{}?error=成员不存在", url)),
+        Err(_) => return Redirect::to(&format!("{}?error=操作失败", url)),
+    };
+
+    if current_role.is_owner() && is_last_owner(&app_state, account_book_id).await {
+        let message = if leaving {
+            "你是唯一的所有者，请先将其他成员设为所有者再退出"
+        } else {
+            "账本至少需要保留一位所有者"
+        };
+        return Redirect::to(&format!("{}?error={}", url, message));
+    }
+
+    match AccountBookMember::remove(&app_state.db_pool, account_book_id, member_id).await {
+        Ok(_) if leaving => Redirect::to("/account-books?success=已退出账本"),
+        Ok(_) => Redirect::to(&format!("{}?success=成员已移除", url)),
+        Err(_) => Redirect::to(&format!("{}?error=操作失败", url)),
+    }
+}
+
+// 查询失败时按最后一位所有者处理，避免误删
+async fn is_last_owner(app_state: &AppState, account_book_id: i64) -> bool {
+    AccountBookMember::count_owners(&app_state.db_pool, account_book_id)
+        .await
+        .map(|count| count <= 1)
+        .unwrap_or(true)
+}
@@ -9,7 +9,8 @@ use rust_decimal::Decimal;
 use rust_decimal::prelude::ToPrimitive;
 
 use crate::middleware::{AppState, CurrentUser};
-use crate::models::{AccountBook, Transaction};
+use crate::models::{wallet_type_label, is_liability_type, AccountBook, Transaction, UserPreferences, WalletLedger};
+use crate::utils::currency_symbol;
 use crate::handlers::account_book::AccountBookDisplay;
 
 // 统计报表相关的结构体
@@ -35,6 +36,36 @@ pub struct DailyStat {
     pub amount: Decimal,
 }
 
+// 按原始货币汇总，折算金额为账本货币
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CurrencyStat {
+    pub currency: String,
+    pub original_income: Decimal,
+    pub original_expense: Decimal,
+    pub converted_income: Decimal,
+    pub converted_expense: Decimal,
+    pub transaction_count: i64,
+}
+
+// 资金账户在区间内每个变动日的日终余额
+#[derive(Debug, Serialize)]
+pub struct WalletBalanceSeries {
+    pub name: String,
+    pub color: String,
+    pub balances: Vec<Decimal>,
+}
+
+// 标签在统计区间内的收支合计，一笔交易有多个标签时分别计入各标签
+#[derive(Debug, Serialize)]
+pub struct TagStat {
+    pub name: String,
+    pub income: Decimal,
+    pub expense: Decimal,
+    pub transaction_count: i64,
+    // 查看该标签在区间内交易的查询参数
+    pub filter_query: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct MonthlyDetail {
     pub date: String,
@@ -55,6 +86,12 @@ struct AccountBookReportsTemplate {
     income_categories: Vec<CategoryStat>,
     daily_expenses: Vec<DailyStat>,
     monthly_details: Vec<MonthlyDetail>,
+    // 区间内含外币交易时才展示
+    currency_stats: Vec<CurrencyStat>,
+    tag_stats: Vec<TagStat>,
+    // 账户余额走势的横轴日期
+    wallet_balance_labels: Vec<String>,
+    wallet_balances: Vec<WalletBalanceSeries>,
     total_income: Decimal,
     total_expense: Decimal,
     net_balance: Decimal,  // 预计算的净收支
@@ -63,9 +100,57 @@ struct AccountBookReportsTemplate {
     average_daily_expense: Decimal,
     start_date: String,
     end_date: String,
+    // 按用户日期格式显示的统计区间
+    start_label: String,
+    end_label: String,
+    // 快捷区间：本周（按用户的每周起始日）和本月
+    week_start_date: String,
+    week_end_date: String,
+    month_start_date: String,
+    month_end_date: String,
+    currency_symbol: String,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "account_books/net_worth.html")]
+struct NetWorthTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    points: Vec<NetWorthPoint>,
+    wallets: Vec<NetWorthWallet>,
+    total_assets: Decimal,
+    total_liabilities: Decimal,
+    net_worth: Decimal,
+    is_positive: bool,
+    start_date: String,
+    end_date: String,
+    start_label: String,
+    end_label: String,
+    currency_symbol: String,
     error: String,
 }
 
+// 某日的资产、负债和净资产，负债以欠款金额（正数）表示
+#[derive(Debug, Serialize)]
+pub struct NetWorthPoint {
+    pub label: String,
+    pub assets: Decimal,
+    pub liabilities: Decimal,
+    pub net_worth: Decimal,
+}
+
+// 统计截止日各账户的余额
+#[derive(Debug, Serialize)]
+pub struct NetWorthWallet {
+    pub name: String,
+    pub type_label: String,
+    pub is_liability: bool,
+    pub balance: Decimal,
+    // 截止日及之前最近一次余额快照，没有时为空
+    pub snapshot_label: String,
+}
+
 #[derive(Deserialize)]
 pub struct ReportsQuery {
     error: Option<String>,
@@ -80,14 +165,15 @@ pub async fn reports(
     State(app_state): State<AppState>,
     Query(query): Query<ReportsQuery>,
 ) -> Result<Html<String>, Redirect> {
-    // 验证账本所有权
-    let book = match AccountBook::find_by_id(&app_state.db_pool, id, user.id).await {
-        Ok(Some(book)) => AccountBookDisplay::from(book),
+    // 验证账本成员身份，已归档的账本同样可以只读查看报表
+    let book = match AccountBook::find_with_role_including_archived(&app_state.db_pool, id, user.id).await {
+        Ok(Some((book, role))) => AccountBookDisplay::from(book).with_role(role),
         _ => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
     };
 
     // 根据参数确定日期范围，默认使用账本的周期数据（基于起始日）
-    let today = chrono::Utc::now().naive_utc().date();
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let today = preferences.today();
     let (start_date, end_date) = match (query.start_date.as_ref(), query.end_date.as_ref()) {
         (Some(start), Some(end)) => {
             // 用户指定了起始和结束日期
@@ -109,8 +195,8 @@ pub async fn reports(
     let monthly_trends = get_monthly_trends(&app_state.db_pool, id, book.cycle_start_day, start_date, end_date).await;
 
     // 获取分类统计数据
-    let expense_categories = get_category_stats(&app_state.db_pool, id, "expense", start_date, end_date).await;
-    let income_categories = get_category_stats(&app_state.db_pool, id, "income", start_date, end_date).await;
+    let expense_categories = get_category_stats(&app_state.db_pool, id, "expense", start_date, end_date, Some(10)).await;
+    let income_categories = get_category_stats(&app_state.db_pool, id, "income", start_date, end_date, Some(10)).await;
 
     // 获取每日支出数据（基于选择的时间范围）
     let daily_expenses = get_daily_expenses(&app_state.db_pool, id, start_date, end_date).await;
@@ -118,7 +204,21 @@ pub async fn reports(
     // 获取月度收支明细（基于账本起始日）
     let monthly_details = get_monthly_details(&app_state.db_pool, id, book.cycle_start_day, start_date, end_date).await;
 
-    // 获取总体统计
+    // 按原始货币汇总，全部金额已折算为账本货币
+    let currency_stats = get_currency_stats(&app_state.db_pool, id, start_date, end_date).await;
+    let currency_stats = if currency_stats.iter().any(|stat| stat.currency != book.currency) {
+        currency_stats
+    } else {
+        Vec::new()
+    };
+
+    let tag_stats = get_tag_stats(&app_state.db_pool, id, start_date, end_date).await;
+
+    // 资金账户余额走势
+    let (wallet_balance_labels, wallet_balances) =
+        get_wallet_balance_history(&app_state.db_pool, id, start_date, end_date).await;
+
+    // 获取总体统计（账本货币）
     let (total_income, total_expense) = Transaction::get_monthly_summary(
         &app_state.db_pool,
         id,
@@ -127,7 +227,7 @@ pub async fn reports(
     ).await.unwrap_or((Decimal::ZERO, Decimal::ZERO));
 
     // 计算日均支出，保留两位小数
-    let days_count = (end_date - start_date).num_days() as i64;
+    let days_count = (end_date - start_date).num_days();
     let average_daily_expense = if days_count > 0 {
         (total_expense / Decimal::from(days_count)).round_dp(2)
     } else {
@@ -139,14 +239,22 @@ pub async fn reports(
     let is_positive_balance = total_income >= total_expense;
     let net_balance_abs = net_balance.abs(); // 绝对值用于显示
 
+    let (week_start, week_end) = preferences.week_range(today);
+    let (month_start, month_end) = calculate_current_cycle_dates(today, 1);
+
     let template = AccountBookReportsTemplate {
         user,
+        currency_symbol: currency_symbol(&book.currency),
         book,
         monthly_trends,
         expense_categories,
         income_categories,
         daily_expenses,
         monthly_details,
+        currency_stats,
+        tag_stats,
+        wallet_balance_labels,
+        wallet_balances,
         total_income,
         total_expense,
         net_balance,
@@ -155,6 +263,128 @@ pub async fn reports(
         average_daily_expense,
         start_date: start_date.format("%Y-%m-%d").to_string(),
         end_date: end_date.format("%Y-%m-%d").to_string(),
+        start_label: preferences.format_date(start_date),
+        end_label: preferences.format_date(end_date),
+        week_start_date: week_start.format("%Y-%m-%d").to_string(),
+        week_end_date: week_end.format("%Y-%m-%d").to_string(),
+        month_start_date: month_start.format("%Y-%m-%d").to_string(),
+        month_end_date: month_end.format("%Y-%m-%d").to_string(),
+        error: query.error.unwrap_or_default(),
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+// 净资产走势：以余额快照为起点，结合交易和转账推算各月末的资产和负债
+pub async fn net_worth(
+    user: CurrentUser,
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+    Query(query): Query<ReportsQuery>,
+) -> Result<Html<String>, Redirect> {
+    let book = match AccountBook::find_with_role_including_archived(&app_state.db_pool, id, user.id).await {
+        Ok(Some((book, role))) => AccountBookDisplay::from(book).with_role(role),
+        _ => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+    };
+
+    // 默认显示最近12个月
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let today = preferences.today();
+    let (start_date, end_date) = match (query.start_date.as_ref(), query.end_date.as_ref()) {
+        (Some(start), Some(end)) => {
+            let start = NaiveDate::parse_from_str(start, "%Y-%m-%d")
+                .map_err(|_| Redirect::to(&format!("/account-books/{}/reports/net-worth?error=起始日期格式错误", id)))?;
+            let end = NaiveDate::parse_from_str(end, "%Y-%m-%d")
+                .map_err(|_| Redirect::to(&format!("/account-books/{}/reports/net-worth?error=结束日期格式错误", id)))?;
+            if start > end {
+                return Err(Redirect::to(&format!("/account-books/{}/reports/net-worth?error=起始日期不能晚于结束日期", id)));
+            }
+            (start, end)
+        }
+        _ => {
+            let this_month = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap();
+            (this_month - chrono::Months::new(11), today)
+        }
+    };
+
+    let ledger = WalletLedger::load(&app_state.db_pool, id, end_date)
+        .await
+        .map_err(|_| Redirect::to(&format!("/account-books/{}?error=加载账户余额失败", id)))?;
+
+    // 区间起止日和其间的各月末
+    let mut dates = vec![start_date];
+    let mut month_start = NaiveDate::from_ymd_opt(start_date.year(), start_date.month(), 1).unwrap();
+    loop {
+        let month_end = month_start + chrono::Months::new(1) - chrono::Duration::days(1);
+        if month_end >= end_date {
+            break;
+        }
+        if month_end > start_date {
+            dates.push(month_end);
+        }
+        month_start = month_start + chrono::Months::new(1);
+    }
+    if end_date > start_date {
+        dates.push(end_date);
+    }
+
+    let points = dates
+        .iter()
+        .map(|date| {
+            let (assets, liabilities) = ledger.wallets.iter().fold(
+                (Decimal::ZERO, Decimal::ZERO),
+                |(assets, liabilities), wallet| {
+                    let balance = ledger.balance_at(wallet, *date);
+                    if is_liability_type(&wallet.wallet_type) {
+                        (assets, liabilities - balance)
+                    } else {
+                        (assets + balance, liabilities)
+                    }
+                },
+            );
+            NetWorthPoint {
+                label: preferences.format_date(*date),
+                assets,
+                liabilities,
+                net_worth: assets - liabilities,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let wallets = ledger
+        .wallets
+        .iter()
+        .map(|wallet| NetWorthWallet {
+            name: wallet.name.clone(),
+            type_label: wallet_type_label(&wallet.wallet_type).to_string(),
+            is_liability: is_liability_type(&wallet.wallet_type),
+            balance: ledger.balance_at(wallet, end_date),
+            snapshot_label: ledger
+                .latest_snapshot(wallet.id, end_date)
+                .map(|(date, balance)| format!("{} 对账 {}{}", preferences.format_date(date), currency_symbol(&book.currency), balance))
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    let (total_assets, total_liabilities, net_worth) = points
+        .last()
+        .map(|point| (point.assets, point.liabilities, point.net_worth))
+        .unwrap_or_default();
+
+    let template = NetWorthTemplate {
+        user,
+        currency_symbol: currency_symbol(&book.currency),
+        book,
+        points,
+        wallets,
+        total_assets,
+        total_liabilities,
+        net_worth,
+        is_positive: net_worth >= Decimal::ZERO,
+        start_date: start_date.format("%Y-%m-%d").to_string(),
+        end_date: end_date.format("%Y-%m-%d").to_string(),
+        start_label: preferences.format_date(start_date),
+        end_label: preferences.format_date(end_date),
         error: query.error.unwrap_or_default(),
     };
 
@@ -216,35 +446,43 @@ async fn get_monthly_trends(
         .collect()
 }
 
-// 获取分类统计数据
-async fn get_category_stats(
+// 获取分类统计数据，按金额从高到低取前 limit 个分类，None 表示全部分类
+pub(crate) async fn get_category_stats(
     pool: &crate::database::DbPool,
     account_book_id: i64,
     category_type: &str,
     start_date: NaiveDate,
     end_date: NaiveDate,
+    limit: Option<i64>,
 ) -> Vec<CategoryStat> {
     let rows: Vec<(String, Option<Decimal>, i64, Option<String>)> = sqlx::query_as(
         r#"
         SELECT 
             c.name,
-            SUM(t.amount) as total_amount,
-            COUNT(t.id) as transaction_count,
+            SUM(l.amount) as total_amount,
+            COUNT(DISTINCT l.id) as transaction_count,
             c.color
         FROM categories c
-        LEFT JOIN transactions t ON c.id = t.category_id 
-            AND t.transaction_date BETWEEN ? AND ?
+        LEFT JOIN (
+            -- 拆分交易按各行的分类和金额计入
+            SELECT t.id, COALESCE(s.category_id, t.category_id) as category_id, COALESCE(s.amount, t.amount) as amount
+            FROM transactions t
+            LEFT JOIN transaction_splits s ON s.transaction_id = t.id
+            WHERE t.account_book_id = ? AND t.transaction_date BETWEEN ? AND ?
+        ) l ON c.id = l.category_id
         WHERE c.account_book_id = ? AND c.`type` = ? AND c.is_active = TRUE
         GROUP BY c.id, c.name, c.color
         HAVING total_amount > 0
         ORDER BY total_amount DESC
-        LIMIT 10
+        LIMIT ?
         "#,
     )
+    .bind(account_book_id)
     .bind(start_date)
     .bind(end_date)
     .bind(account_book_id)
     .bind(category_type)
+    .bind(limit.unwrap_or(i64::MAX))
     .fetch_all(pool)
     .await
     .unwrap_or_default();
@@ -307,6 +545,108 @@ async fn get_daily_expenses(
         .collect()
 }
 
+// 区间内各账户的余额走势，横轴为区间起止日和余额有变化的日期
+async fn get_wallet_balance_history(
+    pool: &crate::database::DbPool,
+    account_book_id: i64,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> (Vec<String>, Vec<WalletBalanceSeries>) {
+    let ledger = match WalletLedger::load(pool, account_book_id, end_date).await {
+        Ok(ledger) if !ledger.wallets.is_empty() => ledger,
+        _ => return (Vec::new(), Vec::new()),
+    };
+
+    let dates = ledger.change_dates(start_date, end_date);
+    let series = ledger
+        .wallets
+        .iter()
+        .map(|wallet| WalletBalanceSeries {
+            name: wallet.name.clone(),
+            color: wallet.color.clone().unwrap_or_else(|| "#34c759".to_string()),
+            balances: dates.iter().map(|date| ledger.balance_at(wallet, *date)).collect(),
+        })
+        .collect();
+
+    let labels = dates.iter().map(|date| date.format("%m-%d").to_string()).collect();
+    (labels, series)
+}
+
+// 按原始货币统计区间内的收支
+async fn get_currency_stats(
+    pool: &crate::database::DbPool,
+    account_book_id: i64,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Vec<CurrencyStat> {
+    sqlx::query_as::<_, CurrencyStat>(
+        r#"
+        SELECT
+            original_currency AS currency,
+            COALESCE(SUM(CASE WHEN `type` = 'income' THEN original_amount ELSE 0 END), 0) AS original_income,
+            COALESCE(SUM(CASE WHEN `type` = 'expense' THEN original_amount ELSE 0 END), 0) AS original_expense,
+            COALESCE(SUM(CASE WHEN `type` = 'income' THEN amount ELSE 0 END), 0) AS converted_income,
+            COALESCE(SUM(CASE WHEN `type` = 'expense' THEN amount ELSE 0 END), 0) AS converted_expense,
+            COUNT(*) AS transaction_count
+        FROM transactions
+        WHERE account_book_id = ? AND transaction_date BETWEEN ? AND ?
+        GROUP BY original_currency
+        ORDER BY original_currency
+        "#,
+    )
+    .bind(account_book_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_all(pool)
+    .await
+    .unwrap_or_default()
+}
+
+// 按标签汇总，支出多的排在前面
+async fn get_tag_stats(
+    pool: &crate::database::DbPool,
+    account_book_id: i64,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Vec<TagStat> {
+    let rows: Vec<(String, Decimal, Decimal, i64)> = sqlx::query_as(
+        r#"
+        SELECT
+            g.name,
+            COALESCE(SUM(CASE WHEN t.`type` = 'income' THEN t.amount ELSE 0 END), 0) AS income,
+            COALESCE(SUM(CASE WHEN t.`type` = 'expense' THEN t.amount ELSE 0 END), 0) AS expense,
+            COUNT(*) AS transaction_count
+        FROM tags g
+        JOIN transaction_tags tt ON tt.tag_id = g.id
+        JOIN transactions t ON t.id = tt.transaction_id
+        WHERE g.account_book_id = ? AND t.transaction_date BETWEEN ? AND ?
+        GROUP BY g.id, g.name
+        ORDER BY expense DESC, income DESC, g.name
+        "#,
+    )
+    .bind(account_book_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_all(pool)
+    .await
+    .unwrap_or_default();
+
+    rows.into_iter()
+        .map(|(name, income, expense, transaction_count)| TagStat {
+            filter_query: format!(
+                "tag={}&start_date={}&end_date={}",
+                urlencoding::encode(&name),
+                start_date.format("%Y-%m-%d"),
+                end_date.format("%Y-%m-%d")
+            ),
+            name,
+            income,
+            expense,
+            transaction_count,
+        })
+        .collect()
+}
+
 // 获取月度收支明细（基于账本起始日的月度周期）
 async fn get_monthly_details(
     pool: &crate::database::DbPool,
@@ -372,7 +712,7 @@ async fn get_monthly_details(
 }
 
 // 根据账本起始日计算当前周期的开始和结束日期
-fn calculate_current_cycle_dates(today: NaiveDate, cycle_start_day: i32) -> (NaiveDate, NaiveDate) {
+pub(crate) fn calculate_current_cycle_dates(today: NaiveDate, cycle_start_day: i32) -> (NaiveDate, NaiveDate) {
     let start_day = cycle_start_day as u32;
     
     // 当前月份的起始日
@@ -1,12 +1,16 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::Json,
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
 
 use crate::middleware::{AppState, CurrentUser};
-use crate::models::{AccountBook, User};
+use crate::models::{parse_tag_names, AccountBook, BookRole, Category, CreateTransaction, SplitLine, Tag, Transaction, TransactionFilter, TransactionSplit, TransactionWithCategory, User, Wallet};
+use crate::services::ExchangeRateService;
+use crate::handlers::transaction::closed_period_message;
 
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
@@ -99,4 +103,301 @@ pub async fn update_preference_by_path(
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
-}
\ No newline at end of file
+}
+#[derive(Serialize)]
+pub struct ApiAccountBook {
+    pub id: i64,
+    pub name: String,
+    pub currency: String,
+}
+
+#[derive(Serialize)]
+pub struct ApiCategory {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub category_type: String,
+}
+
+#[derive(Serialize)]
+pub struct ApiWallet {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub wallet_type: String,
+    pub opening_balance: Decimal,
+    pub balance: Decimal,
+}
+
+#[derive(Serialize)]
+pub struct ApiTag {
+    pub id: i64,
+    pub name: String,
+    pub transaction_count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ApiTransactionQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTransactionRequest {
+    // 传入 splits 时可以省略，取第一行拆分的分类
+    pub category_id: Option<i64>,
+    // 资金账户，不传时不指定账户
+    pub wallet_id: Option<i64>,
+    // original_currency 为空时即账本货币金额，否则为该货币下的原始金额
+    pub amount: Decimal,
+    pub original_currency: Option<String>,
+    // 外币交易未提供汇率时使用已导入的汇率
+    pub exchange_rate: Option<Decimal>,
+    #[serde(rename = "type")]
+    pub transaction_type: String,
+    pub description: Option<String>,
+    pub transaction_date: NaiveDate,
+    pub tags: Option<String>,
+    // 拆分明细，各行金额为原始货币，合计须等于 amount
+    #[serde(default)]
+    pub splits: Vec<SplitLine>,
+}
+
+type ApiError = (StatusCode, Json<ApiResponse<()>>);
+
+fn api_error(status: StatusCode, message: &str) -> ApiError {
+    (status, Json(ApiResponse {
+        success: false,
+        message: message.to_string(),
+        data: None,
+    }))
+}
+
+fn api_ok<T>(message: &str, data: T) -> Json<ApiResponse<T>> {
+    Json(ApiResponse {
+        success: true,
+        message: message.to_string(),
+        data: Some(data),
+    })
+}
+
+// 要求当前用户是账本成员且角色不低于 min_role
+async fn require_account_book(
+    app_state: &AppState,
+    account_book_id: i64,
+    user_id: i64,
+    min_role: BookRole,
+) -> Result<AccountBook, ApiError> {
+    match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user_id).await {
+        Ok(Some((account_book, role))) if role >= min_role => Ok(account_book),
+        Ok(Some(_)) => Err(api_error(StatusCode::FORBIDDEN, "当前角色无权执行此操作")),
+        Ok(None) => Err(api_error(StatusCode::NOT_FOUND, "账本不存在或无权限访问")),
+        Err(_) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载账本失败")),
+    }
+}
+
+// 账本列表
+pub async fn list_account_books(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiResponse<Vec<ApiAccountBook>>>, ApiError> {
+    let books = AccountBook::find_by_user(&app_state.db_pool, user.id)
+        .await
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载账本失败"))?;
+
+    let books = books
+        .into_iter()
+        .map(|book| ApiAccountBook { id: book.id, name: book.name, currency: book.currency })
+        .collect();
+
+    Ok(api_ok("ok", books))
+}
+
+// 账本分类列表，供脚本查找分类ID
+pub async fn list_categories(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiResponse<Vec<ApiCategory>>>, ApiError> {
+    require_account_book(&app_state, account_book_id, user.id, BookRole::Viewer).await?;
+
+    let categories = Category::find_by_account_book(&app_state.db_pool, account_book_id)
+        .await
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载分类失败"))?;
+
+    let categories = categories
+        .into_iter()
+        .map(|category| ApiCategory {
+            id: category.id,
+            name: category.name,
+            category_type: category.category_type,
+        })
+        .collect();
+
+    Ok(api_ok("ok", categories))
+}
+
+// 资金账户列表和当前余额
+pub async fn list_wallets(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiResponse<Vec<ApiWallet>>>, ApiError> {
+    require_account_book(&app_state, account_book_id, user.id, BookRole::Viewer).await?;
+
+    let wallets = Wallet::find_balances(&app_state.db_pool, account_book_id)
+        .await
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载账户失败"))?;
+
+    let wallets = wallets
+        .into_iter()
+        .map(|row| ApiWallet {
+            id: row.wallet.id,
+            name: row.wallet.name,
+            wallet_type: row.wallet.wallet_type,
+            opening_balance: row.wallet.opening_balance,
+            balance: row.balance,
+        })
+        .collect();
+
+    Ok(api_ok("ok", wallets))
+}
+
+// 标签列表，常用的排在前面
+pub async fn list_tags(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiResponse<Vec<ApiTag>>>, ApiError> {
+    require_account_book(&app_state, account_book_id, user.id, BookRole::Viewer).await?;
+
+    let tags = Tag::find_by_account_book_with_counts(&app_state.db_pool, account_book_id)
+        .await
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载标签失败"))?;
+
+    let tags = tags
+        .into_iter()
+        .map(|row| ApiTag {
+            id: row.tag.id,
+            name: row.tag.name,
+            transaction_count: row.transaction_count,
+        })
+        .collect();
+
+    Ok(api_ok("ok", tags))
+}
+
+// 交易记录列表
+pub async fn list_transactions(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    Query(query): Query<ApiTransactionQuery>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiResponse<Vec<TransactionWithCategory>>>, ApiError> {
+    require_account_book(&app_state, account_book_id, user.id, BookRole::Viewer).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let transactions = Transaction::find_by_account_book_with_category(&app_state.db_pool, account_book_id, &TransactionFilter::default(), limit, offset)
+        .await
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载交易记录失败"))?;
+
+    Ok(api_ok("ok", transactions))
+}
+
+// 创建交易记录
+pub async fn create_transaction(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Json(request): Json<CreateTransactionRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<Transaction>>), ApiError> {
+    let account_book = require_account_book(&app_state, account_book_id, user.id, BookRole::Editor).await?;
+
+    if request.amount <= Decimal::ZERO {
+        return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "金额必须大于0"));
+    }
+
+    if request.transaction_type != "income" && request.transaction_type != "expense" {
+        return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "交易类型无效"));
+    }
+
+    if account_book.is_closed(request.transaction_date) {
+        return Err(api_error(StatusCode::CONFLICT, &closed_period_message(account_book.closed_through)));
+    }
+
+    // 拆分时逐行验证分类
+    let category_ids: Vec<i64> = if request.splits.is_empty() {
+        match request.category_id {
+            Some(category_id) => vec![category_id],
+            None => return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "缺少分类")),
+        }
+    } else {
+        request.splits.iter().map(|line| line.category_id).collect()
+    };
+    for category_id in &category_ids {
+        match Category::find_by_id(&app_state.db_pool, *category_id).await {
+            Ok(Some(category)) if category.account_book_id == account_book_id => {
+                if category.category_type != request.transaction_type {
+                    return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "分类类型与交易类型不匹配"));
+                }
+            }
+            Ok(_) => return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "分类不存在或无权限访问")),
+            Err(_) => return Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载分类失败")),
+        }
+    }
+
+    if let Some(wallet_id) = request.wallet_id {
+        match Wallet::find_by_id(&app_state.db_pool, wallet_id).await {
+            Ok(Some(wallet)) if wallet.account_book_id == account_book_id => {}
+            Ok(_) => return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "账户不存在或无权限访问")),
+            Err(_) => return Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "加载账户失败")),
+        }
+    }
+
+    let original_currency = request
+        .original_currency
+        .map(|code| code.trim().to_uppercase())
+        .filter(|code| !code.is_empty())
+        .unwrap_or_else(|| account_book.currency.clone());
+    let amount = ExchangeRateService::convert(
+        &app_state.db_pool,
+        user.id,
+        &account_book.currency,
+        &original_currency,
+        request.amount,
+        request.exchange_rate,
+        request.transaction_date,
+    )
+    .await
+    .map_err(|e| api_error(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()))?;
+
+    let splits = if request.splits.is_empty() {
+        Vec::new()
+    } else {
+        TransactionSplit::allocate(&amount, &request.splits)
+            .map_err(|e| api_error(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()))?
+    };
+
+    let create_transaction = CreateTransaction {
+        account_book_id,
+        category_id: category_ids[0],
+        wallet_id: request.wallet_id,
+        amount: amount.amount,
+        original_currency: amount.original_currency,
+        original_amount: amount.original_amount,
+        exchange_rate: amount.exchange_rate,
+        transaction_type: request.transaction_type,
+        description: request.description.filter(|s| !s.trim().is_empty()),
+        transaction_date: request.transaction_date,
+        tags: parse_tag_names(request.tags.as_deref().unwrap_or_default()),
+        splits,
+        created_by: user.id,
+    };
+
+    match Transaction::create(&app_state.db_pool, create_transaction).await {
+        Ok(transaction) => Ok((StatusCode::CREATED, api_ok("交易记录创建成功", transaction))),
+        Err(_) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "创建交易记录失败")),
+    }
+}
@@ -3,19 +3,24 @@ use axum::{
     response::{Html, Redirect},
     Form,
 };
-use axum_extra::extract::cookie::{Cookie, CookieJar};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
 use askama::Template;
 use serde::Deserialize;
 use validator::Validate;
 
-use crate::middleware::AppState;
-use crate::utils::{RegisterForm, LoginForm};
+use crate::middleware::{AppState, ClientInfo, OptionalCurrentUser};
+use crate::models::{Session, User};
+use crate::services::{EmailVerification, OidcProviderLink, ThrottledError, VerificationExpiredError};
+use crate::utils::{
+    RegisterForm, LoginForm, ForgotPasswordForm, ResendVerificationForm, ResetPasswordForm, TwoFactorForm,
+};
 
 #[derive(Template)]
 #[template(path = "auth/login.html")]
 struct LoginTemplate {
     error: String,
     success: String,
+    oidc_providers: Vec<OidcProviderLink>,
 }
 
 #[derive(Template)]
@@ -30,6 +35,30 @@ struct RegisterTemplate {
 struct VerifyEmailTemplate {
     message: String,
     is_success: bool,
+    is_expired: bool,
+    // 注册验证链接过期时用于重新发送，修改邮箱的链接过期时为空
+    resend_email: String,
+}
+
+#[derive(Template)]
+#[template(path = "auth/two_factor.html")]
+struct TwoFactorTemplate {
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "auth/forgot_password.html")]
+struct ForgotPasswordTemplate {
+    error: String,
+    success: String,
+}
+
+#[derive(Template)]
+#[template(path = "auth/reset_password.html")]
+struct ResetPasswordTemplate {
+    token: String,
+    email: String,
+    error: String,
 }
 
 #[derive(Deserialize)]
@@ -38,10 +67,22 @@ pub struct AuthQuery {
     success: Option<String>,
 }
 
-pub async fn show_login(Query(query): Query<AuthQuery>) -> Html<String> {
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+pub async fn show_login(
+    State(app_state): State<AppState>,
+    Query(query): Query<AuthQuery>,
+) -> Html<String> {
     let template = LoginTemplate {
         error: query.error.unwrap_or_default(),
         success: query.success.unwrap_or_default(),
+        oidc_providers: app_state.oidc_service.provider_links(),
     };
     Html(template.render().unwrap())
 }
@@ -56,6 +97,7 @@ pub async fn show_register(Query(query): Query<AuthQuery>) -> Html<String> {
 
 pub async fn login(
     State(app_state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Form(form): Form<LoginForm>,
 ) -> Result<(CookieJar, Redirect), Redirect> {
@@ -63,8 +105,8 @@ pub async fn login(
     if let Err(errors) = form.validate() {
         let error_msg = errors
             .field_errors()
-            .iter()
-            .flat_map(|(_, v)| v.iter())
+            .values()
+            .flat_map(|v| v.iter())
             .next()
             .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
             .unwrap_or_else(|| "输入数据无效".to_string());
@@ -72,19 +114,147 @@ pub async fn login(
             urlencoding::encode(&error_msg))));
     }
 
+    let user = match app_state
+        .auth_service
+        .login(&app_state.db_pool, form.email, form.password, &client)
+        .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            let error_msg = e.to_string();
+            return Err(Redirect::to(&format!("/auth/login?error={}", 
+                urlencoding::encode(&error_msg))));
+        }
+    };
+
+    complete_login(&app_state, &client, jar, &user).await
+}
+
+// 第一步认证通过后：启用了两步验证的账户需要先输入动态验证码，否则直接创建会话
+async fn complete_login(
+    app_state: &AppState,
+    client: &ClientInfo,
+    jar: CookieJar,
+    user: &User,
+) -> Result<(CookieJar, Redirect), Redirect> {
+    if user.totp_enabled {
+        let pending_token = app_state
+            .auth_service
+            .generate_two_factor_token(user)
+            .map_err(|_| Redirect::to("/auth/login?error=登录失败，请重试"))?;
+        let cookie = Cookie::build(("pending_2fa", pending_token))
+            .path("/auth")
+            .http_only(true)
+            .max_age(time::Duration::minutes(5))
+            .build();
+
+        return Ok((jar.add(cookie), Redirect::to("/auth/two-factor")));
+    }
+
+    start_session(app_state, client, jar, user).await
+}
+
+// 跳转到身份提供方进行授权（授权码模式 + PKCE）
+pub async fn oidc_start(
+    State(app_state): State<AppState>,
+    Path(provider): Path<String>,
+    jar: CookieJar,
+) -> Result<(CookieJar, Redirect), Redirect> {
+    match app_state.oidc_service.authorization_url(&provider).await {
+        Ok((url, flow_token)) => {
+            // 回调是从身份提供方跳转回来的顶级导航，需要Lax才能带上cookie
+            let cookie = Cookie::build(("oidc_flow", flow_token))
+                .path("/auth/oidc")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .max_age(time::Duration::minutes(10))
+                .build();
+            Ok((jar.add(cookie), Redirect::to(&url)))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to start OIDC login with {}: {}", provider, e);
+            Err(Redirect::to("/auth/login?error=无法连接身份提供方，请稍后重试"))
+        }
+    }
+}
+
+// 身份提供方回调
+pub async fn oidc_callback(
+    State(app_state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<OidcCallbackQuery>,
+    client: ClientInfo,
+    jar: CookieJar,
+) -> Result<(CookieJar, Redirect), Redirect> {
+    let flow_token = jar
+        .get("oidc_flow")
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| Redirect::to("/auth/login?error=登录请求已过期，请重试"))?;
+    let jar = jar.add(
+        Cookie::build(("oidc_flow", ""))
+            .path("/auth/oidc")
+            .max_age(time::Duration::seconds(0))
+            .build(),
+    );
+
+    if let Some(error) = query.error {
+        let error_msg = query.error_description.unwrap_or(error);
+        return Err(Redirect::to(&format!("/auth/login?error={}",
+            urlencoding::encode(&format!("身份提供方登录失败：{}", error_msg)))));
+    }
+
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err(Redirect::to("/auth/login?error=登录请求无效，请重试"));
+    };
+
+    let allow_signup = app_state
+        .oidc_service
+        .provider(&provider)
+        .map(|config| config.allow_signup)
+        .map_err(|_| Redirect::to("/auth/login?error=未配置该登录方式"))?;
+
+    let identity = match app_state.oidc_service.complete(&provider, &code, &state, &flow_token).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            tracing::warn!("OIDC login with {} failed: {}", provider, e);
+            return Err(Redirect::to(&format!("/auth/login?error={}",
+                urlencoding::encode(&e.to_string()))));
+        }
+    };
+
     match app_state
         .auth_service
-        .login(&app_state.db_pool, form.email, form.password)
+        .login_with_oidc(&app_state.db_pool, &identity, allow_signup)
         .await
     {
-        Ok((_user, token)) => {
+        Ok(user) => complete_login(&app_state, &client, jar, &user).await,
+        Err(e) => Err(Redirect::to(&format!("/auth/login?error={}",
+            urlencoding::encode(&e.to_string())))),
+    }
+}
+
+// 创建会话并写入登录cookie
+async fn start_session(
+    app_state: &AppState,
+    client: &ClientInfo,
+    jar: CookieJar,
+    user: &User,
+) -> Result<(CookieJar, Redirect), Redirect> {
+    match app_state
+        .auth_service
+        .create_session(&app_state.db_pool, user, client)
+        .await
+    {
+        Ok(token) => {
             let cookie = Cookie::build(("auth_token", token))
                 .path("/")
                 .http_only(true)
                 .max_age(time::Duration::days(30))
                 .build();
             
-            Ok((jar.add(cookie), Redirect::to("/dashboard")))
+            // 处于注销冷静期的账户先进入账户信息页，提示可撤销注销
+            let target = if user.deletion_scheduled_at.is_some() { "/profile/account" } else { "/dashboard" };
+            Ok((jar.add(cookie), Redirect::to(target)))
         }
         Err(e) => {
             let error_msg = e.to_string();
@@ -94,6 +264,76 @@ pub async fn login(
     }
 }
 
+pub async fn show_two_factor(
+    jar: CookieJar,
+    Query(query): Query<AuthQuery>,
+) -> Result<Html<String>, Redirect> {
+    if jar.get("pending_2fa").is_none() {
+        return Err(Redirect::to("/auth/login?error=验证已超时，请重新登录"));
+    }
+
+    let template = TwoFactorTemplate {
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn verify_two_factor(
+    State(app_state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Form(form): Form<TwoFactorForm>,
+) -> Result<(CookieJar, Redirect), Redirect> {
+    let pending_token = jar
+        .get("pending_2fa")
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| Redirect::to("/auth/login?error=验证已超时，请重新登录"))?;
+
+    let user = match app_state
+        .auth_service
+        .verify_two_factor_token(&app_state.db_pool, &pending_token)
+        .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            let error_msg = e.to_string();
+            return Err(Redirect::to(&format!("/auth/login?error={}", 
+                urlencoding::encode(&error_msg))));
+        }
+    };
+
+    // 动态验证码错误同样计入登录失败次数
+    if let Err(e) = app_state
+        .auth_service
+        .check_login_allowed(&app_state.db_pool, &user.email, &client)
+        .await
+    {
+        let error_msg = e.to_string();
+        return Err(Redirect::to(&format!("/auth/two-factor?error={}", 
+            urlencoding::encode(&error_msg))));
+    }
+
+    match app_state
+        .auth_service
+        .verify_second_factor(&app_state.db_pool, &user, &form.code)
+        .await
+    {
+        Ok(true) => {
+            app_state.auth_service.clear_login_failures(&app_state.db_pool, &user.email).await;
+            let cleared = Cookie::build(("pending_2fa", ""))
+                .path("/auth")
+                .max_age(time::Duration::seconds(0))
+                .build();
+            start_session(&app_state, &client, jar.add(cleared), &user).await
+        }
+        Ok(false) => {
+            app_state.auth_service.record_login_failure(&app_state.db_pool, &user.email, &client).await;
+            Err(Redirect::to("/auth/two-factor?error=验证码错误"))
+        }
+        Err(_) => Err(Redirect::to("/auth/two-factor?error=验证失败，请重试")),
+    }
+}
+
 pub async fn register(
     State(app_state): State<AppState>,
     Form(form): Form<RegisterForm>,
@@ -136,24 +376,53 @@ pub async fn verify_email(
     Path(token): Path<String>,
 ) -> Html<String> {
     match app_state.auth_service.verify_email(&app_state.db_pool, &token).await {
-        Ok(()) => {
+        Ok(verification) => {
+            let message = match verification {
+                EmailVerification::Registration { joined_books } if joined_books.is_empty() => {
+                    "邮箱验证成功！现在您可以登录了。".to_string()
+                }
+                EmailVerification::Registration { joined_books } => {
+                    format!("邮箱验证成功！您已加入账本「{}」，登录后即可查看。", joined_books.join("」「"))
+                }
+                EmailVerification::EmailChanged { new_email } => {
+                    format!("邮箱已修改为 {}，请使用新邮箱登录。", new_email)
+                }
+            };
             let template = VerifyEmailTemplate {
-                message: "邮箱验证成功！现在您可以登录了。".to_string(),
+                message,
                 is_success: true,
+                is_expired: false,
+                resend_email: String::new(),
             };
             Html(template.render().unwrap())
         }
         Err(e) => {
+            let expired = e.downcast_ref::<VerificationExpiredError>();
             let template = VerifyEmailTemplate {
                 message: format!("验证失败：{}", e),
                 is_success: false,
+                is_expired: expired.is_some(),
+                resend_email: expired
+                    .and_then(|expired| expired.resend_email.clone())
+                    .unwrap_or_default(),
             };
             Html(template.render().unwrap())
         }
     }
 }
 
-pub async fn logout(jar: CookieJar) -> (CookieJar, Redirect) {
+pub async fn logout(
+    State(app_state): State<AppState>,
+    OptionalCurrentUser(user): OptionalCurrentUser,
+    jar: CookieJar,
+) -> (CookieJar, Redirect) {
+    // 删除服务端会话，使令牌立即失效
+    if let Some(user) = user {
+        if let Err(e) = Session::delete(&app_state.db_pool, &user.session_id, user.id).await {
+            tracing::warn!("Failed to delete session on logout: {}", e);
+        }
+    }
+
     // 创建一个过期的cookie来清除auth_token
     let cookie = Cookie::build(("auth_token", ""))
         .path("/")
@@ -168,11 +437,16 @@ pub async fn logout(jar: CookieJar) -> (CookieJar, Redirect) {
 
 pub async fn resend_verification(
     State(app_state): State<AppState>,
-    Form(form): Form<LoginForm>,
+    client: ClientInfo,
+    Form(form): Form<ResendVerificationForm>,
 ) -> Redirect {
+    if form.validate().is_err() {
+        return Redirect::to("/auth/login?error=请输入有效的邮箱地址");
+    }
+
     match app_state
         .auth_service
-        .resend_verification(&app_state.db_pool, form.email)
+        .resend_verification(&app_state.db_pool, form.email, &client)
         .await
     {
         Ok(()) => Redirect::to("/auth/login?success=验证邮件已重新发送"),
@@ -182,4 +456,95 @@ pub async fn resend_verification(
                 urlencoding::encode(&error_msg)))
         }
     }
-}
\ No newline at end of file
+}
+
+pub async fn show_forgot_password(Query(query): Query<AuthQuery>) -> Html<String> {
+    let template = ForgotPasswordTemplate {
+        error: query.error.unwrap_or_default(),
+        success: query.success.unwrap_or_default(),
+    };
+    Html(template.render().unwrap())
+}
+
+pub async fn forgot_password(
+    State(app_state): State<AppState>,
+    client: ClientInfo,
+    Form(form): Form<ForgotPasswordForm>,
+) -> Redirect {
+    if form.validate().is_err() {
+        return Redirect::to("/auth/forgot-password?error=请输入有效的邮箱地址");
+    }
+
+    match app_state
+        .auth_service
+        .request_password_reset(&app_state.db_pool, form.email, &client)
+        .await
+    {
+        Ok(()) => Redirect::to("/auth/forgot-password?success=如果该邮箱已注册，我们已向其发送密码重置链接"),
+        Err(e) if e.is::<ThrottledError>() => {
+            Redirect::to(&format!("/auth/forgot-password?error={}",
+                urlencoding::encode(&e.to_string())))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to send password reset email: {}", e);
+            Redirect::to("/auth/forgot-password?error=重置邮件发送失败，请稍后重试")
+        }
+    }
+}
+
+pub async fn show_reset_password(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+    Query(query): Query<AuthQuery>,
+) -> Result<Html<String>, Redirect> {
+    match app_state
+        .auth_service
+        .validate_password_reset_token(&app_state.db_pool, &token)
+        .await
+    {
+        Ok(user) => {
+            let template = ResetPasswordTemplate {
+                token,
+                email: user.email,
+                error: query.error.unwrap_or_default(),
+            };
+            Ok(Html(template.render().unwrap()))
+        }
+        Err(e) => {
+            let error_msg = e.to_string();
+            Err(Redirect::to(&format!("/auth/forgot-password?error={}", 
+                urlencoding::encode(&error_msg))))
+        }
+    }
+}
+
+pub async fn reset_password(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+    Form(form): Form<ResetPasswordForm>,
+) -> Redirect {
+    if let Err(errors) = form.validate() {
+        let error_msg = errors
+            .field_errors()
+            .values()
+            .flat_map(|v| v.iter())
+            .next()
+            .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
+            .unwrap_or_else(|| "输入数据无效".to_string());
+        return Redirect::to(&format!("/auth/reset-password/{}?error={}", 
+            urlencoding::encode(&token), urlencoding::encode(&error_msg)));
+    }
+
+    match app_state
+        .auth_service
+        .reset_password(&app_state.db_pool, &token, form.password)
+        .await
+    {
+        Ok(()) => Redirect::to("/auth/login?success=密码已重置，请使用新密码登录"),
+        Err(e) => {
+            let error_msg = e.to_string();
+            Redirect::to(&format!("/auth/forgot-password?error={}", 
+                urlencoding::encode(&error_msg)))
+        }
+    }
+}
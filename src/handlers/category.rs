@@ -9,7 +9,8 @@ use validator::Validate;
 use chrono::{DateTime, Utc};
 
 use crate::middleware::{AppState, CurrentUser};
-use crate::models::{AccountBook, Category, CreateCategory};
+use crate::models::{AccountBook, BookRole, Category, CategoryTemplate, CreateCategory};
+use crate::utils::SaveCategoryTemplateForm;
 
 #[derive(Template)]
 #[template(path = "categories/list.html")]
@@ -18,6 +19,8 @@ struct CategoryListTemplate {
     account_book: AccountBookDisplay,
     income_categories: Vec<CategoryDisplay>,
     expense_categories: Vec<CategoryDisplay>,
+    category_templates: Vec<CategoryTemplateDisplay>,
+    can_edit: bool,
     success: String,
     error: String,
 }
@@ -47,6 +50,13 @@ pub struct AccountBookDisplay {
     pub currency: String,
 }
 
+// 当前用户保存的分类模板
+pub struct CategoryTemplateDisplay {
+    pub id: i64,
+    pub name: String,
+    pub updated_at: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct CategoryDisplay {
     pub id: i64,
@@ -128,9 +138,9 @@ pub async fn list(
     State(app_state): State<AppState>,
     Query(query): Query<CategoryQuery>,
 ) -> Result<Html<String>, Redirect> {
-    // 验证账本权限
-    let account_book = match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
-        Ok(Some(book)) => book,
+    // 验证账本成员身份
+    let (account_book, role) = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some(pair)) => pair,
         Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
         Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
     };
@@ -150,11 +160,24 @@ pub async fn list(
                 }
             }
 
+            let category_templates = CategoryTemplate::find_by_user(&app_state.db_pool, user.id)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .map(|template| CategoryTemplateDisplay {
+                    id: template.id,
+                    name: template.name,
+                    updated_at: template.updated_at.format("%Y-%m-%d").to_string(),
+                })
+                .collect();
+
             let template = CategoryListTemplate {
                 user,
                 account_book: AccountBookDisplay::from(account_book),
                 income_categories,
                 expense_categories,
+                category_templates,
+                can_edit: role.can_edit(),
                 success: query.success.unwrap_or_default(),
                 error: query.error.unwrap_or_default(),
             };
@@ -171,10 +194,10 @@ pub async fn show_new(
     State(app_state): State<AppState>,
     Query(query): Query<CategoryQuery>,
 ) -> Result<Html<String>, Redirect> {
-    // 验证账本权限
-    let account_book = match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能管理分类
+    let account_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(book)) => book,
-        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限编辑")),
         Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
     };
 
@@ -193,10 +216,10 @@ pub async fn create(
     State(app_state): State<AppState>,
     Form(form): Form<CategoryForm>,
 ) -> Redirect {
-    // 验证账本权限
-    match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能管理分类
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(_)) => {}
-        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
         Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
     }
 
@@ -249,10 +272,10 @@ pub async fn show_edit(
     State(app_state): State<AppState>,
     Query(query): Query<CategoryQuery>,
 ) -> Result<Html<String>, Redirect> {
-    // 验证账本权限
-    let account_book = match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能管理分类
+    let account_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(book)) => book,
-        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限编辑")),
         Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
     };
 
@@ -284,10 +307,10 @@ pub async fn update(
     State(app_state): State<AppState>,
     Form(form): Form<UpdateCategoryForm>,
 ) -> Redirect {
-    // 验证账本权限
-    match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能管理分类
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(_)) => {}
-        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
         Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
     }
 
@@ -344,10 +367,10 @@ pub async fn delete(
     Path((account_book_id, category_id)): Path<(i64, i64)>,
     State(app_state): State<AppState>,
 ) -> Redirect {
-    // 验证账本权限
-    match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能管理分类
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(_)) => {}
-        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
         Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
     }
 
@@ -362,11 +385,16 @@ pub async fn delete(
         Err(_) => return Redirect::to(&format!("/account-books/{}/categories?error=获取分类信息失败", account_book_id)),
     }
 
-    // 检查是否有交易记录使用该分类
+    // 检查是否有交易记录（包括拆分明细）使用该分类
     let transaction_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM transactions WHERE category_id = ?"
+        r#"
+        SELECT COUNT(*) FROM transactions t
+        WHERE t.category_id = ?
+            OR EXISTS (SELECT 1 FROM transaction_splits s WHERE s.transaction_id = t.id AND s.category_id = ?)
+        "#
     )
     .bind(category_id)
+    .bind(category_id)
     .fetch_one(&app_state.db_pool)
     .await
     .unwrap_or(0);
@@ -378,6 +406,20 @@ pub async fn delete(
         ));
     }
 
+    // 重复记账规则使用的分类也不能删除
+    let recurring_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM recurring_transactions WHERE category_id = ?")
+        .bind(category_id)
+        .fetch_one(&app_state.db_pool)
+        .await
+        .unwrap_or(0);
+
+    if recurring_count > 0 {
+        return Redirect::to(&format!(
+            "/account-books/{}/categories?error=无法删除分类，有{}条重复记账规则使用该分类",
+            account_book_id, recurring_count
+        ));
+    }
+
     // 删除分类
     match Category::delete(&app_state.db_pool, category_id).await {
         Ok(_) => Redirect::to(&format!("/account-books/{}/categories?success=分类删除成功！", account_book_id)),
@@ -396,13 +438,13 @@ pub async fn update_sort_orders(
     State(app_state): State<AppState>,
     axum::Json(request): axum::Json<UpdateSortOrdersRequest>,
 ) -> axum::response::Result<axum::Json<serde_json::Value>, axum::response::Response> {
-    // 验证账本权限
-    match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能调整排序
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(_)) => {},
         Ok(None) => return Err(axum::response::Response::builder()
             .status(404)
             .header("Content-Type", "application/json")
-            .body("Account book not found or no access permission".into())
+            .body("Account book not found or no edit permission".into())
             .unwrap()),
         Err(_) => return Err(axum::response::Response::builder()
             .status(500)
@@ -453,4 +495,50 @@ pub async fn update_sort_orders(
                 .unwrap())
         }
     }
-}
\ No newline at end of file
+}
+
+// 把账本当前的分类保存为个人模板，任意成员均可保存
+pub async fn save_template(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<SaveCategoryTemplateForm>,
+) -> Redirect {
+    match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限访问"),
+        Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
+    }
+
+    let url = format!("/account-books/{}/categories", account_book_id);
+    let name = form.name.trim().to_string();
+
+    if form.validate().is_err() || name.is_empty() {
+        return Redirect::to(&format!("{}?error=模板名称长度必须在1-50字符之间", url));
+    }
+
+    match CategoryTemplate::save_from_account_book(&app_state.db_pool, user.id, &name, account_book_id).await {
+        Ok(0) => Redirect::to(&format!("{}?error=当前账本没有可保存的分类", url)),
+        Ok(count) => Redirect::to(&format!(
+            "{}?success={}",
+            url,
+            urlencoding::encode(&format!("已将{}个分类保存为模板「{}」", count, name))
+        )),
+        Err(_) => Redirect::to(&format!("{}?error=保存分类模板失败", url)),
+    }
+}
+
+// 删除自己保存的分类模板，不影响已用它创建的账本
+pub async fn delete_template(
+    user: CurrentUser,
+    Path((account_book_id, template_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let url = format!("/account-books/{}/categories", account_book_id);
+
+    match CategoryTemplate::delete(&app_state.db_pool, template_id, user.id).await {
+        Ok(true) => Redirect::to(&format!("{}?success=分类模板已删除", url)),
+        Ok(false) => Redirect::to(&format!("{}?error=分类模板不存在", url)),
+        Err(_) => Redirect::to(&format!("{}?error=删除分类模板失败", url)),
+    }
+}
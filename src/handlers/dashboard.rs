@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum::{
     extract::{Query, State},
     response::{Html, Redirect},
@@ -9,25 +11,74 @@ use rust_decimal::Decimal;
 use rust_decimal::prelude::ToPrimitive;
 
 use crate::middleware::{CurrentUser, OptionalCurrentUser, AppState};
-use crate::models::{AccountBook, Transaction, TransactionWithCategory, User};
+use crate::models::{wallet_type_icon, AccountBook, ExchangeRate, Transaction, TransactionFilter, TransactionWithCategory, User, UserPreferences, Wallet};
+use crate::utils::{currency_symbol, is_valid_currency_code};
+use crate::handlers::account_book_reports::{self, calculate_current_cycle_dates};
+use crate::handlers::profile::SelectOption;
+use crate::handlers::transaction::currency_options;
 
 #[derive(Template)]
 #[template(path = "dashboard/index.html")]
 struct DashboardTemplate {
     user: CurrentUser,
+    display_name: String,
     account_books: Vec<AccountBookDisplay>,
     selected_book: Option<AccountBookDisplay>,
+    currency_symbol: String,
     monthly_stats: MonthlyStats,
     recent_transactions: Vec<TransactionDisplay>,
     category_stats: Vec<CategoryStat>,
+    wallet_balances: Vec<WalletBalance>,
+    total_wallet_balance: Decimal,
     success: String,
     error: String,
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
-struct IndexTemplate {
-    user: Option<CurrentUser>,
+struct IndexTemplate;
+
+#[derive(Template)]
+#[template(path = "dashboard/overview.html")]
+struct OverviewTemplate {
+    user: CurrentUser,
+    reporting_currency: String,
+    currency_symbol: String,
+    currency_options: Vec<SelectOption>,
+    start_date: String,
+    end_date: String,
+    start_label: String,
+    end_label: String,
+    // 快捷区间：本月和今年
+    month_start_date: String,
+    month_end_date: String,
+    year_start_date: String,
+    year_end_date: String,
+    total_income: Decimal,
+    total_expense: Decimal,
+    balance: Decimal,
+    is_positive: bool,
+    books: Vec<BookOverview>,
+    expense_categories: Vec<account_book_reports::CategoryStat>,
+    // 缺少汇率、未计入合计的账本货币
+    missing_currencies: Vec<String>,
+    error: String,
+}
+
+// 单个账本在统计区间内的收支，折算金额为报告货币
+#[derive(Debug, Serialize)]
+pub struct BookOverview {
+    pub id: i64,
+    pub name: String,
+    pub currency: String,
+    pub currency_symbol: String,
+    pub income: Decimal,
+    pub expense: Decimal,
+    pub exchange_rate: Option<Decimal>,
+    pub converted_income: Decimal,
+    pub converted_expense: Decimal,
+    pub converted_balance: Decimal,
+    pub expense_percentage: i32,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -55,9 +106,24 @@ pub struct TransactionDisplay {
     pub transaction_type: String,
     pub description: String,
     pub transaction_date: NaiveDate,
+    pub date_label: String,
     pub category_name: String,
     pub category_icon: String,
     pub category_color: String,
+    pub wallet_name: String,
+    // 该笔交易后所在账户的余额
+    pub wallet_balance: Option<Decimal>,
+}
+
+// 资金账户的当前余额
+#[derive(Debug, Serialize)]
+pub struct WalletBalance {
+    pub id: i64,
+    pub name: String,
+    pub icon: String,
+    pub color: String,
+    pub balance: Decimal,
+    pub is_negative: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -70,6 +136,17 @@ pub struct CategoryStat {
     pub icon: String,
 }
 
+// 分类统计查询结果：(名称, 图标, 颜色, 金额, 笔数)
+type CategoryStatRow = (String, Option<String>, Option<String>, Decimal, i64);
+
+#[derive(Deserialize)]
+pub struct OverviewQuery {
+    start_date: Option<String>,
+    end_date: Option<String>,
+    currency: Option<String>,
+    error: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct DashboardQuery {
     book_id: Option<i64>,
@@ -96,9 +173,13 @@ impl From<TransactionWithCategory> for TransactionDisplay {
             transaction_type: t.transaction_type,
             description: t.description.unwrap_or_default(),
             transaction_date: t.transaction_date,
-            category_name: t.category_name,
+            date_label: t.transaction_date.format("%Y-%m-%d").to_string(),
+            // 拆分交易显示各行的分类
+            category_name: t.split_categories.unwrap_or(t.category_name),
             category_icon: t.category_icon.unwrap_or("tag".to_string()),
             category_color: t.category_color.unwrap_or("#007bff".to_string()),
+            wallet_name: t.wallet_name.unwrap_or_default(),
+            wallet_balance: t.wallet_balance,
         }
     }
 }
@@ -119,8 +200,14 @@ pub async fn dashboard(
         Ok(Some(u)) => u,
         _ => return Err(Redirect::to("/auth/login?error=用户信息获取失败")),
     };
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+
+    // 设置了默认账本时优先打开默认账本，否则打开上次选择的账本
+    let preferred_book_id = preferences
+        .default_account_book_id
+        .or(user_info.last_selected_account_book_id);
 
-    // 确定选中的账本（优先级：URL参数 > 数据库偏好 > 第一个账本）
+    // 确定选中的账本（优先级：URL参数 > 默认账本 > 上次选择 > 第一个账本）
     let selected_book = if let Some(book_id) = query.book_id {
         // 1. URL参数指定的账本
         match AccountBook::find_by_id(&app_state.db_pool, book_id, user.id).await {
@@ -131,12 +218,12 @@ pub async fn dashboard(
             }
             _ => {
                 // URL参数指定的账本无效，使用数据库偏好或第一个账本
-                get_fallback_account_book(&account_books, &user_info).await
+                get_fallback_account_book(&account_books, preferred_book_id).await
             }
         }
-    } else if let Some(last_book_id) = user_info.last_selected_account_book_id {
+    } else if let Some(preferred_book_id) = preferred_book_id {
         // 2. 数据库中存储的用户偏好
-        match AccountBook::find_by_id(&app_state.db_pool, last_book_id, user.id).await {
+        match AccountBook::find_by_id(&app_state.db_pool, preferred_book_id, user.id).await {
             Ok(Some(book)) => Some(AccountBookDisplay::from(book)),
             _ => {
                 // 偏好的账本已不存在，使用第一个账本并更新偏好
@@ -157,11 +244,12 @@ pub async fn dashboard(
     };
 
     // 获取统计数据
-    let (monthly_stats, recent_transactions, category_stats) = if let Some(ref book) = selected_book {
-        let stats = get_monthly_stats(&app_state.db_pool, book.id).await;
-        let transactions = get_recent_transactions(&app_state.db_pool, book.id).await;
-        let cat_stats = get_category_stats(&app_state.db_pool, book.id).await;
-        (stats, transactions, cat_stats)
+    let (monthly_stats, recent_transactions, category_stats, wallet_balances) = if let Some(ref book) = selected_book {
+        let stats = get_monthly_stats(&app_state.db_pool, book.id, &preferences).await;
+        let transactions = get_recent_transactions(&app_state.db_pool, book.id, &preferences).await;
+        let cat_stats = get_category_stats(&app_state.db_pool, book.id, &preferences).await;
+        let balances = get_wallet_balances(&app_state.db_pool, book.id).await;
+        (stats, transactions, cat_stats, balances)
     } else {
         (
             MonthlyStats {
@@ -169,21 +257,33 @@ pub async fn dashboard(
                 expense: Decimal::ZERO,
                 balance: Decimal::ZERO,
                 transaction_count: 0,
-                month_name: get_current_month_name(),
+                month_name: preferences.month_name(preferences.today().month()),
                 is_positive: true,
             },
             Vec::new(),
             Vec::new(),
+            Vec::new(),
         )
     };
 
+    let currency_symbol = currency_symbol(
+        selected_book
+            .as_ref()
+            .map(|book| book.currency.as_str())
+            .unwrap_or(&preferences.default_currency),
+    );
+
     let template = DashboardTemplate {
+        display_name: preferences.display_name_or(&user.email).to_string(),
         user,
         account_books,
         selected_book,
+        currency_symbol,
         monthly_stats,
         recent_transactions,
         category_stats,
+        total_wallet_balance: wallet_balances.iter().map(|wallet| wallet.balance).sum(),
+        wallet_balances,
         success: query.success.unwrap_or_default(),
         error: query.error.unwrap_or_default(),
     };
@@ -194,9 +294,9 @@ pub async fn dashboard(
 // 获取备选账本（用于用户偏好账本不存在时的回退逻辑）
 async fn get_fallback_account_book(
     account_books: &[AccountBookDisplay], 
-    user_info: &User
+    preferred_book_id: Option<i64>,
 ) -> Option<AccountBookDisplay> {
-    if let Some(last_book_id) = user_info.last_selected_account_book_id {
+    if let Some(last_book_id) = preferred_book_id {
         // 检查偏好的账本是否仍在账本列表中
         if let Some(book) = account_books.iter().find(|b| b.id == last_book_id) {
             return Some(book.clone());
@@ -206,8 +306,12 @@ async fn get_fallback_account_book(
     account_books.first().cloned()
 }
 
-async fn get_monthly_stats(pool: &crate::database::DbPool, account_book_id: i64) -> MonthlyStats {
-    let now = chrono::Utc::now().naive_utc().date();
+async fn get_monthly_stats(
+    pool: &crate::database::DbPool,
+    account_book_id: i64,
+    preferences: &UserPreferences,
+) -> MonthlyStats {
+    let now = preferences.today();
     let start_date = NaiveDate::from_ymd_opt(now.year(), now.month(), 1).unwrap();
     let end_date = if now.month() == 12 {
         NaiveDate::from_ymd_opt(now.year() + 1, 1, 1).unwrap() - chrono::Duration::days(1)
@@ -234,22 +338,53 @@ async fn get_monthly_stats(pool: &crate::database::DbPool, account_book_id: i64)
         expense,
         balance: income - expense,
         transaction_count,
-        month_name: get_current_month_name(),
+        month_name: preferences.month_name(now.month()),
         is_positive: income >= expense,
     }
 }
 
-async fn get_recent_transactions(pool: &crate::database::DbPool, account_book_id: i64) -> Vec<TransactionDisplay> {
-    Transaction::find_by_account_book_with_category(pool, account_book_id, 10, 0)
+async fn get_recent_transactions(
+    pool: &crate::database::DbPool,
+    account_book_id: i64,
+    preferences: &UserPreferences,
+) -> Vec<TransactionDisplay> {
+    Transaction::find_by_account_book_with_category(pool, account_book_id, &TransactionFilter::default(), 10, 0)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|t| {
+            let mut display = TransactionDisplay::from(t);
+            display.date_label = preferences.format_date(display.transaction_date);
+            display
+        })
+        .collect()
+}
+
+async fn get_wallet_balances(
+    pool: &crate::database::DbPool,
+    account_book_id: i64,
+) -> Vec<WalletBalance> {
+    Wallet::find_balances(pool, account_book_id)
         .await
         .unwrap_or_default()
         .into_iter()
-        .map(TransactionDisplay::from)
+        .map(|row| WalletBalance {
+            id: row.wallet.id,
+            icon: wallet_type_icon(&row.wallet.wallet_type).to_string(),
+            name: row.wallet.name,
+            color: row.wallet.color.unwrap_or_else(|| "#34c759".to_string()),
+            is_negative: row.balance < Decimal::ZERO,
+            balance: row.balance,
+        })
         .collect()
 }
 
-async fn get_category_stats(pool: &crate::database::DbPool, account_book_id: i64) -> Vec<CategoryStat> {
-    let now = chrono::Utc::now().naive_utc().date();
+async fn get_category_stats(
+    pool: &crate::database::DbPool,
+    account_book_id: i64,
+    preferences: &UserPreferences,
+) -> Vec<CategoryStat> {
+    let now = preferences.today();
     let start_date = NaiveDate::from_ymd_opt(now.year(), now.month(), 1).unwrap();
     let end_date = if now.month() == 12 {
         NaiveDate::from_ymd_opt(now.year() + 1, 1, 1).unwrap() - chrono::Duration::days(1)
@@ -257,13 +392,17 @@ async fn get_category_stats(pool: &crate::database::DbPool, account_book_id: i64
         NaiveDate::from_ymd_opt(now.year(), now.month() + 1, 1).unwrap() - chrono::Duration::days(1)
     };
 
-    let result: Vec<(String, Option<String>, Option<String>, Decimal, i64)> = sqlx::query_as(
+    let result: Vec<CategoryStatRow> = sqlx::query_as(
         r#"
-        SELECT c.name, c.icon, c.color, SUM(t.amount) as total_amount, COUNT(t.id) as transaction_count
+        SELECT c.name, c.icon, c.color, SUM(l.amount) as total_amount, COUNT(DISTINCT l.id) as transaction_count
         FROM categories c
-        LEFT JOIN transactions t ON c.id = t.category_id 
-            AND t.`type` = 'expense' 
-            AND t.transaction_date BETWEEN ? AND ?
+        LEFT JOIN (
+            -- 拆分交易按各行的分类和金额计入
+            SELECT t.id, COALESCE(s.category_id, t.category_id) as category_id, COALESCE(s.amount, t.amount) as amount
+            FROM transactions t
+            LEFT JOIN transaction_splits s ON s.transaction_id = t.id
+            WHERE t.account_book_id = ? AND t.`type` = 'expense' AND t.transaction_date BETWEEN ? AND ?
+        ) l ON c.id = l.category_id
         WHERE c.account_book_id = ? AND c.`type` = 'expense' AND c.is_active = TRUE
         GROUP BY c.id, c.name, c.icon, c.color
         HAVING total_amount > 0
@@ -271,6 +410,7 @@ async fn get_category_stats(pool: &crate::database::DbPool, account_book_id: i64
         LIMIT 10
         "#
     )
+    .bind(account_book_id)
     .bind(start_date)
     .bind(end_date)
     .bind(account_book_id)
@@ -301,20 +441,165 @@ async fn get_category_stats(pool: &crate::database::DbPool, account_book_id: i64
         .collect()
 }
 
-fn get_current_month_name() -> String {
-    let now = chrono::Utc::now().naive_utc().date();
-    let month_names = [
-        "", "一月", "二月", "三月", "四月", "五月", "六月",
-        "七月", "八月", "九月", "十月", "十一月", "十二月"
-    ];
-    format!("{}月", month_names.get(now.month() as usize).unwrap_or(&"未知"))
+// 跨账本汇总：把各账本的收支按汇率折算为报告货币后合计
+pub async fn overview(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<OverviewQuery>,
+) -> Result<Html<String>, Redirect> {
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let today = preferences.today();
+
+    // 默认统计本月
+    let (start_date, end_date) = match (query.start_date.as_ref(), query.end_date.as_ref()) {
+        (Some(start), Some(end)) => {
+            let start = NaiveDate::parse_from_str(start, "%Y-%m-%d")
+                .map_err(|_| Redirect::to("/dashboard/overview?error=起始日期格式错误"))?;
+            let end = NaiveDate::parse_from_str(end, "%Y-%m-%d")
+                .map_err(|_| Redirect::to("/dashboard/overview?error=结束日期格式错误"))?;
+            if start > end {
+                return Err(Redirect::to("/dashboard/overview?error=起始日期不能晚于结束日期"));
+            }
+            (start, end)
+        }
+        _ => calculate_current_cycle_dates(today, 1),
+    };
+
+    let reporting_currency = query
+        .currency
+        .map(|currency| currency.trim().to_uppercase())
+        .filter(|currency| is_valid_currency_code(currency))
+        .unwrap_or_else(|| preferences.default_currency.clone());
+
+    // 使用区间结束日（不晚于今天）当天或之前最近的汇率
+    let rate_date = end_date.min(today);
+
+    let account_books = AccountBook::find_by_user(&app_state.db_pool, user.id)
+        .await
+        .unwrap_or_default();
+
+    let mut books = Vec::new();
+    let mut missing_currencies: Vec<String> = Vec::new();
+    let mut category_totals: HashMap<String, account_book_reports::CategoryStat> = HashMap::new();
+
+    for book in account_books {
+        let exchange_rate = if book.currency == reporting_currency {
+            Some(Decimal::ONE)
+        } else {
+            ExchangeRate::find_rate(&app_state.db_pool, user.id, &book.currency, &reporting_currency, rate_date)
+                .await
+                .unwrap_or(None)
+        };
+
+        let (income, expense) = Transaction::get_monthly_summary(&app_state.db_pool, book.id, start_date, end_date)
+            .await
+            .unwrap_or((Decimal::ZERO, Decimal::ZERO));
+
+        let rate = match exchange_rate {
+            Some(rate) => rate,
+            None => {
+                if !missing_currencies.contains(&book.currency) {
+                    missing_currencies.push(book.currency.clone());
+                }
+                Decimal::ZERO
+            }
+        };
+
+        // 支出分类按名称跨账本合并，每个账本取全部分类，合并后再取前10个
+        if exchange_rate.is_some() {
+            let stats = account_book_reports::get_category_stats(&app_state.db_pool, book.id, "expense", start_date, end_date, None).await;
+            for stat in stats {
+                let amount = (stat.amount * rate).round_dp(2);
+                let entry = category_totals
+                    .entry(stat.name.clone())
+                    .or_insert(account_book_reports::CategoryStat {
+                        name: stat.name,
+                        amount: Decimal::ZERO,
+                        percentage: 0,
+                        color: stat.color,
+                        transaction_count: 0,
+                    });
+                entry.amount += amount;
+                entry.transaction_count += stat.transaction_count;
+            }
+        }
+
+        let converted_income = (income * rate).round_dp(2);
+        let converted_expense = (expense * rate).round_dp(2);
+
+        books.push(BookOverview {
+            id: book.id,
+            name: book.name,
+            currency_symbol: currency_symbol(&book.currency),
+            currency: book.currency,
+            income,
+            expense,
+            exchange_rate,
+            converted_income,
+            converted_expense,
+            converted_balance: converted_income - converted_expense,
+            expense_percentage: 0,
+        });
+    }
+
+    let total_income: Decimal = books.iter().map(|book| book.converted_income).sum();
+    let total_expense: Decimal = books.iter().map(|book| book.converted_expense).sum();
+
+    for book in books.iter_mut() {
+        book.expense_percentage = percentage_of(book.converted_expense, total_expense);
+    }
+
+    let mut expense_categories: Vec<account_book_reports::CategoryStat> = category_totals.into_values().collect();
+    expense_categories.sort_by(|a, b| b.amount.cmp(&a.amount).then_with(|| a.name.cmp(&b.name)));
+    expense_categories.truncate(10);
+    for stat in expense_categories.iter_mut() {
+        stat.percentage = percentage_of(stat.amount, total_expense);
+    }
+
+    let (month_start, month_end) = calculate_current_cycle_dates(today, 1);
+    let year_start = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap();
+    let year_end = NaiveDate::from_ymd_opt(today.year(), 12, 31).unwrap();
+
+    let template = OverviewTemplate {
+        user,
+        currency_symbol: currency_symbol(&reporting_currency),
+        currency_options: currency_options(&reporting_currency),
+        reporting_currency,
+        start_date: start_date.format("%Y-%m-%d").to_string(),
+        end_date: end_date.format("%Y-%m-%d").to_string(),
+        start_label: preferences.format_date(start_date),
+        end_label: preferences.format_date(end_date),
+        month_start_date: month_start.format("%Y-%m-%d").to_string(),
+        month_end_date: month_end.format("%Y-%m-%d").to_string(),
+        year_start_date: year_start.format("%Y-%m-%d").to_string(),
+        year_end_date: year_end.format("%Y-%m-%d").to_string(),
+        total_income,
+        total_expense,
+        balance: total_income - total_expense,
+        is_positive: total_income >= total_expense,
+        books,
+        expense_categories,
+        missing_currencies,
+        error: query.error.unwrap_or_default(),
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+// 计算整数百分比
+fn percentage_of(amount: Decimal, total: Decimal) -> i32 {
+    if total > Decimal::ZERO {
+        (amount / total * Decimal::from(100)).to_f64().unwrap_or(0.0).round() as i32
+    } else {
+        0
+    }
 }
 
 pub async fn index(OptionalCurrentUser(user): OptionalCurrentUser) -> Result<Html<String>, Redirect> {
     match user {
         Some(_) => Err(Redirect::to("/dashboard")),
         None => {
-            let template = IndexTemplate { user: None };
+            let template = IndexTemplate;
             Ok(Html(template.render().unwrap()))
         }
     }
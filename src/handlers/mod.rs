@@ -2,6 +2,12 @@ pub mod auth;
 pub mod dashboard;
 pub mod account_book;
 pub mod account_book_reports;
+pub mod account_book_members;
 pub mod category;
+pub mod wallet;
 pub mod transaction;
-pub mod api;
\ No newline at end of file
+pub mod transfer;
+pub mod recurring;
+pub mod tag;
+pub mod api;
+pub mod profile;
\ No newline at end of file
@@ -0,0 +1,897 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::middleware::{AppState, CurrentUser};
+use crate::models::{
+    is_valid_timezone, AccountBook, ApiToken, ExchangeRate, RecoveryCode, Session, UpdateUserPreferences, User, UserIdentity,
+    UserPreferences, DATE_FORMAT_OPTIONS, LOCALE_OPTIONS, TIMEZONE_OPTIONS, WEEK_START_OPTIONS,
+};
+use crate::services::{DataExportService, ExchangeRateService, ACCOUNT_DELETION_GRACE_DAYS, MAX_EXCHANGE_RATE_IMPORT_ROWS};
+use crate::utils::{
+    is_supported_currency, totp_provisioning_uri, totp_qr_svg, ApiTokenForm, ChangeEmailForm,
+    DeleteAccountForm, DisableTwoFactorForm, ImportExchangeRatesForm, PreferencesForm, TwoFactorForm, SUPPORTED_CURRENCIES,
+};
+
+#[derive(Template)]
+#[template(path = "profile/sessions.html")]
+struct SessionsTemplate {
+    user: CurrentUser,
+    sessions: Vec<SessionDisplay>,
+    success: String,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "profile/account.html")]
+struct AccountTemplate {
+    user: CurrentUser,
+    pending_email: String,
+    totp_enabled: bool,
+    // 已申请注销时为计划删除日期
+    deletion_scheduled_at: String,
+    grace_days: i64,
+    success: String,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "profile/preferences.html")]
+struct PreferencesTemplate {
+    user: CurrentUser,
+    preferences: UserPreferences,
+    timezone_options: Vec<SelectOption>,
+    locale_options: Vec<SelectOption>,
+    currency_options: Vec<SelectOption>,
+    date_format_options: Vec<SelectOption>,
+    week_start_options: Vec<SelectOption>,
+    account_books: Vec<AccountBookOption>,
+    display_name: String,
+    // 0表示未设置默认账本
+    default_book_id: i64,
+    week_start: String,
+    today_preview: String,
+    success: String,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SelectOption {
+    pub value: String,
+    pub label: String,
+}
+
+impl SelectOption {
+    pub fn new(value: impl ToString, label: impl ToString) -> Self {
+        Self { value: value.to_string(), label: label.to_string() }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "profile/security.html")]
+struct SecurityTemplate {
+    user: CurrentUser,
+    totp_enabled: bool,
+    pending_setup: Option<TotpSetupDisplay>,
+    recovery_codes: Vec<String>,
+    remaining_recovery_codes: i64,
+    identities: Vec<IdentityDisplay>,
+    success: String,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdentityDisplay {
+    pub id: i64,
+    pub provider_name: String,
+    pub email: String,
+    pub last_login_at: String,
+}
+
+// 待确认的两步验证绑定信息
+pub struct TotpSetupDisplay {
+    pub qr_svg: String,
+    pub secret: String,
+    pub uri: String,
+}
+
+#[derive(Template)]
+#[template(path = "profile/tokens.html")]
+struct ApiTokensTemplate {
+    user: CurrentUser,
+    tokens: Vec<ApiTokenDisplay>,
+    account_books: Vec<AccountBookOption>,
+    new_token: String,
+    success: String,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "profile/exchange_rates.html")]
+struct ExchangeRatesTemplate {
+    user: CurrentUser,
+    rates: Vec<ExchangeRateDisplay>,
+    max_rows: usize,
+    success: String,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExchangeRateDisplay {
+    pub rate_date: String,
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiTokenDisplay {
+    pub id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    pub scope_label: String,
+    pub account_book_name: String,
+    pub last_used_at: String,
+    pub expires_at: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountBookOption {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionDisplay {
+    pub id: String,
+    pub device: String,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub created_at: String,
+    pub last_seen_at: String,
+    pub is_current: bool,
+}
+
+impl SessionDisplay {
+    fn new(session: Session, current_session_id: &str) -> Self {
+        let user_agent = session.user_agent.unwrap_or_default();
+        Self {
+            is_current: session.id == current_session_id,
+            id: session.id,
+            device: describe_user_agent(&user_agent),
+            user_agent,
+            ip_address: session.ip_address.unwrap_or_else(|| "未知".to_string()),
+            created_at: session.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            last_seen_at: session.last_seen_at.format("%Y-%m-%d %H:%M").to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    error: Option<String>,
+    success: Option<String>,
+}
+
+// 账户信息（登录邮箱）
+pub async fn account(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, Redirect> {
+    let account = load_user(&app_state, user.id).await?;
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+
+    let template = AccountTemplate {
+        user,
+        pending_email: account.pending_email.unwrap_or_default(),
+        totp_enabled: account.totp_enabled,
+        deletion_scheduled_at: account.deletion_scheduled_at
+            .map(|t| preferences.format_date(t.with_timezone(&preferences.tz()).date_naive()))
+            .unwrap_or_default(),
+        grace_days: ACCOUNT_DELETION_GRACE_DAYS,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 申请修改登录邮箱，确认链接发送到新邮箱
+pub async fn change_email(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Form(form): Form<ChangeEmailForm>,
+) -> Redirect {
+    if let Err(errors) = form.validate() {
+        let error_msg = errors
+            .field_errors()
+            .values()
+            .flat_map(|errs| errs.iter())
+            .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Redirect::to(&format!("/profile/account?error={}",
+            urlencoding::encode(&error_msg)));
+    }
+
+    let account = match load_user(&app_state, user.id).await {
+        Ok(account) => account,
+        Err(redirect) => return redirect,
+    };
+
+    match app_state
+        .auth_service
+        .request_email_change(&app_state.db_pool, &account, &form.new_email, &form.password)
+        .await
+    {
+        Ok(()) => Redirect::to("/profile/account?success=确认邮件已发送到新邮箱，点击邮件中的链接后生效"),
+        Err(e) => Redirect::to(&format!("/profile/account?error={}",
+            urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 取消尚未确认的邮箱修改
+pub async fn cancel_email_change(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    match User::cancel_pending_email(&app_state.db_pool, user.id).await {
+        Ok(()) => Redirect::to("/profile/account?success=已取消邮箱修改"),
+        Err(_) => Redirect::to("/profile/account?error=操作失败"),
+    }
+}
+
+// 下载全部个人数据（ZIP，内含JSON和CSV）
+pub async fn export_data(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+) -> Result<Response, Redirect> {
+    let archive = DataExportService::build_archive(&app_state.db_pool, user.id)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Failed to export data for user {}: {}", user.id, e);
+            Redirect::to("/profile/account?error=导出数据失败，请稍后重试")
+        })?;
+
+    let filename = format!("accounting-export-{}.zip", chrono::Utc::now().format("%Y%m%d"));
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        archive,
+    )
+        .into_response())
+}
+
+// 申请注销账户，冷静期结束后彻底删除
+pub async fn delete_account(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+    Form(form): Form<DeleteAccountForm>,
+) -> (CookieJar, Redirect) {
+    let account = match load_user(&app_state, user.id).await {
+        Ok(account) => account,
+        Err(redirect) => return (jar, redirect),
+    };
+
+    match app_state
+        .auth_service
+        .request_account_deletion(&app_state.db_pool, &account, &form.password, form.code.as_deref().unwrap_or_default())
+        .await
+    {
+        Ok(_) => {
+            let message = format!("账户将在{}天后彻底删除，期间重新登录即可撤销", ACCOUNT_DELETION_GRACE_DAYS);
+            (jar.add(expired_auth_cookie()), Redirect::to(&format!("/auth/login?success={}",
+                urlencoding::encode(&message))))
+        }
+        Err(e) => (jar, Redirect::to(&format!("/profile/account?error={}",
+            urlencoding::encode(&e.to_string())))),
+    }
+}
+
+pub async fn cancel_account_deletion(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    match User::cancel_deletion(&app_state.db_pool, user.id).await {
+        Ok(true) => Redirect::to("/profile/account?success=已撤销账户注销"),
+        Ok(false) => Redirect::to("/profile/account"),
+        Err(_) => Redirect::to("/profile/account?error=操作失败"),
+    }
+}
+
+// 个人偏好设置
+pub async fn preferences(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, Redirect> {
+    let preferences = UserPreferences::find_by_user(&app_state.db_pool, user.id)
+        .await
+        .map_err(|_| Redirect::to("/dashboard?error=加载偏好设置失败"))?;
+    let account_books = AccountBook::find_by_user(&app_state.db_pool, user.id)
+        .await
+        .map_err(|_| Redirect::to("/dashboard?error=加载偏好设置失败"))?;
+
+    let mut timezone_options: Vec<SelectOption> = TIMEZONE_OPTIONS
+        .iter()
+        .map(|(value, label)| SelectOption::new(value, format!("{} ({})", label, value)))
+        .collect();
+    // 保留列表之外的已保存时区
+    if !TIMEZONE_OPTIONS.iter().any(|(value, _)| *value == preferences.timezone) {
+        timezone_options.push(SelectOption::new(&preferences.timezone, &preferences.timezone));
+    }
+
+    let template = PreferencesTemplate {
+        user,
+        timezone_options,
+        locale_options: LOCALE_OPTIONS.iter().map(|(value, label)| SelectOption::new(value, label)).collect(),
+        currency_options: SUPPORTED_CURRENCIES
+            .iter()
+            .map(|(code, name, symbol)| SelectOption::new(code, format!("{} ({})", name, symbol)))
+            .collect(),
+        date_format_options: DATE_FORMAT_OPTIONS.iter().map(|(value, label)| SelectOption::new(value, label)).collect(),
+        week_start_options: WEEK_START_OPTIONS.iter().map(|(value, label)| SelectOption::new(value, label)).collect(),
+        account_books: account_books
+            .into_iter()
+            .map(|book| AccountBookOption { id: book.id, name: book.name })
+            .collect(),
+        display_name: preferences.display_name.clone().unwrap_or_default(),
+        default_book_id: preferences.default_account_book_id.unwrap_or(0),
+        week_start: preferences.week_start.to_string(),
+        today_preview: preferences.format_date(preferences.today()),
+        preferences,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_preferences(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Form(form): Form<PreferencesForm>,
+) -> Redirect {
+    if let Err(errors) = form.validate() {
+        let error_msg = errors
+            .field_errors()
+            .values()
+            .flat_map(|errs| errs.iter())
+            .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Redirect::to(&format!("/profile/preferences?error={}",
+            urlencoding::encode(&error_msg)));
+    }
+
+    if !is_valid_timezone(&form.timezone) {
+        return Redirect::to("/profile/preferences?error=时区无效");
+    }
+    if !LOCALE_OPTIONS.iter().any(|(value, _)| *value == form.locale) {
+        return Redirect::to("/profile/preferences?error=语言无效");
+    }
+    if !is_supported_currency(&form.default_currency) {
+        return Redirect::to("/profile/preferences?error=货币类型无效");
+    }
+    if !DATE_FORMAT_OPTIONS.iter().any(|(value, _)| *value == form.date_format) {
+        return Redirect::to("/profile/preferences?error=日期格式无效");
+    }
+    if !WEEK_START_OPTIONS.iter().any(|(value, _)| *value == form.week_start) {
+        return Redirect::to("/profile/preferences?error=每周起始日无效");
+    }
+
+    let default_account_book_id = match form.default_account_book_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(value) => {
+            let Ok(id) = value.parse::<i64>() else {
+                return Redirect::to("/profile/preferences?error=账本无效");
+            };
+            match AccountBook::find_by_id(&app_state.db_pool, id, user.id).await {
+                Ok(Some(_)) => Some(id),
+                _ => return Redirect::to("/profile/preferences?error=账本不存在或无权限访问"),
+            }
+        }
+    };
+
+    let update = UpdateUserPreferences {
+        display_name: form.display_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty()),
+        timezone: form.timezone,
+        locale: form.locale,
+        default_currency: form.default_currency,
+        date_format: form.date_format,
+        week_start: form.week_start,
+        default_account_book_id,
+    };
+
+    match UserPreferences::save(&app_state.db_pool, user.id, &update).await {
+        Ok(()) => Redirect::to("/profile/preferences?success=偏好设置已保存"),
+        Err(e) => {
+            tracing::warn!("Failed to save preferences: {}", e);
+            Redirect::to("/profile/preferences?error=保存失败")
+        }
+    }
+}
+
+// 登录设备列表
+pub async fn sessions(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, Redirect> {
+    let sessions = match Session::find_by_user(&app_state.db_pool, user.id).await {
+        Ok(sessions) => sessions
+            .into_iter()
+            .map(|session| SessionDisplay::new(session, &user.session_id))
+            .collect(),
+        Err(_) => return Err(Redirect::to("/dashboard?error=加载登录设备失败")),
+    };
+
+    let template = SessionsTemplate {
+        user,
+        sessions,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 注销单个会话
+pub async fn revoke_session(
+    user: CurrentUser,
+    Path(session_id): Path<String>,
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Redirect) {
+    match Session::delete(&app_state.db_pool, &session_id, user.id).await {
+        Ok(true) if session_id == user.session_id => {
+            (jar.add(expired_auth_cookie()), Redirect::to("/auth/login?success=已成功退出登录"))
+        }
+        Ok(true) => (jar, Redirect::to("/profile/sessions?success=该设备已退出登录")),
+        Ok(false) => (jar, Redirect::to("/profile/sessions?error=会话不存在或已失效")),
+        Err(_) => (jar, Redirect::to("/profile/sessions?error=操作失败")),
+    }
+}
+
+// 退出所有设备（包括当前设备）
+pub async fn revoke_all_sessions(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Redirect) {
+    match Session::delete_all_for_user(&app_state.db_pool, user.id).await {
+        Ok(()) => (jar.add(expired_auth_cookie()), Redirect::to("/auth/login?success=已退出所有设备")),
+        Err(_) => (jar, Redirect::to("/profile/sessions?error=操作失败")),
+    }
+}
+
+// 账户安全（两步验证）
+pub async fn security(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, Redirect> {
+    render_security(
+        user,
+        &app_state,
+        Vec::new(),
+        query.success.unwrap_or_default(),
+        query.error.unwrap_or_default(),
+    )
+    .await
+}
+
+// 生成TOTP密钥，页面展示二维码等待确认
+pub async fn setup_two_factor(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let account = match load_user(&app_state, user.id).await {
+        Ok(account) => account,
+        Err(redirect) => return redirect,
+    };
+
+    match app_state.auth_service.begin_totp_enrollment(&app_state.db_pool, &account).await {
+        Ok(_) => Redirect::to("/profile/security"),
+        Err(e) => Redirect::to(&format!("/profile/security?error={}",
+            urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 输入第一个验证码完成绑定，恢复码只在此时展示一次
+pub async fn enable_two_factor(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Form(form): Form<TwoFactorForm>,
+) -> Result<Html<String>, Redirect> {
+    let account = load_user(&app_state, user.id).await?;
+    if account.totp_enabled {
+        return Err(Redirect::to("/profile/security?error=两步验证已启用"));
+    }
+
+    match app_state
+        .auth_service
+        .confirm_totp_enrollment(&app_state.db_pool, &account, &form.code)
+        .await
+    {
+        Ok(codes) => {
+            render_security(user, &app_state, codes, "两步验证已启用，请妥善保存恢复码".to_string(), String::new()).await
+        }
+        Err(e) => Err(Redirect::to(&format!("/profile/security?error={}",
+            urlencoding::encode(&e.to_string())))),
+    }
+}
+
+// 重新生成恢复码，旧的恢复码全部作废
+pub async fn regenerate_recovery_codes(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Form(form): Form<TwoFactorForm>,
+) -> Result<Html<String>, Redirect> {
+    let account = load_user(&app_state, user.id).await?;
+    if !account.totp_enabled {
+        return Err(Redirect::to("/profile/security?error=尚未启用两步验证"));
+    }
+
+    match app_state.auth_service.verify_second_factor(&app_state.db_pool, &account, &form.code).await {
+        Ok(true) => {}
+        Ok(false) => return Err(Redirect::to("/profile/security?error=验证码错误")),
+        Err(_) => return Err(Redirect::to("/profile/security?error=操作失败")),
+    }
+
+    match app_state.auth_service.regenerate_recovery_codes(&app_state.db_pool, account.id).await {
+        Ok(codes) => {
+            render_security(user, &app_state, codes, "已生成新的恢复码，旧恢复码已失效".to_string(), String::new()).await
+        }
+        Err(_) => Err(Redirect::to("/profile/security?error=生成恢复码失败")),
+    }
+}
+
+// 关闭两步验证，需要密码和验证码
+pub async fn disable_two_factor(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Form(form): Form<DisableTwoFactorForm>,
+) -> Redirect {
+    let account = match load_user(&app_state, user.id).await {
+        Ok(account) => account,
+        Err(redirect) => return redirect,
+    };
+
+    match app_state
+        .auth_service
+        .disable_totp(&app_state.db_pool, &account, &form.password, &form.code)
+        .await
+    {
+        Ok(()) => Redirect::to("/profile/security?success=两步验证已关闭"),
+        Err(e) => Redirect::to(&format!("/profile/security?error={}",
+            urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 解除外部身份关联
+pub async fn unlink_identity(
+    user: CurrentUser,
+    Path(identity_id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    match UserIdentity::delete(&app_state.db_pool, identity_id, user.id).await {
+        Ok(true) => Redirect::to("/profile/security?success=已解除关联"),
+        Ok(false) => Redirect::to("/profile/security?error=关联不存在"),
+        Err(_) => Redirect::to("/profile/security?error=操作失败"),
+    }
+}
+
+async fn render_security(
+    user: CurrentUser,
+    app_state: &AppState,
+    recovery_codes: Vec<String>,
+    success: String,
+    error: String,
+) -> Result<Html<String>, Redirect> {
+    let account = load_user(app_state, user.id).await?;
+
+    let pending_setup = match (&account.totp_secret, account.totp_enabled) {
+        (Some(secret), false) => {
+            let uri = totp_provisioning_uri(secret, &account.email)
+                .map_err(|_| Redirect::to("/dashboard?error=加载安全设置失败"))?;
+            let qr_svg = totp_qr_svg(&uri)
+                .map_err(|_| Redirect::to("/dashboard?error=加载安全设置失败"))?;
+            Some(TotpSetupDisplay { qr_svg, secret: secret.clone(), uri })
+        }
+        _ => None,
+    };
+
+    let remaining_recovery_codes = if account.totp_enabled {
+        RecoveryCode::count_unused(&app_state.db_pool, account.id)
+            .await
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    let identities = UserIdentity::find_by_user(&app_state.db_pool, account.id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|identity| IdentityDisplay {
+            id: identity.id,
+            provider_name: app_state
+                .oidc_service
+                .provider(&identity.provider)
+                .map(|provider| provider.display_name.clone())
+                .unwrap_or(identity.provider),
+            email: identity.email.unwrap_or_default(),
+            last_login_at: identity.last_login_at
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "从未登录".to_string()),
+        })
+        .collect();
+
+    let template = SecurityTemplate {
+        user,
+        totp_enabled: account.totp_enabled,
+        pending_setup,
+        recovery_codes,
+        remaining_recovery_codes,
+        identities,
+        success,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 个人访问令牌列表
+pub async fn api_tokens(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, Redirect> {
+    render_api_tokens(
+        user,
+        &app_state,
+        String::new(),
+        query.success.unwrap_or_default(),
+        query.error.unwrap_or_default(),
+    )
+    .await
+}
+
+// 创建令牌，明文只在创建后的页面展示一次
+pub async fn create_api_token(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Form(form): Form<ApiTokenForm>,
+) -> Result<Html<String>, Redirect> {
+    if let Err(errors) = form.validate() {
+        let error_msg = errors
+            .field_errors()
+            .values()
+            .flat_map(|errs| errs.iter())
+            .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(Redirect::to(&format!("/profile/tokens?error={}",
+            urlencoding::encode(&error_msg))));
+    }
+
+    let account_book_id = match form.account_book_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(value) => {
+            let id = value.parse::<i64>()
+                .map_err(|_| Redirect::to("/profile/tokens?error=账本无效"))?;
+            match AccountBook::find_by_id(&app_state.db_pool, id, user.id).await {
+                Ok(Some(_)) => Some(id),
+                _ => return Err(Redirect::to("/profile/tokens?error=账本不存在或无权限访问")),
+            }
+        }
+    };
+
+    let expires_in_days = match form.expires_in_days.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(value) => match value.parse::<i64>() {
+            Ok(days) if days > 0 => Some(days),
+            _ => return Err(Redirect::to("/profile/tokens?error=有效期无效")),
+        },
+    };
+
+    match app_state
+        .auth_service
+        .create_api_token(&app_state.db_pool, user.id, &form.name, &form.scope, account_book_id, expires_in_days)
+        .await
+    {
+        Ok((_, token)) => {
+            render_api_tokens(user, &app_state, token, "访问令牌已创建，请立即复制保存".to_string(), String::new()).await
+        }
+        Err(e) => Err(Redirect::to(&format!("/profile/tokens?error={}",
+            urlencoding::encode(&e.to_string())))),
+    }
+}
+
+// 撤销令牌，立即失效
+pub async fn revoke_api_token(
+    user: CurrentUser,
+    Path(token_id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    match ApiToken::delete(&app_state.db_pool, token_id, user.id).await {
+        Ok(true) => Redirect::to("/profile/tokens?success=访问令牌已撤销"),
+        Ok(false) => Redirect::to("/profile/tokens?error=访问令牌不存在"),
+        Err(_) => Redirect::to("/profile/tokens?error=操作失败"),
+    }
+}
+
+// 汇率列表，记外币账时按交易日期查找最近的汇率
+pub async fn exchange_rates(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, Redirect> {
+    let rates = ExchangeRate::find_recent_by_user(&app_state.db_pool, user.id, 200)
+        .await
+        .map_err(|_| Redirect::to("/dashboard?error=加载汇率失败"))?
+        .into_iter()
+        .map(|rate| ExchangeRateDisplay {
+            rate_date: rate.rate_date.format("%Y-%m-%d").to_string(),
+            base_currency: rate.base_currency,
+            quote_currency: rate.quote_currency,
+            rate: rate.rate.normalize().to_string(),
+        })
+        .collect();
+
+    let template = ExchangeRatesTemplate {
+        user,
+        rates,
+        max_rows: MAX_EXCHANGE_RATE_IMPORT_ROWS,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 从CSV导入汇率，任意一行有误时整批不导入
+pub async fn import_exchange_rates(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+    Form(form): Form<ImportExchangeRatesForm>,
+) -> Redirect {
+    match ExchangeRateService::import_csv(&app_state.db_pool, user.id, &form.csv).await {
+        Ok(count) => Redirect::to(&format!("/profile/exchange-rates?success=已导入{}条汇率", count)),
+        Err(e) => Redirect::to(&format!("/profile/exchange-rates?error={}",
+            urlencoding::encode(&e.to_string()))),
+    }
+}
+
+// 清空汇率，已记录的交易保留当时使用的汇率
+pub async fn clear_exchange_rates(
+    user: CurrentUser,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    match ExchangeRate::delete_all(&app_state.db_pool, user.id).await {
+        Ok(_) => Redirect::to("/profile/exchange-rates?success=汇率已清空"),
+        Err(_) => Redirect::to("/profile/exchange-rates?error=操作失败"),
+    }
+}
+
+async fn render_api_tokens(
+    user: CurrentUser,
+    app_state: &AppState,
+    new_token: String,
+    success: String,
+    error: String,
+) -> Result<Html<String>, Redirect> {
+    let account_books = AccountBook::find_by_user(&app_state.db_pool, user.id)
+        .await
+        .map_err(|_| Redirect::to("/dashboard?error=加载访问令牌失败"))?;
+    let tokens = ApiToken::find_by_user(&app_state.db_pool, user.id)
+        .await
+        .map_err(|_| Redirect::to("/dashboard?error=加载访问令牌失败"))?;
+
+    let tokens = tokens
+        .into_iter()
+        .map(|token| ApiTokenDisplay {
+            id: token.id,
+            scope_label: if token.is_read_only() { "只读" } else { "读写" }.to_string(),
+            account_book_name: match token.account_book_id {
+                Some(book_id) => account_books
+                    .iter()
+                    .find(|book| book.id == book_id)
+                    .map(|book| book.name.clone())
+                    .unwrap_or_else(|| "已删除的账本".to_string()),
+                None => "全部账本".to_string(),
+            },
+            last_used_at: token.last_used_at
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "从未使用".to_string()),
+            expires_at: token.expires_at
+                .map(|t| t.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "永不过期".to_string()),
+            created_at: token.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            name: token.name,
+            token_prefix: token.token_prefix,
+        })
+        .collect();
+
+    let template = ApiTokensTemplate {
+        user,
+        tokens,
+        account_books: account_books
+            .into_iter()
+            .map(|book| AccountBookOption { id: book.id, name: book.name })
+            .collect(),
+        new_token,
+        success,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn load_user(app_state: &AppState, user_id: i64) -> Result<User, Redirect> {
+    match User::find_by_id(&app_state.db_pool, user_id).await {
+        Ok(Some(user)) => Ok(user),
+        _ => Err(Redirect::to("/dashboard?error=用户不存在")),
+    }
+}
+
+fn expired_auth_cookie() -> Cookie<'static> {
+    Cookie::build(("auth_token", ""))
+        .path("/")
+        .max_age(time::Duration::seconds(0))
+        .http_only(true)
+        .build()
+}
+
+// 从User-Agent中提取浏览器和系统的简要描述
+fn describe_user_agent(user_agent: &str) -> String {
+    if user_agent.is_empty() {
+        return "未知设备".to_string();
+    }
+
+    let browser = if user_agent.contains("Edg/") {
+        "Edge"
+    } else if user_agent.contains("MicroMessenger") {
+        "微信"
+    } else if user_agent.contains("Firefox/") {
+        "Firefox"
+    } else if user_agent.contains("Chrome/") {
+        "Chrome"
+    } else if user_agent.contains("Safari/") {
+        "Safari"
+    } else if user_agent.contains("curl/") {
+        "curl"
+    } else {
+        "其他客户端"
+    };
+
+    let os = if user_agent.contains("iPhone") || user_agent.contains("iPad") {
+        "iOS"
+    } else if user_agent.contains("Android") {
+        "Android"
+    } else if user_agent.contains("Windows") {
+        "Windows"
+    } else if user_agent.contains("Mac OS X") {
+        "macOS"
+    } else if user_agent.contains("Linux") {
+        "Linux"
+    } else {
+        ""
+    };
+
+    if os.is_empty() {
+        browser.to_string()
+    } else {
+        format!("{} · {}", browser, os)
+    }
+}
@@ -0,0 +1,787 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, Redirect},
+    Form,
+};
+use askama::Template;
+use serde::Deserialize;
+use chrono::{Datelike, Duration, NaiveDate};
+use rust_decimal::Decimal;
+
+use crate::handlers::account_book::AccountBookDisplay;
+use crate::handlers::profile::SelectOption;
+use crate::handlers::transaction::currency_options;
+use crate::middleware::{AppState, CurrentUser};
+use crate::models::{
+    frequency_label, parse_tag_names, AccountBook, BookRole, Category, CreateRecurringTransaction, RecurringOccurrence,
+    RecurringTransaction, RecurringTransactionWithCategory, UpdateRecurringTransaction, UserPreferences, Wallet,
+    RECURRING_FREQUENCIES, RECURRING_MAX_INTERVAL_DAYS,
+};
+use crate::services::RecurringService;
+use crate::utils::{currency_symbol, is_valid_currency_code};
+
+// 预览的后续次数
+const PREVIEW_COUNT: usize = 12;
+// 详情页显示的最近记录条数
+const HISTORY_COUNT: i64 = 20;
+// 最多生成的次数上限
+const MAX_OCCURRENCES_LIMIT: i32 = 10000;
+// 起始日期最早可以追溯的天数，避免一次补生成过多交易
+const MAX_BACKFILL_DAYS: i64 = 366;
+
+#[derive(Template)]
+#[template(path = "recurring/list.html")]
+struct RecurringListTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    rules: Vec<RuleDisplay>,
+    categories: Vec<CategoryOption>,
+    wallets: Vec<WalletOption>,
+    currency_options: Vec<SelectOption>,
+    frequency_options: Vec<SelectOption>,
+    today: String,
+    success: String,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "recurring/detail.html")]
+struct RecurringDetailTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    rule: RuleDisplay,
+    categories: Vec<CategoryOption>,
+    wallets: Vec<WalletOption>,
+    currency_options: Vec<SelectOption>,
+    upcoming: Vec<OccurrenceDisplay>,
+    history: Vec<OccurrenceDisplay>,
+    // 因所在期间已结账未生成的次数
+    blocked_count: i64,
+    // 规则创建者已不是账本的编辑者，规则被暂停
+    creator_lost_access: bool,
+    success: String,
+    error: String,
+}
+
+pub struct RuleDisplay {
+    pub id: i64,
+    pub transaction_type: String,
+    pub category_id: i64,
+    pub category_name: String,
+    pub category_icon: String,
+    pub category_color: String,
+    pub wallet_id: i64,
+    pub wallet_name: String,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub amount_label: String,
+    pub exchange_rate: String,
+    pub description: String,
+    pub tags: String,
+    pub schedule_label: String,
+    pub start_label: String,
+    pub end_label: String,
+    pub end_date: String,
+    pub max_occurrences: String,
+    pub next_label: String,
+    pub is_active: bool,
+    pub is_finished: bool,
+}
+
+pub struct OccurrenceDisplay {
+    pub sequence: i32,
+    pub date_label: String,
+    pub status_label: String,
+    // pending 待生成、created 已生成、skipped 已跳过、blocked 期间已结账未生成
+    pub status: String,
+    pub transaction_id: i64,
+    pub is_due: bool,
+}
+
+pub struct CategoryOption {
+    pub id: i64,
+    pub name: String,
+    pub category_type: String,
+}
+
+pub struct WalletOption {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct RecurringQuery {
+    success: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateRecurringForm {
+    pub transaction_type: String,
+    pub category_id: i64,
+    pub wallet_id: Option<String>,
+    pub amount: String,
+    pub original_currency: Option<String>,
+    pub exchange_rate: Option<String>,
+    pub frequency: String,
+    pub interval_days: Option<String>,
+    pub start_date: String,
+    pub end_date: Option<String>,
+    pub max_occurrences: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRecurringForm {
+    pub category_id: i64,
+    pub wallet_id: Option<String>,
+    pub amount: String,
+    pub original_currency: Option<String>,
+    pub exchange_rate: Option<String>,
+    pub end_date: Option<String>,
+    pub max_occurrences: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<String>,
+}
+
+// 描述规则的周期，如“每月31日（没有该日的月份取月末）”
+fn schedule_label(rule: &RecurringTransaction) -> String {
+    const WEEKDAYS: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+    let start = rule.start_date;
+    match rule.frequency.as_str() {
+        "weekly" => format!("每周{}", WEEKDAYS[start.weekday().num_days_from_monday() as usize]),
+        "monthly" if start.day() > 28 => format!("每月{}日（没有该日的月份取月末）", start.day()),
+        "monthly" => format!("每月{}日", start.day()),
+        "yearly" if start.month() == 2 && start.day() == 29 => "每年2月29日（平年取2月28日）".to_string(),
+        "yearly" => format!("每年{}月{}日", start.month(), start.day()),
+        "every_n_days" => format!("每隔{}天", rule.interval_days),
+        _ => frequency_label(&rule.frequency).to_string(),
+    }
+}
+
+impl RuleDisplay {
+    fn new(row: RecurringTransactionWithCategory, preferences: &UserPreferences) -> Self {
+        let rule = row.rule;
+        let end_label = match (rule.end_date, rule.max_occurrences) {
+            (Some(end_date), Some(max)) => format!("{} 或共{}次后结束", preferences.format_date(end_date), max),
+            (Some(end_date), None) => format!("{} 结束", preferences.format_date(end_date)),
+            (None, Some(max)) => format!("共{}次", max),
+            (None, None) => "不结束".to_string(),
+        };
+        let next_label = match rule.next_date {
+            Some(date) => preferences.format_date(date),
+            None => "已结束".to_string(),
+        };
+
+        Self {
+            id: rule.id,
+            schedule_label: schedule_label(&rule),
+            start_label: preferences.format_date(rule.start_date),
+            amount_label: format!("{}{}", currency_symbol(&rule.original_currency), rule.original_amount),
+            end_label,
+            next_label,
+            is_finished: rule.next_date.is_none(),
+            transaction_type: rule.transaction_type,
+            category_id: rule.category_id,
+            category_name: row.category_name,
+            category_icon: row.category_icon.unwrap_or_else(|| "tag".to_string()),
+            category_color: row.category_color.unwrap_or_else(|| "#007AFF".to_string()),
+            wallet_id: rule.wallet_id.unwrap_or_default(),
+            wallet_name: row.wallet_name.unwrap_or_default(),
+            original_currency: rule.original_currency,
+            original_amount: rule.original_amount,
+            exchange_rate: rule.exchange_rate.map(|rate| rate.normalize().to_string()).unwrap_or_default(),
+            description: rule.description.unwrap_or_default(),
+            tags: rule.tags.unwrap_or_default(),
+            end_date: rule.end_date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+            max_occurrences: rule.max_occurrences.map(|max| max.to_string()).unwrap_or_default(),
+            is_active: rule.is_active,
+        }
+    }
+}
+
+fn list_url(account_book_id: i64) -> String {
+    format!("/account-books/{}/recurring", account_book_id)
+}
+
+fn detail_url(account_book_id: i64, rule_id: i64) -> String {
+    format!("/account-books/{}/recurring/{}", account_book_id, rule_id)
+}
+
+fn redirect_with_error(url: &str, message: &str) -> Redirect {
+    Redirect::to(&format!("{}?error={}", url, urlencoding::encode(message)))
+}
+
+async fn category_options(app_state: &AppState, account_book_id: i64) -> Vec<CategoryOption> {
+    Category::find_by_account_book(&app_state.db_pool, account_book_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| CategoryOption { id: c.id, name: c.name, category_type: c.category_type })
+        .collect()
+}
+
+async fn wallet_options(app_state: &AppState, account_book_id: i64) -> Vec<WalletOption> {
+    Wallet::find_by_account_book(&app_state.db_pool, account_book_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|wallet| WalletOption { id: wallet.id, name: wallet.name })
+        .collect()
+}
+
+// 规则存在且属于该账本
+async fn find_rule(app_state: &AppState, account_book_id: i64, rule_id: i64) -> Option<RecurringTransaction> {
+    match RecurringTransaction::find_by_id(&app_state.db_pool, rule_id).await {
+        Ok(Some(rule)) if rule.account_book_id == account_book_id => Some(rule),
+        _ => None,
+    }
+}
+
+fn parse_optional<T: std::str::FromStr>(value: Option<&str>, message: &str) -> Result<Option<T>, String> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse::<T>().map(Some).map_err(|_| message.to_string()),
+    }
+}
+
+// 校验金额、货币、账户、结束条件等新建和修改共用的字段
+struct CommonFields {
+    wallet_id: Option<i64>,
+    original_currency: String,
+    original_amount: Decimal,
+    exchange_rate: Option<Decimal>,
+    end_date: Option<NaiveDate>,
+    max_occurrences: Option<i32>,
+    description: Option<String>,
+    tags: Option<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn validate_common(
+    app_state: &AppState,
+    book: &AccountBook,
+    wallet_id: Option<&str>,
+    amount: &str,
+    original_currency: Option<&str>,
+    exchange_rate: Option<&str>,
+    end_date: Option<&str>,
+    max_occurrences: Option<&str>,
+    description: Option<String>,
+    tags: Option<String>,
+) -> Result<CommonFields, String> {
+    let original_amount = match amount.trim().parse::<Decimal>() {
+        Ok(amount) if amount > Decimal::ZERO => amount.round_dp(2),
+        _ => return Err("金额必须大于0".to_string()),
+    };
+
+    let original_currency = match original_currency.map(str::trim) {
+        None | Some("") => book.currency.clone(),
+        Some(code) => code.to_uppercase(),
+    };
+    if !is_valid_currency_code(&original_currency) {
+        return Err("货币代码无效".to_string());
+    }
+
+    // 与账本货币相同时不需要汇率
+    let exchange_rate = if original_currency == book.currency {
+        None
+    } else {
+        match parse_optional::<Decimal>(exchange_rate, "汇率格式错误")? {
+            Some(rate) if rate <= Decimal::ZERO => return Err("汇率必须大于0".to_string()),
+            rate => rate,
+        }
+    };
+
+    let wallet_id = parse_optional::<i64>(wallet_id, "账户无效")?.filter(|id| *id > 0);
+    if let Some(wallet_id) = wallet_id {
+        match Wallet::find_by_id(&app_state.db_pool, wallet_id).await {
+            Ok(Some(wallet)) if wallet.account_book_id == book.id => {}
+            _ => return Err("账户不存在或无权限访问".to_string()),
+        }
+    }
+
+    let end_date = match end_date.map(str::trim) {
+        None | Some("") => None,
+        Some(date) => Some(NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| "结束日期格式错误".to_string())?),
+    };
+    let max_occurrences = match parse_optional::<i32>(max_occurrences, "次数必须为整数")? {
+        Some(max) if !(1..=MAX_OCCURRENCES_LIMIT).contains(&max) => {
+            return Err(format!("次数必须在1-{}之间", MAX_OCCURRENCES_LIMIT));
+        }
+        max => max,
+    };
+
+    let description = description.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    if description.as_ref().is_some_and(|s| s.chars().count() > 500) {
+        return Err("描述长度不能超过500字符".to_string());
+    }
+    let tags = tags.map(|s| parse_tag_names(&s).join(",")).filter(|s| !s.is_empty());
+    if tags.as_ref().is_some_and(|s| s.chars().count() > 500) {
+        return Err("标签长度不能超过500字符".to_string());
+    }
+
+    Ok(CommonFields {
+        wallet_id,
+        original_currency,
+        original_amount,
+        exchange_rate,
+        end_date,
+        max_occurrences,
+        description,
+        tags,
+    })
+}
+
+// 分类存在、属于该账本且与交易类型一致
+async fn validate_category(
+    app_state: &AppState,
+    account_book_id: i64,
+    category_id: i64,
+    transaction_type: &str,
+) -> Result<(), String> {
+    match Category::find_by_id(&app_state.db_pool, category_id).await {
+        Ok(Some(category)) if category.account_book_id == account_book_id => {
+            if category.category_type != transaction_type {
+                return Err("分类类型与交易类型不匹配".to_string());
+            }
+            Ok(())
+        }
+        _ => Err("分类不存在或无权限访问".to_string()),
+    }
+}
+
+// 重复记账规则列表，任意成员可以查看，编辑者及以上可以新建
+pub async fn list(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Query(query): Query<RecurringQuery>,
+) -> Result<Html<String>, Redirect> {
+    let book = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some((book, role))) => AccountBookDisplay::from(book).with_role(role),
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
+    };
+
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+
+    let rules = match RecurringTransaction::find_by_account_book_with_category(&app_state.db_pool, account_book_id).await {
+        Ok(rows) => rows.into_iter().map(|row| RuleDisplay::new(row, &preferences)).collect(),
+        Err(_) => return Err(Redirect::to(&format!("/account-books/{}?error=加载重复记账规则失败", account_book_id))),
+    };
+
+    let template = RecurringListTemplate {
+        user,
+        categories: category_options(&app_state, account_book_id).await,
+        wallets: wallet_options(&app_state, account_book_id).await,
+        currency_options: currency_options(&book.currency),
+        frequency_options: RECURRING_FREQUENCIES
+            .iter()
+            .map(|(value, label)| SelectOption::new(value, label))
+            .collect(),
+        today: preferences.today().format("%Y-%m-%d").to_string(),
+        book,
+        rules,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 新建规则，到期的次数由后台任务生成，起始日期在今天及之前时立即补生成
+pub async fn create(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<CreateRecurringForm>,
+) -> Redirect {
+    let url = list_url(account_book_id);
+
+    // 编辑者及以上才能记账
+    let book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    };
+
+    if form.transaction_type != "income" && form.transaction_type != "expense" {
+        return redirect_with_error(&url, "交易类型无效");
+    }
+    if let Err(message) = validate_category(&app_state, account_book_id, form.category_id, &form.transaction_type).await {
+        return redirect_with_error(&url, &message);
+    }
+
+    if !RECURRING_FREQUENCIES.iter().any(|(value, _)| *value == form.frequency) {
+        return redirect_with_error(&url, "重复周期无效");
+    }
+    let interval_days = if form.frequency == "every_n_days" {
+        match parse_optional::<i32>(form.interval_days.as_deref(), "间隔天数必须为整数") {
+            Ok(Some(days)) if (1..=RECURRING_MAX_INTERVAL_DAYS).contains(&days) => days,
+            Ok(_) => return redirect_with_error(&url, &format!("间隔天数必须在1-{}之间", RECURRING_MAX_INTERVAL_DAYS)),
+            Err(message) => return redirect_with_error(&url, &message),
+        }
+    } else {
+        1
+    };
+
+    let start_date = match NaiveDate::parse_from_str(form.start_date.trim(), "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => return redirect_with_error(&url, "起始日期格式错误"),
+    };
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let today = preferences.today();
+    if start_date < today - Duration::days(MAX_BACKFILL_DAYS) {
+        return redirect_with_error(&url, "起始日期不能早于一年前");
+    }
+
+    let fields = match validate_common(
+        &app_state,
+        &book,
+        form.wallet_id.as_deref(),
+        &form.amount,
+        form.original_currency.as_deref(),
+        form.exchange_rate.as_deref(),
+        form.end_date.as_deref(),
+        form.max_occurrences.as_deref(),
+        form.description,
+        form.tags,
+    ).await {
+        Ok(fields) => fields,
+        Err(message) => return redirect_with_error(&url, &message),
+    };
+    if fields.end_date.is_some_and(|end_date| end_date < start_date) {
+        return redirect_with_error(&url, "结束日期不能早于起始日期");
+    }
+
+    let create = CreateRecurringTransaction {
+        account_book_id,
+        category_id: form.category_id,
+        wallet_id: fields.wallet_id,
+        transaction_type: form.transaction_type,
+        original_currency: fields.original_currency,
+        original_amount: fields.original_amount,
+        exchange_rate: fields.exchange_rate,
+        description: fields.description,
+        tags: fields.tags,
+        frequency: form.frequency,
+        interval_days,
+        start_date,
+        end_date: fields.end_date,
+        max_occurrences: fields.max_occurrences,
+        created_by: user.id,
+    };
+
+    let rule_id = match RecurringTransaction::create(&app_state.db_pool, create).await {
+        Ok(rule_id) => rule_id,
+        Err(_) => return redirect_with_error(&url, "创建重复记账规则失败"),
+    };
+
+    let detail = detail_url(account_book_id, rule_id);
+    if start_date <= today {
+        if let Err(e) = RecurringService::materialize_rule(&app_state.db_pool, rule_id, today).await {
+            return redirect_with_error(&detail, &format!("规则已创建，但生成已到期的交易失败：{}", e));
+        }
+    }
+    Redirect::to(&format!("{}?success=重复记账规则已创建", detail))
+}
+
+// 规则详情：预览后续的次数，可以跳过或单独修改某一次
+pub async fn detail(
+    user: CurrentUser,
+    Path((account_book_id, rule_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Query(query): Query<RecurringQuery>,
+) -> Result<Html<String>, Redirect> {
+    let (book, role) = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some(found)) => found,
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
+    };
+
+    let row = match RecurringTransaction::find_by_account_book_with_category(&app_state.db_pool, account_book_id).await {
+        Ok(rows) => rows.into_iter().find(|row| row.rule.id == rule_id),
+        Err(_) => None,
+    };
+    let row = match row {
+        Some(row) => row,
+        None => return Err(redirect_with_error(&list_url(account_book_id), "重复记账规则不存在")),
+    };
+
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let today = preferences.today();
+    let rule = &row.rule;
+
+    let creator_lost_access = !rule.is_active
+        && matches!(RecurringService::booking_user(&app_state.db_pool, rule, &book).await, Ok(None));
+    let blocked_count = RecurringOccurrence::count_blocked(&app_state.db_pool, rule.id).await.unwrap_or(0);
+    let book = AccountBookDisplay::from(book).with_role(role);
+
+    // 提前生成或跳过的次数已有记录，其余为待生成
+    let recorded: HashMap<i32, RecurringOccurrence> =
+        RecurringOccurrence::find_from_sequence(&app_state.db_pool, rule.id, rule.next_sequence)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|occurrence| (occurrence.sequence, occurrence))
+            .collect();
+    let mut upcoming = Vec::new();
+    let mut sequence = rule.next_sequence;
+    while upcoming.len() < PREVIEW_COUNT {
+        let date = match rule.occurrence_date(sequence) {
+            Some(date) => date,
+            None => break,
+        };
+        upcoming.push(match recorded.get(&sequence) {
+            Some(occurrence) => OccurrenceDisplay::recorded(occurrence, &preferences),
+            None => OccurrenceDisplay {
+                sequence,
+                date_label: preferences.format_date(date),
+                status_label: if date <= today { "已到期，待生成".to_string() } else { "待生成".to_string() },
+                status: "pending".to_string(),
+                transaction_id: 0,
+                is_due: date <= today,
+            },
+        });
+        sequence += 1;
+    }
+
+    let history = RecurringOccurrence::find_recent(&app_state.db_pool, rule.id, rule.next_sequence, HISTORY_COUNT)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|occurrence| OccurrenceDisplay::recorded(occurrence, &preferences))
+        .collect();
+
+    let template = RecurringDetailTemplate {
+        user,
+        categories: category_options(&app_state, account_book_id)
+            .await
+            .into_iter()
+            .filter(|c| c.category_type == rule.transaction_type)
+            .collect(),
+        wallets: wallet_options(&app_state, account_book_id).await,
+        currency_options: currency_options(&book.currency),
+        book,
+        rule: RuleDisplay::new(row, &preferences),
+        upcoming,
+        history,
+        blocked_count,
+        creator_lost_access,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+impl OccurrenceDisplay {
+    fn recorded(occurrence: &RecurringOccurrence, preferences: &UserPreferences) -> Self {
+        let status_label = match (occurrence.status.as_str(), occurrence.transaction_id) {
+            ("skipped", _) => "已跳过",
+            ("blocked", _) => "所在期间已结账，未生成",
+            (_, Some(_)) => "已生成",
+            _ => "已生成（交易已删除）",
+        };
+        Self {
+            sequence: occurrence.sequence,
+            date_label: preferences.format_date(occurrence.occurrence_date),
+            status_label: status_label.to_string(),
+            status: occurrence.status.clone(),
+            transaction_id: occurrence.transaction_id.unwrap_or_default(),
+            is_due: false,
+        }
+    }
+}
+
+// 修改规则，只影响之后生成的交易
+pub async fn update(
+    user: CurrentUser,
+    Path((account_book_id, rule_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Form(form): Form<UpdateRecurringForm>,
+) -> Redirect {
+    let url = detail_url(account_book_id, rule_id);
+
+    let book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    };
+    let rule = match find_rule(&app_state, account_book_id, rule_id).await {
+        Some(rule) => rule,
+        None => return redirect_with_error(&list_url(account_book_id), "重复记账规则不存在"),
+    };
+
+    if let Err(message) = validate_category(&app_state, account_book_id, form.category_id, &rule.transaction_type).await {
+        return redirect_with_error(&url, &message);
+    }
+
+    let fields = match validate_common(
+        &app_state,
+        &book,
+        form.wallet_id.as_deref(),
+        &form.amount,
+        form.original_currency.as_deref(),
+        form.exchange_rate.as_deref(),
+        form.end_date.as_deref(),
+        form.max_occurrences.as_deref(),
+        form.description,
+        form.tags,
+    ).await {
+        Ok(fields) => fields,
+        Err(message) => return redirect_with_error(&url, &message),
+    };
+    if fields.end_date.is_some_and(|end_date| end_date < rule.start_date) {
+        return redirect_with_error(&url, "结束日期不能早于起始日期");
+    }
+
+    let update = UpdateRecurringTransaction {
+        category_id: form.category_id,
+        wallet_id: fields.wallet_id,
+        original_currency: fields.original_currency,
+        original_amount: fields.original_amount,
+        exchange_rate: fields.exchange_rate,
+        description: fields.description,
+        tags: fields.tags,
+        end_date: fields.end_date,
+        max_occurrences: fields.max_occurrences,
+    };
+
+    match RecurringTransaction::update(&app_state.db_pool, rule_id, update).await {
+        Ok(_) => Redirect::to(&format!("{}?success=规则已更新，之后生成的交易按新设置记录", url)),
+        Err(_) => redirect_with_error(&url, "更新规则失败"),
+    }
+}
+
+// 暂停或恢复规则
+pub async fn toggle(
+    user: CurrentUser,
+    Path((account_book_id, rule_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let url = detail_url(account_book_id, rule_id);
+
+    let book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    };
+    let rule = match find_rule(&app_state, account_book_id, rule_id).await {
+        Some(rule) => rule,
+        None => return redirect_with_error(&list_url(account_book_id), "重复记账规则不存在"),
+    };
+
+    if rule.is_active {
+        return match RecurringTransaction::set_active(&app_state.db_pool, rule_id, false).await {
+            Ok(_) => Redirect::to(&format!("{}?success=规则已暂停", url)),
+            Err(_) => redirect_with_error(&url, "更新规则失败"),
+        };
+    }
+
+    // 原创建者已不能编辑账本时，由恢复规则的成员接管记账
+    let result = match RecurringService::booking_user(&app_state.db_pool, &rule, &book).await {
+        Ok(Some(_)) => RecurringTransaction::set_active(&app_state.db_pool, rule_id, true)
+            .await
+            .map(|_| "规则已恢复，暂停期间到期的交易会补生成"),
+        Ok(None) => RecurringTransaction::resume_as(&app_state.db_pool, rule_id, user.id)
+            .await
+            .map(|_| "规则已恢复，之后生成的交易记在您名下，暂停期间到期的交易会补生成"),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(message) => Redirect::to(&format!("{}?success={}", url, urlencoding::encode(message))),
+        Err(_) => redirect_with_error(&url, "更新规则失败"),
+    }
+}
+
+// 删除规则，已生成的交易保留
+pub async fn delete(
+    user: CurrentUser,
+    Path((account_book_id, rule_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let url = list_url(account_book_id);
+
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    }
+    if find_rule(&app_state, account_book_id, rule_id).await.is_none() {
+        return redirect_with_error(&url, "重复记账规则不存在");
+    }
+
+    match RecurringTransaction::delete(&app_state.db_pool, rule_id).await {
+        Ok(_) => Redirect::to(&format!("{}?success=规则已删除，已生成的交易仍保留", url)),
+        Err(_) => redirect_with_error(&url, "删除规则失败"),
+    }
+}
+
+// 跳过尚未生成的某一次
+pub async fn skip_occurrence(
+    user: CurrentUser,
+    Path((account_book_id, rule_id, sequence)): Path<(i64, i64, i32)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let url = detail_url(account_book_id, rule_id);
+
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    }
+    if find_rule(&app_state, account_book_id, rule_id).await.is_none() {
+        return redirect_with_error(&list_url(account_book_id), "重复记账规则不存在");
+    }
+
+    match RecurringService::skip_occurrence(&app_state.db_pool, rule_id, sequence).await {
+        Ok(_) => Redirect::to(&format!("{}?success=已跳过这一次", url)),
+        Err(e) => redirect_with_error(&url, &e.to_string()),
+    }
+}
+
+// 取消跳过，到期后按规则正常生成
+pub async fn restore_occurrence(
+    user: CurrentUser,
+    Path((account_book_id, rule_id, sequence)): Path<(i64, i64, i32)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let url = detail_url(account_book_id, rule_id);
+
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    }
+    if find_rule(&app_state, account_book_id, rule_id).await.is_none() {
+        return redirect_with_error(&list_url(account_book_id), "重复记账规则不存在");
+    }
+
+    match RecurringOccurrence::restore_skipped(&app_state.db_pool, rule_id, sequence).await {
+        Ok(true) => Redirect::to(&format!("{}?success=已取消跳过", url)),
+        Ok(false) => redirect_with_error(&url, "该次未跳过或已处理"),
+        Err(_) => redirect_with_error(&url, "取消跳过失败"),
+    }
+}
+
+// 单独修改某一次：提前生成这一次的交易，然后打开交易编辑页面
+pub async fn edit_occurrence(
+    user: CurrentUser,
+    Path((account_book_id, rule_id, sequence)): Path<(i64, i64, i32)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let url = detail_url(account_book_id, rule_id);
+
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    }
+    if find_rule(&app_state, account_book_id, rule_id).await.is_none() {
+        return redirect_with_error(&list_url(account_book_id), "重复记账规则不存在");
+    }
+
+    match RecurringService::generate_occurrence(&app_state.db_pool, rule_id, sequence).await {
+        Ok(transaction_id) => Redirect::to(&format!(
+            "/account-books/{}/transactions/{}/edit",
+            account_book_id, transaction_id
+        )),
+        Err(e) => redirect_with_error(&url, &e.to_string()),
+    }
+}
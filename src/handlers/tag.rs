@@ -0,0 +1,182 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, Redirect},
+    Form,
+};
+use askama::Template;
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::handlers::account_book::AccountBookDisplay;
+use crate::middleware::{AppState, CurrentUser};
+use crate::models::{AccountBook, BookRole, Tag};
+use crate::utils::TagForm;
+
+#[derive(Template)]
+#[template(path = "tags/list.html")]
+struct TagListTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    tags: Vec<TagDisplay>,
+    success: String,
+    error: String,
+}
+
+pub struct TagDisplay {
+    pub id: i64,
+    pub name: String,
+    pub transaction_count: i64,
+    // 带该标签筛选交易列表的查询参数
+    pub filter_query: String,
+}
+
+#[derive(Deserialize)]
+pub struct TagQuery {
+    success: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeTagForm {
+    pub target_id: i64,
+}
+
+// 标签列表及使用次数，任意成员可以查看，编辑者及以上可以管理
+pub async fn list(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Query(query): Query<TagQuery>,
+) -> Result<Html<String>, Redirect> {
+    let book = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some((book, role))) => AccountBookDisplay::from(book).with_role(role),
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
+    };
+
+    let tags = match Tag::find_by_account_book_with_counts(&app_state.db_pool, account_book_id).await {
+        Ok(tags) => tags
+            .into_iter()
+            .map(|row| TagDisplay {
+                filter_query: format!("tag={}", urlencoding::encode(&row.tag.name)),
+                id: row.tag.id,
+                name: row.tag.name,
+                transaction_count: row.transaction_count,
+            })
+            .collect(),
+        Err(_) => return Err(Redirect::to(&format!("/account-books/{}?error=加载标签失败", account_book_id))),
+    };
+
+    let template = TagListTemplate {
+        user,
+        book,
+        tags,
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 验证编辑权限并取出属于该账本的标签
+async fn find_editable_tag(
+    app_state: &AppState,
+    account_book_id: i64,
+    tag_id: i64,
+    user_id: i64,
+) -> Result<Tag, Redirect> {
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user_id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限编辑")),
+        Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
+    }
+
+    match Tag::find_by_id(&app_state.db_pool, tag_id).await {
+        Ok(Some(tag)) if tag.account_book_id == account_book_id => Ok(tag),
+        _ => Err(Redirect::to(&format!("/account-books/{}/tags?error=标签不存在", account_book_id))),
+    }
+}
+
+// 重命名标签，已使用该标签的交易随之更新；新名称已存在时请使用合并
+pub async fn rename(
+    user: CurrentUser,
+    Path((account_book_id, tag_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Form(form): Form<TagForm>,
+) -> Redirect {
+    let tag = match find_editable_tag(&app_state, account_book_id, tag_id, user.id).await {
+        Ok(tag) => tag,
+        Err(redirect) => return redirect,
+    };
+
+    let form = TagForm { name: form.name.trim().to_string() };
+    if let Err(errors) = form.validate() {
+        let error_msg = errors
+            .field_errors()
+            .values()
+            .flat_map(|errs| errs.iter())
+            .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Redirect::to(&format!("/account-books/{}/tags?error={}",
+            account_book_id, urlencoding::encode(&error_msg)));
+    }
+    if form.name.contains([',', '，']) {
+        return Redirect::to(&format!("/account-books/{}/tags?error=标签名称不能包含逗号", account_book_id));
+    }
+
+    // 只改变大小写时名称仍匹配自身
+    match Tag::find_by_name(&app_state.db_pool, account_book_id, &form.name).await {
+        Ok(Some(existing)) if existing.id != tag.id => {
+            return Redirect::to(&format!("/account-books/{}/tags?error={}", account_book_id,
+                urlencoding::encode(&format!("已存在标签「{}」，如需合并请使用合并功能", existing.name))));
+        }
+        Ok(_) => {}
+        Err(_) => return Redirect::to(&format!("/account-books/{}/tags?error=重命名标签失败", account_book_id)),
+    }
+
+    match Tag::rename(&app_state.db_pool, tag.id, &form.name).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/tags?success=标签已重命名", account_book_id)),
+        Err(_) => Redirect::to(&format!("/account-books/{}/tags?error=重命名标签失败", account_book_id)),
+    }
+}
+
+// 把标签合并到另一个标签，原标签删除
+pub async fn merge(
+    user: CurrentUser,
+    Path((account_book_id, tag_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Form(form): Form<MergeTagForm>,
+) -> Redirect {
+    let source = match find_editable_tag(&app_state, account_book_id, tag_id, user.id).await {
+        Ok(tag) => tag,
+        Err(redirect) => return redirect,
+    };
+
+    let target = match Tag::find_by_id(&app_state.db_pool, form.target_id).await {
+        Ok(Some(tag)) if tag.account_book_id == account_book_id && tag.id != source.id => tag,
+        _ => return Redirect::to(&format!("/account-books/{}/tags?error=请选择要合并到的其他标签", account_book_id)),
+    };
+
+    match Tag::merge(&app_state.db_pool, source.id, target.id).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/tags?success={}", account_book_id,
+            urlencoding::encode(&format!("已将「{}」合并到「{}」", source.name, target.name)))),
+        Err(_) => Redirect::to(&format!("/account-books/{}/tags?error=合并标签失败", account_book_id)),
+    }
+}
+
+// 删除标签，相关交易只移除该标签
+pub async fn delete(
+    user: CurrentUser,
+    Path((account_book_id, tag_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let tag = match find_editable_tag(&app_state, account_book_id, tag_id, user.id).await {
+        Ok(tag) => tag,
+        Err(redirect) => return redirect,
+    };
+
+    match Tag::delete(&app_state.db_pool, tag.id).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/tags?success=标签已删除", account_book_id)),
+        Err(_) => Redirect::to(&format!("/account-books/{}/tags?error=删除标签失败", account_book_id)),
+    }
+}
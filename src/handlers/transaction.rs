@@ -1,14 +1,19 @@
 use axum::{
-    extract::{Path, Query, State, Form},
+    extract::{Path, Query, State},
     response::{Html, Redirect},
 };
+// 拆分明细以重复的表单字段提交，需要支持多值的表单解析
+use axum_extra::extract::Form;
 use askama::Template;
 use serde::{Deserialize, Serialize};
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
 
+use crate::handlers::profile::SelectOption;
 use crate::middleware::{CurrentUser, AppState};
-use crate::models::{AccountBook, Transaction, CreateTransaction, Category};
+use crate::models::{parse_tag_names, wallet_type_icon, AccountBook, BookRole, Tag, Transaction, TransactionAmount, TransactionFilter, TransactionSplit, NewTransactionSplit, SplitLine, CreateTransaction, UpdateTransaction, Category, Wallet};
+use crate::services::ExchangeRateService;
+use crate::utils::{currency_symbol, SUPPORTED_CURRENCIES};
 
 #[derive(Template)]
 #[template(path = "transactions/list.html")]
@@ -18,6 +23,19 @@ struct TransactionListTemplate {
     transactions: Vec<TransactionDisplay>,
     page: i64,
     has_next: bool,
+    filter: ListFilter,
+    category_options: Vec<CategoryOption>,
+    // 筛选条件的查询参数（以 & 结尾），用于翻页链接
+    filter_query: String,
+    is_filtered: bool,
+    // 符合筛选条件的全部交易合计
+    filtered_income: Decimal,
+    filtered_expense: Decimal,
+    filtered_balance: Decimal,
+    filtered_count: i64,
+    currency_symbol: String,
+    // 查看者只能浏览，不显示新增/编辑/删除入口
+    can_edit: bool,
     success: String,
     error: String,
 }
@@ -28,6 +46,12 @@ struct NewTransactionTemplate {
     user: CurrentUser,
     account_book: AccountBookDisplay,
     categories: Vec<CategoryDisplay>,
+    wallets: Vec<WalletDisplay>,
+    currency_options: Vec<SelectOption>,
+    // 账本已有的标签，按使用次数排序，用于输入时自动补全
+    tag_names: Vec<String>,
+    // 新建时没有拆分明细，与编辑页面共用拆分表单
+    splits: Vec<SplitDisplay>,
     error: String,
 }
 
@@ -38,6 +62,10 @@ struct EditTransactionTemplate {
     account_book: AccountBookDisplay,
     transaction: TransactionDisplay,
     categories: Vec<CategoryDisplay>,
+    wallets: Vec<WalletDisplay>,
+    currency_options: Vec<SelectOption>,
+    tag_names: Vec<String>,
+    splits: Vec<SplitDisplay>,
     error: String,
 }
 
@@ -47,12 +75,19 @@ pub struct AccountBookDisplay {
     pub name: String,
     pub description: String,
     pub currency: String,
+    // 已结账截止日期，该日期及之前的交易不能修改
+    pub closed_through: Option<NaiveDate>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct TransactionDisplay {
     pub id: i64,
     pub amount: Decimal,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub exchange_rate: Decimal,
+    // 原始货币与账本货币不同
+    pub is_foreign: bool,
     pub transaction_type: String,
     pub description: String,
     pub transaction_date: NaiveDate,
@@ -62,6 +97,24 @@ pub struct TransactionDisplay {
     pub category_icon: String,
     pub category_color: String,
     pub tags: String,
+    // 拆分明细的分类名称，未拆分时为空
+    pub split_categories: String,
+    // 未指定账户时为0
+    pub wallet_id: i64,
+    pub wallet_name: String,
+    // 该笔交易后所在账户的余额
+    pub wallet_balance: Option<Decimal>,
+    pub created_by_name: String,
+    // 位于已结账期间内，不能编辑或删除
+    pub is_locked: bool,
+}
+
+// 拆分明细的一行，金额为交易的原始货币
+#[derive(Debug, Serialize)]
+pub struct SplitDisplay {
+    pub category_id: i64,
+    pub amount: Decimal,
+    pub note: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -73,30 +126,145 @@ pub struct CategoryDisplay {
     pub color: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct WalletDisplay {
+    pub id: i64,
+    pub name: String,
+    pub icon: String,
+}
+
+pub struct CategoryOption {
+    pub id: i64,
+    pub name: String,
+    pub category_type: String,
+    pub selected: bool,
+}
+
+// 交易列表的筛选条件，保存查询参数的原始值用于回填表单和生成分享链接
+#[derive(Default)]
+pub struct ListFilter {
+    pub start_date: String,
+    pub end_date: String,
+    pub transaction_type: String,
+    pub category_ids: Vec<i64>,
+    pub min_amount: String,
+    pub max_amount: String,
+    pub tag: String,
+    pub keyword: String,
+}
+
+impl ListFilter {
+    // 解析为查询条件，格式错误的条件忽略并返回提示
+    fn to_filter(&self) -> (TransactionFilter, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut parse_date = |value: &str, label: &str| {
+            if value.is_empty() {
+                return None;
+            }
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+            if date.is_none() {
+                errors.push(format!("{}格式错误", label));
+            }
+            date
+        };
+        let start_date = parse_date(&self.start_date, "起始日期");
+        let end_date = parse_date(&self.end_date, "结束日期");
+
+        let mut parse_amount = |value: &str, label: &str| {
+            if value.is_empty() {
+                return None;
+            }
+            let amount = value.parse::<Decimal>().ok();
+            if amount.is_none() {
+                errors.push(format!("{}格式错误", label));
+            }
+            amount
+        };
+        let min_amount = parse_amount(&self.min_amount, "最小金额");
+        let max_amount = parse_amount(&self.max_amount, "最大金额");
+
+        let filter = TransactionFilter {
+            start_date,
+            end_date,
+            transaction_type: Some(self.transaction_type.clone())
+                .filter(|t| t == "income" || t == "expense"),
+            category_ids: self.category_ids.clone(),
+            min_amount,
+            max_amount,
+            tag: Some(self.tag.clone()).filter(|s| !s.is_empty()),
+            keyword: Some(self.keyword.clone()).filter(|s| !s.is_empty()),
+        };
+
+        (filter, errors)
+    }
+
+    fn query_string(&self) -> String {
+        let mut pairs: Vec<(&str, String)> = vec![
+            ("start_date", self.start_date.clone()),
+            ("end_date", self.end_date.clone()),
+            ("type", self.transaction_type.clone()),
+        ];
+        pairs.extend(self.category_ids.iter().map(|id| ("category_id", id.to_string())));
+        pairs.extend([
+            ("min_amount", self.min_amount.clone()),
+            ("max_amount", self.max_amount.clone()),
+            ("tag", self.tag.clone()),
+            ("q", self.keyword.clone()),
+        ]);
+
+        pairs
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| format!("{}={}&", key, urlencoding::encode(&value)))
+            .collect()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct TransactionQuery {
-    page: Option<i64>,
-    success: Option<String>,
     error: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct CreateTransactionForm {
-    pub category_id: i64,
+    // 拆分时为空，交易的分类取第一行拆分的分类
+    pub category_id: Option<i64>,
+    // 为空时不指定账户
+    pub wallet_id: Option<String>,
     pub amount: String,
+    // 为空时使用账本货币
+    pub original_currency: Option<String>,
+    // 外币交易可手动填写汇率，为空时查找已导入的汇率
+    pub exchange_rate: Option<String>,
     pub transaction_type: String,
     pub description: Option<String>,
     pub transaction_date: String,
     pub tags: Option<String>,
+    // 拆分明细：同一行的分类、金额和备注在各自列表中的位置相同
+    #[serde(default)]
+    pub split_category_id: Vec<String>,
+    #[serde(default)]
+    pub split_amount: Vec<String>,
+    #[serde(default)]
+    pub split_note: Vec<String>,
 }
 
 #[derive(Deserialize)]
 pub struct UpdateTransactionForm {
-    pub category_id: i64,
+    pub category_id: Option<i64>,
+    pub wallet_id: Option<String>,
     pub amount: String,
+    pub original_currency: Option<String>,
+    pub exchange_rate: Option<String>,
     pub description: Option<String>,
     pub transaction_date: String,
     pub tags: Option<String>,
+    #[serde(default)]
+    pub split_category_id: Vec<String>,
+    #[serde(default)]
+    pub split_amount: Vec<String>,
+    #[serde(default)]
+    pub split_note: Vec<String>,
 }
 
 impl From<crate::models::AccountBook> for AccountBookDisplay {
@@ -106,6 +274,7 @@ impl From<crate::models::AccountBook> for AccountBookDisplay {
             name: book.name,
             description: book.description.unwrap_or_default(),
             currency: book.currency,
+            closed_through: book.closed_through,
         }
     }
 }
@@ -115,6 +284,10 @@ impl From<crate::models::TransactionWithCategory> for TransactionDisplay {
         Self {
             id: t.id,
             amount: t.amount,
+            is_foreign: false,
+            original_currency: t.original_currency,
+            original_amount: t.original_amount,
+            exchange_rate: t.exchange_rate.normalize(),
             transaction_type: t.transaction_type,
             description: t.description.unwrap_or_default(),
             transaction_date: t.transaction_date,
@@ -124,10 +297,32 @@ impl From<crate::models::TransactionWithCategory> for TransactionDisplay {
             category_icon: t.category_icon.unwrap_or("tag".to_string()),
             category_color: t.category_color.unwrap_or("#007bff".to_string()),
             tags: t.tags.unwrap_or_default(),
+            split_categories: t.split_categories.unwrap_or_default(),
+            wallet_id: t.wallet_id.unwrap_or(0),
+            wallet_name: t.wallet_name.unwrap_or_default(),
+            wallet_balance: t.wallet_balance,
+            created_by_name: t.created_by_name.unwrap_or_default(),
+            is_locked: false,
         }
     }
 }
 
+impl TransactionDisplay {
+    fn with_book(mut self, book: &AccountBookDisplay) -> Self {
+        self.is_foreign = self.original_currency != book.currency;
+        self.is_locked = book.closed_through.is_some_and(|closed_through| self.transaction_date <= closed_through);
+        self
+    }
+}
+
+// 已结账期间的提示
+pub(crate) fn closed_period_message(closed_through: Option<NaiveDate>) -> String {
+    format!(
+        "{} 及之前的期间已结账，不能新增、修改或删除交易，如需调整请联系账本所有者重新开放",
+        closed_through.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default()
+    )
+}
+
 impl From<crate::models::Category> for CategoryDisplay {
     fn from(c: crate::models::Category) -> Self {
         Self {
@@ -140,20 +335,195 @@ impl From<crate::models::Category> for CategoryDisplay {
     }
 }
 
-// 交易列表
+impl From<Wallet> for WalletDisplay {
+    fn from(wallet: Wallet) -> Self {
+        Self {
+            id: wallet.id,
+            icon: wallet_type_icon(&wallet.wallet_type).to_string(),
+            name: wallet.name,
+        }
+    }
+}
+
+async fn load_tag_names(app_state: &AppState, account_book_id: i64) -> Vec<String> {
+    Tag::find_by_account_book_with_counts(&app_state.db_pool, account_book_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|t| t.tag.name)
+        .collect()
+}
+
+async fn load_wallets(app_state: &AppState, account_book_id: i64) -> Vec<WalletDisplay> {
+    Wallet::find_by_account_book(&app_state.db_pool, account_book_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(WalletDisplay::from)
+        .collect()
+}
+
+// 解析表单中的资金账户，为空表示不指定账户；交易原来关联的账户即使已删除也可以保留
+async fn resolve_wallet(
+    app_state: &AppState,
+    account_book_id: i64,
+    wallet_id: Option<&str>,
+    current_wallet_id: Option<i64>,
+) -> Result<Option<i64>, String> {
+    let wallet_id = match wallet_id.map(str::trim) {
+        None | Some("") | Some("0") => return Ok(None),
+        Some(id) => id.parse::<i64>().map_err(|_| "账户无效".to_string())?,
+    };
+
+    if current_wallet_id == Some(wallet_id) {
+        return Ok(Some(wallet_id));
+    }
+
+    match Wallet::find_by_id(&app_state.db_pool, wallet_id).await {
+        Ok(Some(wallet)) if wallet.account_book_id == account_book_id => Ok(Some(wallet_id)),
+        _ => Err("账户不存在或无权限访问".to_string()),
+    }
+}
+
+pub(crate) fn currency_options(book_currency: &str) -> Vec<SelectOption> {
+    let mut options: Vec<SelectOption> = SUPPORTED_CURRENCIES
+        .iter()
+        .map(|(code, name, symbol)| SelectOption::new(code, format!("{} {} ({})", code, name, symbol)))
+        .collect();
+    if !options.iter().any(|option| option.value == book_currency) {
+        options.insert(0, SelectOption::new(book_currency, book_currency));
+    }
+    options
+}
+
+// 解析表单中的原始金额、货币和汇率，并折算为账本货币
+async fn resolve_amount(
+    app_state: &AppState,
+    user_id: i64,
+    book_currency: &str,
+    amount: &str,
+    original_currency: Option<&str>,
+    exchange_rate: Option<&str>,
+    transaction_date: NaiveDate,
+) -> Result<TransactionAmount, String> {
+    let original_amount = match amount.parse::<Decimal>() {
+        Ok(amt) if amt > Decimal::ZERO => amt,
+        _ => return Err("金额必须大于0".to_string()),
+    };
+
+    let original_currency = match original_currency.map(str::trim) {
+        None | Some("") => book_currency.to_string(),
+        Some(code) => code.to_uppercase(),
+    };
+
+    let manual_rate = match exchange_rate.map(str::trim) {
+        None | Some("") => None,
+        Some(rate) => Some(rate.parse::<Decimal>().map_err(|_| "汇率格式错误".to_string())?),
+    };
+
+    ExchangeRateService::convert(
+        &app_state.db_pool,
+        user_id,
+        book_currency,
+        &original_currency,
+        original_amount,
+        manual_rate,
+        transaction_date,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// 读取表单中的拆分行，分类和金额都未填写的行忽略
+fn parse_split_lines(category_ids: &[String], amounts: &[String], notes: &[String]) -> Result<Vec<SplitLine>, String> {
+    let mut lines = Vec::new();
+    for (index, category_id) in category_ids.iter().enumerate() {
+        let category_id = category_id.trim();
+        let amount = amounts.get(index).map(|amount| amount.trim()).unwrap_or_default();
+        if category_id.is_empty() && amount.is_empty() {
+            continue;
+        }
+
+        let row = lines.len() + 1;
+        let category_id = category_id
+            .parse::<i64>()
+            .map_err(|_| format!("请选择第{}行拆分的分类", row))?;
+        let amount = amount
+            .parse::<Decimal>()
+            .map_err(|_| format!("第{}行拆分的金额格式错误", row))?;
+        lines.push(SplitLine {
+            category_id,
+            amount,
+            note: notes.get(index).cloned(),
+        });
+    }
+    Ok(lines)
+}
+
+// 校验拆分行的分类属于该账本且与交易类型一致，并折算各行金额；返回交易的分类（第一行）和拆分明细
+async fn resolve_splits(
+    app_state: &AppState,
+    account_book_id: i64,
+    transaction_type: &str,
+    amount: &TransactionAmount,
+    lines: &[SplitLine],
+) -> Result<(i64, Vec<NewTransactionSplit>), String> {
+    for line in lines {
+        match Category::find_by_id(&app_state.db_pool, line.category_id).await {
+            Ok(Some(category)) if category.account_book_id == account_book_id => {
+                if category.category_type != transaction_type {
+                    return Err("拆分的分类类型与交易类型不匹配".to_string());
+                }
+            }
+            _ => return Err("拆分的分类不存在或无权限访问".to_string()),
+        }
+    }
+
+    let splits = TransactionSplit::allocate(amount, lines).map_err(|e| e.to_string())?;
+    Ok((lines[0].category_id, splits))
+}
+
+// 交易列表，支持按日期、类型、分类、金额、标签和描述筛选；
+// 分类可多选（重复的 category_id 参数），因此按键值对读取查询参数
 pub async fn list(
     user: CurrentUser,
     Path(account_book_id): Path<i64>,
-    Query(query): Query<TransactionQuery>,
+    Query(params): Query<Vec<(String, String)>>,
     State(app_state): State<AppState>,
 ) -> Result<Html<String>, Redirect> {
-    // 验证账本所有权
-    let account_book = match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
-        Ok(Some(book)) => AccountBookDisplay::from(book),
+    // 验证账本成员身份
+    let (account_book, role) = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some((book, role))) => (AccountBookDisplay::from(book), role),
         _ => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
     };
 
-    let page = query.page.unwrap_or(1);
+    let mut page = 1;
+    let mut success = String::new();
+    let mut error = String::new();
+    let mut list_filter = ListFilter::default();
+    for (key, value) in params {
+        let value = value.trim().to_string();
+        match key.as_str() {
+            "page" => page = value.parse::<i64>().unwrap_or(1).max(1),
+            "success" => success = value,
+            "error" => error = value,
+            "start_date" => list_filter.start_date = value,
+            "end_date" => list_filter.end_date = value,
+            "type" => list_filter.transaction_type = value,
+            "category_id" => list_filter.category_ids.extend(value.parse::<i64>().ok()),
+            "min_amount" => list_filter.min_amount = value,
+            "max_amount" => list_filter.max_amount = value,
+            "tag" => list_filter.tag = value,
+            "q" => list_filter.keyword = value,
+            _ => {}
+        }
+    }
+
+    let (filter, filter_errors) = list_filter.to_filter();
+    if error.is_empty() && !filter_errors.is_empty() {
+        error = filter_errors.join("；");
+    }
+
     let limit = 20;
     let offset = (page - 1) * limit;
 
@@ -161,25 +531,53 @@ pub async fn list(
     let transactions = Transaction::find_by_account_book_with_category(
         &app_state.db_pool,
         account_book_id,
+        &filter,
         limit + 1, // 多查一条用于判断是否有下一页
         offset,
     ).await.unwrap_or_default();
 
+    let (filtered_income, filtered_expense, filtered_count) =
+        Transaction::get_filtered_summary(&app_state.db_pool, account_book_id, &filter)
+            .await
+            .unwrap_or((Decimal::ZERO, Decimal::ZERO, 0));
+
+    let category_options = Category::find_by_account_book(&app_state.db_pool, account_book_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| CategoryOption {
+            selected: list_filter.category_ids.contains(&c.id),
+            id: c.id,
+            name: c.name,
+            category_type: c.category_type,
+        })
+        .collect();
+
     let has_next = transactions.len() > limit as usize;
     let transactions = transactions
         .into_iter()
         .take(limit as usize)
-        .map(TransactionDisplay::from)
+        .map(|t| TransactionDisplay::from(t).with_book(&account_book))
         .collect();
 
     let template = TransactionListTemplate {
         user,
+        currency_symbol: currency_symbol(&account_book.currency),
         account_book,
         transactions,
         page,
         has_next,
-        success: query.success.unwrap_or_default(),
-        error: query.error.unwrap_or_default(),
+        filter_query: list_filter.query_string(),
+        is_filtered: !filter.is_empty(),
+        filter: list_filter,
+        category_options,
+        filtered_income,
+        filtered_expense,
+        filtered_balance: filtered_income - filtered_expense,
+        filtered_count,
+        can_edit: role.can_edit(),
+        success,
+        error,
     };
 
     Ok(Html(template.render().unwrap()))
@@ -192,10 +590,10 @@ pub async fn show_new(
     Query(query): Query<TransactionQuery>,
     State(app_state): State<AppState>,
 ) -> Result<Html<String>, Redirect> {
-    // 验证账本所有权
-    let account_book = match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能记账
+    let account_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(book)) => AccountBookDisplay::from(book),
-        _ => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        _ => return Err(Redirect::to("/account-books?error=账本不存在或无权限编辑")),
     };
 
     // 获取账本的分类
@@ -208,6 +606,10 @@ pub async fn show_new(
 
     let template = NewTransactionTemplate {
         user,
+        currency_options: currency_options(&account_book.currency),
+        wallets: load_wallets(&app_state, account_book_id).await,
+        tag_names: load_tag_names(&app_state, account_book_id).await,
+        splits: Vec::new(),
         account_book,
         categories,
         error: query.error.unwrap_or_default(),
@@ -223,15 +625,10 @@ pub async fn create(
     State(app_state): State<AppState>,
     Form(form): Form<CreateTransactionForm>,
 ) -> Redirect {
-    // 验证账本所有权
-    if AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await.is_err() {
-        return Redirect::to("/account-books?error=账本不存在或无权限访问");
-    }
-
-    // 验证并解析金额
-    let amount = match form.amount.parse::<Decimal>() {
-        Ok(amt) if amt > Decimal::ZERO => amt,
-        _ => return Redirect::to(&format!("/account-books/{}/transactions/new?error=金额必须大于0", account_book_id)),
+    // 编辑者及以上才能记账
+    let account_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
     };
 
     // 验证并解析日期
@@ -240,30 +637,81 @@ pub async fn create(
         Err(_) => return Redirect::to(&format!("/account-books/{}/transactions/new?error=日期格式错误", account_book_id)),
     };
 
+    if account_book.is_closed(transaction_date) {
+        return Redirect::to(&format!("/account-books/{}/transactions/new?error={}",
+            account_book_id, urlencoding::encode(&closed_period_message(account_book.closed_through))));
+    }
+
+    // 验证金额，外币按汇率折算为账本货币
+    let amount = match resolve_amount(
+        &app_state,
+        user.id,
+        &account_book.currency,
+        &form.amount,
+        form.original_currency.as_deref(),
+        form.exchange_rate.as_deref(),
+        transaction_date,
+    ).await {
+        Ok(amount) => amount,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/new?error={}",
+            account_book_id, urlencoding::encode(&message))),
+    };
+
     // 验证交易类型
     if form.transaction_type != "income" && form.transaction_type != "expense" {
         return Redirect::to(&format!("/account-books/{}/transactions/new?error=交易类型无效", account_book_id));
     }
 
-    // 验证分类是否存在且属于该账本
-    match Category::find_by_id(&app_state.db_pool, form.category_id).await {
-        Ok(Some(category)) if category.account_book_id == account_book_id => {
-            // 验证分类类型与交易类型是否匹配
-            if category.category_type != form.transaction_type {
-                return Redirect::to(&format!("/account-books/{}/transactions/new?error=分类类型与交易类型不匹配", account_book_id));
+    let split_lines = match parse_split_lines(&form.split_category_id, &form.split_amount, &form.split_note) {
+        Ok(lines) => lines,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/new?error={}",
+            account_book_id, urlencoding::encode(&message))),
+    };
+
+    // 拆分时逐行验证分类，否则验证交易的分类是否存在且属于该账本
+    let (category_id, splits) = if split_lines.is_empty() {
+        let category_id = match form.category_id {
+            Some(category_id) => category_id,
+            None => return Redirect::to(&format!("/account-books/{}/transactions/new?error=请选择分类", account_book_id)),
+        };
+        match Category::find_by_id(&app_state.db_pool, category_id).await {
+            Ok(Some(category)) if category.account_book_id == account_book_id => {
+                // 验证分类类型与交易类型是否匹配
+                if category.category_type != form.transaction_type {
+                    return Redirect::to(&format!("/account-books/{}/transactions/new?error=分类类型与交易类型不匹配", account_book_id));
+                }
             }
+            _ => return Redirect::to(&format!("/account-books/{}/transactions/new?error=分类不存在或无权限访问", account_book_id)),
         }
-        _ => return Redirect::to(&format!("/account-books/{}/transactions/new?error=分类不存在或无权限访问", account_book_id)),
-    }
+        (category_id, Vec::new())
+    } else {
+        match resolve_splits(&app_state, account_book_id, &form.transaction_type, &amount, &split_lines).await {
+            Ok(resolved) => resolved,
+            Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/new?error={}",
+                account_book_id, urlencoding::encode(&message))),
+        }
+    };
+
+    let wallet_id = match resolve_wallet(&app_state, account_book_id, form.wallet_id.as_deref(), None).await {
+        Ok(wallet_id) => wallet_id,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/new?error={}",
+            account_book_id, urlencoding::encode(&message))),
+    };
 
     let create_transaction = CreateTransaction {
         account_book_id,
-        category_id: form.category_id,
-        amount,
+        category_id,
+        wallet_id,
+        amount: amount.amount,
+        original_currency: amount.original_currency,
+        original_amount: amount.original_amount,
+        exchange_rate: amount.exchange_rate,
         transaction_type: form.transaction_type,
         description: form.description.filter(|s| !s.trim().is_empty()),
         transaction_date,
-        tags: form.tags.filter(|s| !s.trim().is_empty()),
+        tags: parse_tag_names(form.tags.as_deref().unwrap_or_default()),
+        splits,
+        created_by: user.id,
     };
 
     match Transaction::create(&app_state.db_pool, create_transaction).await {
@@ -279,18 +727,18 @@ pub async fn show_edit(
     Query(query): Query<TransactionQuery>,
     State(app_state): State<AppState>,
 ) -> Result<Html<String>, Redirect> {
-    // 验证账本所有权
-    let account_book = match AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await {
+    // 编辑者及以上才能记账
+    let account_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
         Ok(Some(book)) => AccountBookDisplay::from(book),
-        _ => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        _ => return Err(Redirect::to("/account-books?error=账本不存在或无权限编辑")),
     };
 
     // 获取交易信息（包含分类信息）
-    let transaction = match Transaction::find_by_account_book_with_category(&app_state.db_pool, account_book_id, 1000, 0).await {
+    let transaction = match Transaction::find_by_account_book_with_category(&app_state.db_pool, account_book_id, &TransactionFilter::default(), 1000, 0).await {
         Ok(transactions) => {
             transactions.into_iter()
                 .find(|t| t.id == transaction_id)
-                .map(TransactionDisplay::from)
+                .map(|t| TransactionDisplay::from(t).with_book(&account_book))
         }
         _ => None,
     };
@@ -300,6 +748,11 @@ pub async fn show_edit(
         None => return Err(Redirect::to(&format!("/account-books/{}/transactions?error=交易记录不存在", account_book_id))),
     };
 
+    if transaction.is_locked {
+        return Err(Redirect::to(&format!("/account-books/{}/transactions?error={}",
+            account_book_id, urlencoding::encode(&closed_period_message(account_book.closed_through)))));
+    }
+
     // 获取账本的分类
     let categories = Category::find_by_account_book(&app_state.db_pool, account_book_id)
         .await
@@ -308,8 +761,33 @@ pub async fn show_edit(
         .map(CategoryDisplay::from)
         .collect();
 
+    let splits = TransactionSplit::find_by_transaction(&app_state.db_pool, transaction_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| SplitDisplay {
+            category_id: row.split.category_id,
+            amount: row.split.original_amount,
+            note: row.split.note.unwrap_or_default(),
+        })
+        .collect();
+
+    // 交易关联的账户已删除时仍作为选项保留
+    let mut wallets = load_wallets(&app_state, account_book_id).await;
+    if transaction.wallet_id != 0 && !wallets.iter().any(|wallet| wallet.id == transaction.wallet_id) {
+        wallets.push(WalletDisplay {
+            id: transaction.wallet_id,
+            name: format!("{}（已删除）", transaction.wallet_name),
+            icon: "wallet2".to_string(),
+        });
+    }
+
     let template = EditTransactionTemplate {
         user,
+        currency_options: currency_options(&account_book.currency),
+        wallets,
+        tag_names: load_tag_names(&app_state, account_book_id).await,
+        splits,
         account_book,
         transaction,
         categories,
@@ -326,21 +804,16 @@ pub async fn update(
     State(app_state): State<AppState>,
     Form(form): Form<UpdateTransactionForm>,
 ) -> Redirect {
-    // 验证账本所有权
-    if AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await.is_err() {
-        return Redirect::to("/account-books?error=账本不存在或无权限访问");
-    }
+    // 编辑者及以上才能记账
+    let account_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    };
 
     // 验证交易是否存在且属于该账本
-    match Transaction::find_by_id(&app_state.db_pool, transaction_id).await {
-        Ok(Some(transaction)) if transaction.account_book_id == account_book_id => {},
+    let existing = match Transaction::find_by_id(&app_state.db_pool, transaction_id).await {
+        Ok(Some(transaction)) if transaction.account_book_id == account_book_id => transaction,
         _ => return Redirect::to(&format!("/account-books/{}/transactions?error=交易记录不存在", account_book_id)),
-    }
-
-    // 验证并解析金额
-    let amount = match form.amount.parse::<Decimal>() {
-        Ok(amt) if amt > Decimal::ZERO => amt,
-        _ => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error=金额必须大于0", account_book_id, transaction_id)),
     };
 
     // 验证并解析日期
@@ -349,21 +822,69 @@ pub async fn update(
         Err(_) => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error=日期格式错误", account_book_id, transaction_id)),
     };
 
-    // 验证分类是否存在且属于该账本
-    match Category::find_by_id(&app_state.db_pool, form.category_id).await {
-        Ok(Some(category)) if category.account_book_id == account_book_id => {},
-        _ => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error=分类不存在或无权限访问", account_book_id, transaction_id)),
+    // 原日期和新日期都不能位于已结账期间
+    if account_book.is_closed(existing.transaction_date) || account_book.is_closed(transaction_date) {
+        return Redirect::to(&format!("/account-books/{}/transactions?error={}",
+            account_book_id, urlencoding::encode(&closed_period_message(account_book.closed_through))));
     }
 
-    match Transaction::update(
-        &app_state.db_pool,
-        transaction_id,
-        form.category_id,
-        amount,
-        form.description.as_deref(),
+    // 验证金额，外币按汇率折算为账本货币
+    let amount = match resolve_amount(
+        &app_state,
+        user.id,
+        &account_book.currency,
+        &form.amount,
+        form.original_currency.as_deref(),
+        form.exchange_rate.as_deref(),
         transaction_date,
-        form.tags.as_deref(),
     ).await {
+        Ok(amount) => amount,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error={}",
+            account_book_id, transaction_id, urlencoding::encode(&message))),
+    };
+
+    let split_lines = match parse_split_lines(&form.split_category_id, &form.split_amount, &form.split_note) {
+        Ok(lines) => lines,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error={}",
+            account_book_id, transaction_id, urlencoding::encode(&message))),
+    };
+
+    // 拆分时逐行验证分类，否则验证交易的分类是否存在且属于该账本
+    let (category_id, splits) = if split_lines.is_empty() {
+        let category_id = match form.category_id {
+            Some(category_id) => category_id,
+            None => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error=请选择分类", account_book_id, transaction_id)),
+        };
+        match Category::find_by_id(&app_state.db_pool, category_id).await {
+            Ok(Some(category)) if category.account_book_id == account_book_id => {},
+            _ => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error=分类不存在或无权限访问", account_book_id, transaction_id)),
+        }
+        (category_id, Vec::new())
+    } else {
+        match resolve_splits(&app_state, account_book_id, &existing.transaction_type, &amount, &split_lines).await {
+            Ok(resolved) => resolved,
+            Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error={}",
+                account_book_id, transaction_id, urlencoding::encode(&message))),
+        }
+    };
+
+    let wallet_id = match resolve_wallet(&app_state, account_book_id, form.wallet_id.as_deref(), existing.wallet_id).await {
+        Ok(wallet_id) => wallet_id,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error={}",
+            account_book_id, transaction_id, urlencoding::encode(&message))),
+    };
+
+    let update_transaction = UpdateTransaction {
+        category_id,
+        wallet_id,
+        amount,
+        description: form.description,
+        transaction_date,
+        tags: parse_tag_names(form.tags.as_deref().unwrap_or_default()),
+        splits,
+    };
+
+    match Transaction::update(&app_state.db_pool, transaction_id, update_transaction).await {
         Ok(_) => Redirect::to(&format!("/account-books/{}/transactions?success=交易记录更新成功", account_book_id)),
         Err(_) => Redirect::to(&format!("/account-books/{}/transactions/{}/edit?error=更新交易记录失败", account_book_id, transaction_id)),
     }
@@ -375,15 +896,21 @@ pub async fn delete(
     Path((account_book_id, transaction_id)): Path<(i64, i64)>,
     State(app_state): State<AppState>,
 ) -> Redirect {
-    // 验证账本所有权
-    if AccountBook::find_by_id(&app_state.db_pool, account_book_id, user.id).await.is_err() {
-        return Redirect::to("/account-books?error=账本不存在或无权限访问");
-    }
+    // 编辑者及以上才能记账
+    let account_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    };
 
     // 验证交易是否存在且属于该账本
-    match Transaction::find_by_id(&app_state.db_pool, transaction_id).await {
-        Ok(Some(transaction)) if transaction.account_book_id == account_book_id => {},
+    let transaction = match Transaction::find_by_id(&app_state.db_pool, transaction_id).await {
+        Ok(Some(transaction)) if transaction.account_book_id == account_book_id => transaction,
         _ => return Redirect::to(&format!("/account-books/{}/transactions?error=交易记录不存在", account_book_id)),
+    };
+
+    if account_book.is_closed(transaction.transaction_date) {
+        return Redirect::to(&format!("/account-books/{}/transactions?error={}",
+            account_book_id, urlencoding::encode(&closed_period_message(account_book.closed_through))));
     }
 
     match Transaction::delete(&app_state.db_pool, transaction_id).await {
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, Redirect},
+    Form,
+};
+use askama::Template;
+use serde::Deserialize;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::handlers::account_book::AccountBookDisplay;
+use crate::middleware::{AppState, CurrentUser};
+use crate::models::{AccountBook, BookRole, CreateTransfer, Transfer, TransferWithWallets, UserPreferences, Wallet};
+use crate::services::ExchangeRateService;
+use crate::utils::currency_symbol;
+
+#[derive(Template)]
+#[template(path = "transfers/list.html")]
+struct TransferListTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    transfers: Vec<TransferDisplay>,
+    // 转出账户只能是当前账本的账户
+    from_wallets: Vec<WalletOption>,
+    // 转入账户按账本分组，包括用户可以编辑的其他账本
+    to_books: Vec<BookWallets>,
+    today: String,
+    success: String,
+    error: String,
+}
+
+pub struct TransferDisplay {
+    pub id: i64,
+    pub date_label: String,
+    pub description: String,
+    pub from_label: String,
+    pub to_label: String,
+    // 相对当前账本的方向：out 转出、in 转入、internal 账本内转账
+    pub direction: String,
+    pub amount_label: String,
+    pub can_delete: bool,
+}
+
+pub struct WalletOption {
+    pub id: i64,
+    pub name: String,
+}
+
+pub struct BookWallets {
+    pub name: String,
+    pub currency: String,
+    pub wallets: Vec<WalletOption>,
+}
+
+#[derive(Deserialize)]
+pub struct TransferQuery {
+    success: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTransferForm {
+    pub from_wallet_id: i64,
+    pub to_wallet_id: i64,
+    pub amount: String,
+    // 跨币种转账的到账金额，为空时按导入的汇率折算
+    pub to_amount: Option<String>,
+    pub transfer_date: String,
+    pub description: Option<String>,
+}
+
+// 账本已结账时的提示
+fn closed_message(book: &AccountBook) -> String {
+    format!(
+        "账本「{}」{} 及之前的期间已结账，不能新增或删除该期间的转账",
+        book.name,
+        book.closed_through.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default()
+    )
+}
+
+fn redirect_with_error(account_book_id: i64, message: &str) -> Redirect {
+    Redirect::to(&format!("/account-books/{}/transfers?error={}", account_book_id, urlencoding::encode(message)))
+}
+
+impl TransferDisplay {
+    fn new(
+        row: TransferWithWallets,
+        account_book_id: i64,
+        editable_books: &HashMap<i64, AccountBook>,
+        preferences: &UserPreferences,
+    ) -> Self {
+        let transfer = row.transfer;
+        let direction = if transfer.from_account_book_id == transfer.to_account_book_id {
+            "internal"
+        } else if transfer.from_account_book_id == Some(account_book_id) {
+            "out"
+        } else {
+            "in"
+        };
+
+        let amount_label = match direction {
+            "in" => format!("+{}{}", currency_symbol(&row.to_book_currency), transfer.to_amount),
+            "out" => format!("-{}{}", currency_symbol(&row.from_book_currency), transfer.amount),
+            _ => format!("{}{}", currency_symbol(&row.from_book_currency), transfer.amount),
+        };
+
+        // 删除需要两端账本的编辑权限，且两端都未结账；另一端账本已删除时只看当前账本
+        let can_delete = [transfer.from_account_book_id, transfer.to_account_book_id]
+            .iter()
+            .flatten()
+            .all(|book_id| {
+                editable_books
+                    .get(book_id)
+                    .is_some_and(|book| !book.is_closed(transfer.transfer_date))
+            });
+
+        let label = |book_id: Option<i64>, book_name: &str, wallet_name: &str| {
+            if book_id == Some(account_book_id) {
+                wallet_name.to_string()
+            } else {
+                format!("{} · {}", book_name, wallet_name)
+            }
+        };
+
+        Self {
+            id: transfer.id,
+            date_label: preferences.format_date(transfer.transfer_date),
+            description: transfer.description.unwrap_or_default(),
+            from_label: label(transfer.from_account_book_id, &row.from_book_name, &row.from_wallet_name),
+            to_label: label(transfer.to_account_book_id, &row.to_book_name, &row.to_wallet_name),
+            direction: direction.to_string(),
+            amount_label,
+            can_delete,
+        }
+    }
+}
+
+// 用户可以编辑的全部账本
+async fn editable_books(app_state: &AppState, user_id: i64) -> HashMap<i64, AccountBook> {
+    AccountBook::find_by_user_with_role(&app_state.db_pool, user_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, role)| role.can_edit())
+        .map(|(book, _)| (book.id, book))
+        .collect()
+}
+
+async fn wallet_options(app_state: &AppState, account_book_id: i64) -> Vec<WalletOption> {
+    Wallet::find_by_account_book(&app_state.db_pool, account_book_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|wallet| WalletOption { id: wallet.id, name: wallet.name })
+        .collect()
+}
+
+// 转账记录，任意成员可以查看，编辑者及以上可以新增
+pub async fn list(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Query(query): Query<TransferQuery>,
+) -> Result<Html<String>, Redirect> {
+    let book = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some((book, role))) => AccountBookDisplay::from(book).with_role(role),
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
+    };
+
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let editable_books = editable_books(&app_state, user.id).await;
+
+    let transfers = match Transfer::find_by_account_book(&app_state.db_pool, account_book_id, 100, 0).await {
+        Ok(rows) => rows
+            .into_iter()
+            .map(|row| TransferDisplay::new(row, account_book_id, &editable_books, &preferences))
+            .collect(),
+        Err(_) => return Err(Redirect::to(&format!("/account-books/{}?error=加载转账记录失败", account_book_id))),
+    };
+
+    let (from_wallets, to_books) = if book.can_edit {
+        // 当前账本排在最前
+        let mut books: Vec<&AccountBook> = editable_books.values().collect();
+        books.sort_by_key(|b| (b.id != account_book_id, b.name.clone()));
+
+        let mut to_books = Vec::new();
+        for b in books {
+            let wallets = wallet_options(&app_state, b.id).await;
+            if !wallets.is_empty() {
+                to_books.push(BookWallets { name: b.name.clone(), currency: b.currency.clone(), wallets });
+            }
+        }
+        (wallet_options(&app_state, account_book_id).await, to_books)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    let template = TransferListTemplate {
+        user,
+        book,
+        transfers,
+        from_wallets,
+        to_books,
+        today: preferences.today().format("%Y-%m-%d").to_string(),
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 新增转账，转出和转入账本都需要编辑权限
+pub async fn create(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<CreateTransferForm>,
+) -> Redirect {
+    let from_book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+    };
+
+    match Wallet::find_by_id(&app_state.db_pool, form.from_wallet_id).await {
+        Ok(Some(wallet)) if wallet.account_book_id == account_book_id => {}
+        _ => return redirect_with_error(account_book_id, "转出账户不存在"),
+    }
+
+    let to_wallet = match Wallet::find_by_id(&app_state.db_pool, form.to_wallet_id).await {
+        Ok(Some(wallet)) => wallet,
+        _ => return redirect_with_error(account_book_id, "转入账户不存在"),
+    };
+
+    if to_wallet.id == form.from_wallet_id {
+        return redirect_with_error(account_book_id, "转出和转入账户不能相同");
+    }
+
+    let to_book = match AccountBook::find_with_min_role(&app_state.db_pool, to_wallet.account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        _ => return redirect_with_error(account_book_id, "转入账本不存在或无权限编辑"),
+    };
+
+    let transfer_date = match NaiveDate::parse_from_str(&form.transfer_date, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => return redirect_with_error(account_book_id, "日期格式错误"),
+    };
+
+    for book in [&from_book, &to_book] {
+        if book.is_closed(transfer_date) {
+            return redirect_with_error(account_book_id, &closed_message(book));
+        }
+    }
+
+    let amount = match form.amount.trim().parse::<Decimal>() {
+        Ok(amount) if amount > Decimal::ZERO => amount.round_dp(2),
+        _ => return redirect_with_error(account_book_id, "金额必须大于0"),
+    };
+
+    // 同币种到账金额与转出金额相同；跨币种可手动填写，否则按汇率折算
+    let to_amount = if from_book.currency == to_book.currency {
+        amount
+    } else {
+        match form.to_amount.as_deref().map(str::trim) {
+            None | Some("") => match ExchangeRateService::convert(
+                &app_state.db_pool,
+                user.id,
+                &to_book.currency,
+                &from_book.currency,
+                amount,
+                None,
+                transfer_date,
+            ).await {
+                Ok(converted) => converted.amount,
+                Err(e) => return redirect_with_error(account_book_id, &format!("{}，也可以直接填写到账金额", e)),
+            },
+            Some(value) => match value.parse::<Decimal>() {
+                Ok(to_amount) if to_amount > Decimal::ZERO => to_amount.round_dp(2),
+                _ => return redirect_with_error(account_book_id, "到账金额必须大于0"),
+            },
+        }
+    };
+
+    let description = form
+        .description
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    if description.as_ref().is_some_and(|s| s.chars().count() > 500) {
+        return redirect_with_error(account_book_id, "说明长度不能超过500字符");
+    }
+
+    let create_transfer = CreateTransfer {
+        from_account_book_id: from_book.id,
+        from_wallet_id: form.from_wallet_id,
+        to_account_book_id: to_book.id,
+        to_wallet_id: to_wallet.id,
+        amount,
+        to_amount,
+        description,
+        transfer_date,
+        created_by: user.id,
+    };
+
+    match Transfer::create(&app_state.db_pool, create_transfer).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/transfers?success=转账已记录", account_book_id)),
+        Err(_) => redirect_with_error(account_book_id, "记录转账失败"),
+    }
+}
+
+// 删除转账，两端账本都需要编辑权限且未结账
+pub async fn delete(
+    user: CurrentUser,
+    Path((account_book_id, transfer_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let transfer = match Transfer::find_by_id(&app_state.db_pool, transfer_id).await {
+        Ok(Some(transfer))
+            if transfer.from_account_book_id == Some(account_book_id)
+                || transfer.to_account_book_id == Some(account_book_id) =>
+        {
+            transfer
+        }
+        _ => return redirect_with_error(account_book_id, "转账记录不存在"),
+    };
+
+    for book_id in [transfer.from_account_book_id, transfer.to_account_book_id].into_iter().flatten() {
+        match AccountBook::find_with_min_role(&app_state.db_pool, book_id, user.id, BookRole::Editor).await {
+            Ok(Some(book)) if book.is_closed(transfer.transfer_date) => {
+                return redirect_with_error(account_book_id, &closed_message(&book));
+            }
+            Ok(Some(_)) => {}
+            _ => return redirect_with_error(account_book_id, "需要转出和转入账本的编辑权限才能删除转账"),
+        }
+    }
+
+    match Transfer::delete(&app_state.db_pool, transfer_id).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/transfers?success=转账已删除", account_book_id)),
+        Err(_) => redirect_with_error(account_book_id, "删除转账失败"),
+    }
+}
@@ -0,0 +1,411 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, Redirect},
+    Form,
+};
+use askama::Template;
+use serde::Deserialize;
+use validator::Validate;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::handlers::account_book::AccountBookDisplay;
+use crate::handlers::profile::SelectOption;
+use crate::middleware::{AppState, CurrentUser};
+use crate::models::{is_liability_type, wallet_type_icon, wallet_type_label, AccountBook, BookRole, CreateWallet, UserPreferences, Wallet, WalletSnapshot, WalletWithBalance, WALLET_TYPES};
+use crate::utils::{currency_symbol, WalletForm, WalletSnapshotForm};
+
+#[derive(Template)]
+#[template(path = "wallets/list.html")]
+struct WalletListTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    wallets: Vec<WalletDisplay>,
+    total_assets: Decimal,
+    // 负债合计，以欠款金额（正数）显示
+    total_liabilities: Decimal,
+    net_worth: Decimal,
+    currency_symbol: String,
+    type_options: Vec<SelectOption>,
+    success: String,
+    error: String,
+}
+
+#[derive(Template)]
+#[template(path = "wallets/edit.html")]
+struct EditWalletTemplate {
+    user: CurrentUser,
+    book: AccountBookDisplay,
+    wallet: WalletDisplay,
+    snapshots: Vec<SnapshotDisplay>,
+    currency_symbol: String,
+    type_options: Vec<SelectOption>,
+    today: String,
+    success: String,
+    error: String,
+}
+
+pub struct WalletDisplay {
+    pub id: i64,
+    pub name: String,
+    pub wallet_type: String,
+    pub type_label: String,
+    pub icon: String,
+    pub color: String,
+    pub opening_balance: Decimal,
+    pub balance: Decimal,
+    pub is_negative: bool,
+    pub is_liability: bool,
+}
+
+pub struct SnapshotDisplay {
+    pub id: i64,
+    pub date_label: String,
+    pub balance: Decimal,
+    pub note: String,
+}
+
+impl From<WalletWithBalance> for WalletDisplay {
+    fn from(row: WalletWithBalance) -> Self {
+        let mut display = WalletDisplay::from(row.wallet);
+        display.balance = row.balance;
+        display.is_negative = row.balance < Decimal::ZERO;
+        display
+    }
+}
+
+impl From<Wallet> for WalletDisplay {
+    fn from(wallet: Wallet) -> Self {
+        Self {
+            id: wallet.id,
+            type_label: wallet_type_label(&wallet.wallet_type).to_string(),
+            icon: wallet_type_icon(&wallet.wallet_type).to_string(),
+            is_liability: is_liability_type(&wallet.wallet_type),
+            name: wallet.name,
+            wallet_type: wallet.wallet_type,
+            color: wallet.color.unwrap_or_else(|| "#34c759".to_string()),
+            opening_balance: wallet.opening_balance,
+            balance: wallet.opening_balance,
+            is_negative: wallet.opening_balance < Decimal::ZERO,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WalletQuery {
+    success: Option<String>,
+    error: Option<String>,
+}
+
+fn type_options() -> Vec<SelectOption> {
+    WALLET_TYPES
+        .iter()
+        .map(|(value, label, _)| SelectOption::new(value, label))
+        .collect()
+}
+
+// 校验表单，返回期初余额
+fn validate_form(form: &WalletForm) -> Result<Decimal, String> {
+    if let Err(errors) = form.validate() {
+        let error_msg = errors
+            .field_errors()
+            .values()
+            .flat_map(|errs| errs.iter())
+            .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(error_msg);
+    }
+
+    if !WALLET_TYPES.iter().any(|(value, _, _)| *value == form.wallet_type) {
+        return Err("账户类型无效".to_string());
+    }
+
+    match form.opening_balance.as_deref().map(str::trim) {
+        None | Some("") => Ok(Decimal::ZERO),
+        Some(amount) => amount
+            .parse::<Decimal>()
+            .map(|amount| amount.round_dp(2))
+            .map_err(|_| "期初余额格式错误".to_string()),
+    }
+}
+
+// 账户列表和当前余额，任意成员可以查看，编辑者及以上可以管理
+pub async fn list(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Query(query): Query<WalletQuery>,
+) -> Result<Html<String>, Redirect> {
+    let book = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some((book, role))) => AccountBookDisplay::from(book).with_role(role),
+        Ok(None) => return Err(Redirect::to("/account-books?error=账本不存在或无权限访问")),
+        Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
+    };
+
+    let wallets: Vec<WalletDisplay> = match Wallet::find_balances(&app_state.db_pool, account_book_id).await {
+        Ok(wallets) => wallets.into_iter().map(WalletDisplay::from).collect(),
+        Err(_) => return Err(Redirect::to(&format!("/account-books/{}?error=加载账户列表失败", account_book_id))),
+    };
+
+    let total_assets: Decimal = wallets.iter().filter(|w| !w.is_liability).map(|w| w.balance).sum();
+    let total_liabilities: Decimal = -wallets.iter().filter(|w| w.is_liability).map(|w| w.balance).sum::<Decimal>();
+
+    let template = WalletListTemplate {
+        user,
+        total_assets,
+        total_liabilities,
+        net_worth: total_assets - total_liabilities,
+        currency_symbol: currency_symbol(&book.currency),
+        book,
+        wallets,
+        type_options: type_options(),
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 新建账户
+pub async fn create(
+    user: CurrentUser,
+    Path(account_book_id): Path<i64>,
+    State(app_state): State<AppState>,
+    Form(form): Form<WalletForm>,
+) -> Redirect {
+    // 编辑者及以上才能管理账户
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+        Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
+    }
+
+    let opening_balance = match validate_form(&form) {
+        Ok(amount) => amount,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/wallets?error={}",
+            account_book_id, urlencoding::encode(&message))),
+    };
+
+    let create_wallet = CreateWallet {
+        account_book_id,
+        name: form.name.trim().to_string(),
+        wallet_type: form.wallet_type,
+        color: form.color.filter(|s| !s.is_empty()),
+        opening_balance,
+    };
+
+    match Wallet::create(&app_state.db_pool, create_wallet).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/wallets?success=账户创建成功", account_book_id)),
+        Err(_) => Redirect::to(&format!("/account-books/{}/wallets?error=创建账户失败，账户名称可能已存在", account_book_id)),
+    }
+}
+
+// 显示编辑账户页面
+pub async fn show_edit(
+    user: CurrentUser,
+    Path((account_book_id, wallet_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Query(query): Query<WalletQuery>,
+) -> Result<Html<String>, Redirect> {
+    // 编辑者及以上才能管理账户
+    let book = match AccountBook::find_with_role(&app_state.db_pool, account_book_id, user.id).await {
+        Ok(Some((book, role))) if role.can_edit() => AccountBookDisplay::from(book).with_role(role),
+        Ok(_) => return Err(Redirect::to("/account-books?error=账本不存在或无权限编辑")),
+        Err(_) => return Err(Redirect::to("/account-books?error=获取账本信息失败")),
+    };
+
+    // 从账本的账户余额中查找，同时得到当前余额
+    let wallet = match Wallet::find_balances(&app_state.db_pool, account_book_id).await {
+        Ok(rows) => match rows.into_iter().find(|row| row.wallet.id == wallet_id) {
+            Some(row) => row,
+            None => return Err(Redirect::to(&format!("/account-books/{}/wallets?error=账户不存在", account_book_id))),
+        },
+        Err(_) => return Err(Redirect::to(&format!("/account-books/{}/wallets?error=获取账户信息失败", account_book_id))),
+    };
+
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    let snapshots = WalletSnapshot::find_by_wallet(&app_state.db_pool, wallet_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|snapshot| SnapshotDisplay {
+            id: snapshot.id,
+            date_label: preferences.format_date(snapshot.snapshot_date),
+            balance: snapshot.balance,
+            note: snapshot.note.unwrap_or_default(),
+        })
+        .collect();
+
+    let template = EditWalletTemplate {
+        user,
+        currency_symbol: currency_symbol(&book.currency),
+        book,
+        wallet: WalletDisplay::from(wallet),
+        snapshots,
+        type_options: type_options(),
+        today: preferences.today().format("%Y-%m-%d").to_string(),
+        success: query.success.unwrap_or_default(),
+        error: query.error.unwrap_or_default(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// 更新账户
+pub async fn update(
+    user: CurrentUser,
+    Path((account_book_id, wallet_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Form(form): Form<WalletForm>,
+) -> Redirect {
+    // 编辑者及以上才能管理账户
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+        Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
+    }
+
+    match Wallet::find_by_id(&app_state.db_pool, wallet_id).await {
+        Ok(Some(wallet)) if wallet.account_book_id == account_book_id => {}
+        _ => return Redirect::to(&format!("/account-books/{}/wallets?error=账户不存在", account_book_id)),
+    }
+
+    let opening_balance = match validate_form(&form) {
+        Ok(amount) => amount,
+        Err(message) => return Redirect::to(&format!("/account-books/{}/wallets/{}/edit?error={}",
+            account_book_id, wallet_id, urlencoding::encode(&message))),
+    };
+
+    match Wallet::update(
+        &app_state.db_pool,
+        wallet_id,
+        form.name.trim(),
+        &form.wallet_type,
+        form.color.as_deref().filter(|s| !s.is_empty()),
+        opening_balance,
+    ).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/wallets?success=账户更新成功", account_book_id)),
+        Err(_) => Redirect::to(&format!("/account-books/{}/wallets/{}/edit?error=更新账户失败，账户名称可能已存在",
+            account_book_id, wallet_id)),
+    }
+}
+
+// 删除账户，已关联的交易记录保留
+pub async fn delete(
+    user: CurrentUser,
+    Path((account_book_id, wallet_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    // 编辑者及以上才能管理账户
+    match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+        Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
+    }
+
+    match Wallet::find_by_id(&app_state.db_pool, wallet_id).await {
+        Ok(Some(wallet)) if wallet.account_book_id == account_book_id => {}
+        _ => return Redirect::to(&format!("/account-books/{}/wallets?error=账户不存在", account_book_id)),
+    }
+
+    match Wallet::delete(&app_state.db_pool, wallet_id).await {
+        Ok(_) => Redirect::to(&format!("/account-books/{}/wallets?success=账户已删除", account_book_id)),
+        Err(_) => Redirect::to(&format!("/account-books/{}/wallets?error=删除账户失败", account_book_id)),
+    }
+}
+
+// 记录账户某日的实际余额，同一天重复记录时覆盖
+pub async fn create_snapshot(
+    user: CurrentUser,
+    Path((account_book_id, wallet_id)): Path<(i64, i64)>,
+    State(app_state): State<AppState>,
+    Form(form): Form<WalletSnapshotForm>,
+) -> Redirect {
+    let edit_url = format!("/account-books/{}/wallets/{}/edit", account_book_id, wallet_id);
+
+    // 编辑者及以上才能管理账户
+    let book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+        Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
+    };
+
+    match Wallet::find_by_id(&app_state.db_pool, wallet_id).await {
+        Ok(Some(wallet)) if wallet.account_book_id == account_book_id => {}
+        _ => return Redirect::to(&format!("/account-books/{}/wallets?error=账户不存在", account_book_id)),
+    }
+
+    if let Err(errors) = form.validate() {
+        let error_msg = errors
+            .field_errors()
+            .values()
+            .flat_map(|errs| errs.iter())
+            .map(|e| e.message.as_ref().unwrap_or(&"验证失败".into()).to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Redirect::to(&format!("{}?error={}", edit_url, urlencoding::encode(&error_msg)));
+    }
+
+    let snapshot_date = match NaiveDate::parse_from_str(&form.snapshot_date, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => return Redirect::to(&format!("{}?error=日期格式错误", edit_url)),
+    };
+
+    let preferences = UserPreferences::load(&app_state.db_pool, user.id).await;
+    if snapshot_date > preferences.today() {
+        return Redirect::to(&format!("{}?error=不能记录未来日期的余额", edit_url));
+    }
+
+    // 快照会改变之后的余额，已结账期间内不能记录
+    if book.is_closed(snapshot_date) {
+        return Redirect::to(&format!("{}?error={}", edit_url,
+            urlencoding::encode("该日期所在期间已结账，不能记录余额快照")));
+    }
+
+    let balance = match form.balance.trim().parse::<Decimal>() {
+        Ok(balance) => balance.round_dp(2),
+        Err(_) => return Redirect::to(&format!("{}?error=余额格式错误", edit_url)),
+    };
+
+    let note = form.note.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+    match WalletSnapshot::upsert(&app_state.db_pool, wallet_id, snapshot_date, balance, note, user.id).await {
+        Ok(_) => Redirect::to(&format!("{}?success=余额快照已记录", edit_url)),
+        Err(_) => Redirect::to(&format!("{}?error=记录余额快照失败", edit_url)),
+    }
+}
+
+// 删除余额快照
+pub async fn delete_snapshot(
+    user: CurrentUser,
+    Path((account_book_id, wallet_id, snapshot_id)): Path<(i64, i64, i64)>,
+    State(app_state): State<AppState>,
+) -> Redirect {
+    let edit_url = format!("/account-books/{}/wallets/{}/edit", account_book_id, wallet_id);
+
+    // 编辑者及以上才能管理账户
+    let book = match AccountBook::find_with_min_role(&app_state.db_pool, account_book_id, user.id, BookRole::Editor).await {
+        Ok(Some(book)) => book,
+        Ok(None) => return Redirect::to("/account-books?error=账本不存在或无权限编辑"),
+        Err(_) => return Redirect::to("/account-books?error=获取账本信息失败"),
+    };
+
+    match Wallet::find_by_id(&app_state.db_pool, wallet_id).await {
+        Ok(Some(wallet)) if wallet.account_book_id == account_book_id => {}
+        _ => return Redirect::to(&format!("/account-books/{}/wallets?error=账户不存在", account_book_id)),
+    }
+
+    let snapshot = match WalletSnapshot::find_by_id(&app_state.db_pool, snapshot_id).await {
+        Ok(Some(snapshot)) if snapshot.wallet_id == wallet_id => snapshot,
+        _ => return Redirect::to(&format!("{}?error=余额快照不存在", edit_url)),
+    };
+
+    if book.is_closed(snapshot.snapshot_date) {
+        return Redirect::to(&format!("{}?error={}", edit_url,
+            urlencoding::encode("该快照所在期间已结账，不能删除")));
+    }
+
+    match WalletSnapshot::delete(&app_state.db_pool, snapshot_id).await {
+        Ok(_) => Redirect::to(&format!("{}?success=余额快照已删除", edit_url)),
+        Err(_) => Redirect::to(&format!("{}?error=删除余额快照失败", edit_url)),
+    }
+}
@@ -6,6 +6,8 @@ pub mod services;
 pub mod middleware;
 pub mod utils;
 
+use std::sync::Arc;
+
 use axum::{
     routing::{get, post},
     Router,
@@ -19,9 +21,9 @@ use tower_http::{
 
 use config::AppConfig;
 use database::create_pool;
-use services::{AuthService, EmailService};
+use services::{AuthService, EmailService, OidcService, RecurringService};
 use middleware::AppState;
-use handlers::{auth, dashboard, account_book, account_book_reports, category, transaction, api};
+use handlers::{auth, dashboard, account_book, account_book_reports, account_book_members, category, wallet, transaction, transfer, recurring, tag, api, profile};
 
 pub async fn create_app() -> anyhow::Result<Router> {
     // 加载配置
@@ -36,17 +38,33 @@ pub async fn create_app() -> anyhow::Result<Router> {
     // 创建认证服务
     let auth_service = AuthService::new(config.jwt_secret.clone(), email_service);
     
+    // 创建OIDC登录服务
+    let oidc_service = OidcService::new(
+        config.oidc_providers.clone(),
+        config.app_url.clone(),
+        config.jwt_secret.clone(),
+    );
+    
     // 创建应用状态
     let app_state = AppState {
         db_pool,
         auth_service,
+        oidc_service,
+        trusted_proxies: Arc::new(config.trusted_proxies.clone()),
     };
 
+    // 后台定期彻底删除注销冷静期已过的账户
+    spawn_account_purge(app_state.clone());
+
+    // 后台定期生成到期的重复记账交易
+    spawn_recurring_transactions(app_state.clone());
+
     // 创建路由
     let app = Router::new()
         // 首页和仪表板
         .route("/", get(dashboard::index))
         .route("/dashboard", get(dashboard::dashboard))
+        .route("/dashboard/overview", get(dashboard::overview))
         
         // 认证路由
         .route("/auth/login", get(auth::show_login).post(auth::login))
@@ -54,15 +72,35 @@ pub async fn create_app() -> anyhow::Result<Router> {
         .route("/auth/logout", get(auth::logout).post(auth::logout))
         .route("/auth/verify/:token", get(auth::verify_email))
         .route("/auth/resend-verification", post(auth::resend_verification))
+        .route("/auth/two-factor", get(auth::show_two_factor).post(auth::verify_two_factor))
+        .route("/auth/oidc/:provider", get(auth::oidc_start))
+        .route("/auth/oidc/:provider/callback", get(auth::oidc_callback))
+        .route("/auth/forgot-password", get(auth::show_forgot_password).post(auth::forgot_password))
+        .route("/auth/reset-password/:token", get(auth::show_reset_password).post(auth::reset_password))
         
         // 账本路由
         .route("/account-books", get(account_book::list).post(account_book::create))
         .route("/account-books/new", get(account_book::show_new))
+        .route("/account-books/archived", get(account_book::archived))
         .route("/account-books/:id", get(account_book::detail))
         .route("/account-books/:id/edit", get(account_book::show_edit))
         .route("/account-books/:id/update", post(account_book::update))
-        .route("/account-books/:id/delete", post(account_book::delete))
+        .route("/account-books/:id/duplicate", get(account_book::show_duplicate).post(account_book::duplicate))
+        .route("/account-books/:id/archive", post(account_book::archive))
+        .route("/account-books/:id/restore", post(account_book::restore))
+        .route("/account-books/:id/purge", post(account_book::purge))
         .route("/account-books/:id/reports", get(account_book_reports::reports))
+        .route("/account-books/:id/reports/net-worth", get(account_book_reports::net_worth))
+        .route("/account-books/:id/periods", get(account_book::periods))
+        .route("/account-books/:id/periods/close", post(account_book::close_period))
+        .route("/account-books/:id/periods/reopen", post(account_book::reopen_period))
+        .route("/account-books/:id/members", get(account_book_members::list))
+        .route("/account-books/:account_book_id/members/:user_id/role", post(account_book_members::update_role))
+        .route("/account-books/:account_book_id/members/:user_id/remove", post(account_book_members::remove))
+        .route("/account-books/:id/invitations", post(account_book_members::invite))
+        .route("/account-books/:account_book_id/invitations/:invitation_id/revoke", post(account_book_members::revoke_invitation))
+        .route("/invitations/:token", get(account_book_members::show_invitation))
+        .route("/invitations/:token/accept", post(account_book_members::accept_invitation))
         
         // 分类路由
         .route("/account-books/:id/categories", get(category::list).post(category::create))
@@ -71,6 +109,18 @@ pub async fn create_app() -> anyhow::Result<Router> {
         .route("/account-books/:account_book_id/categories/:category_id/update", post(category::update))
         .route("/account-books/:account_book_id/categories/:category_id/delete", post(category::delete))
         .route("/account-books/:id/categories/sort", post(category::update_sort_orders))
+        .route("/account-books/:id/categories/save-template", post(category::save_template))
+        .route("/account-books/:account_book_id/category-templates/:template_id/delete", post(category::delete_template))
+        
+        // 资金账户路由
+        .route("/account-books/:id/wallets", get(wallet::list).post(wallet::create))
+        .route("/account-books/:account_book_id/wallets/:wallet_id/edit", get(wallet::show_edit))
+        .route("/account-books/:account_book_id/wallets/:wallet_id/update", post(wallet::update))
+        .route("/account-books/:account_book_id/wallets/:wallet_id/delete", post(wallet::delete))
+        .route("/account-books/:account_book_id/wallets/:wallet_id/snapshots", post(wallet::create_snapshot))
+        .route("/account-books/:account_book_id/wallets/:wallet_id/snapshots/:snapshot_id/delete", post(wallet::delete_snapshot))
+        .route("/account-books/:id/transfers", get(transfer::list).post(transfer::create))
+        .route("/account-books/:account_book_id/transfers/:transfer_id/delete", post(transfer::delete))
         
         // 交易路由
         .route("/account-books/:id/transactions", get(transaction::list))
@@ -78,10 +128,54 @@ pub async fn create_app() -> anyhow::Result<Router> {
         .route("/account-books/:account_book_id/transactions/:transaction_id/edit", get(transaction::show_edit))
         .route("/account-books/:account_book_id/transactions/:transaction_id/update", post(transaction::update))
         .route("/account-books/:account_book_id/transactions/:transaction_id/delete", post(transaction::delete))
+
+        // 重复记账路由
+        .route("/account-books/:id/recurring", get(recurring::list).post(recurring::create))
+        .route("/account-books/:account_book_id/recurring/:rule_id", get(recurring::detail))
+        .route("/account-books/:account_book_id/recurring/:rule_id/update", post(recurring::update))
+        .route("/account-books/:account_book_id/recurring/:rule_id/toggle", post(recurring::toggle))
+        .route("/account-books/:account_book_id/recurring/:rule_id/delete", post(recurring::delete))
+        .route("/account-books/:account_book_id/recurring/:rule_id/occurrences/:sequence/skip", post(recurring::skip_occurrence))
+        .route("/account-books/:account_book_id/recurring/:rule_id/occurrences/:sequence/restore", post(recurring::restore_occurrence))
+        .route("/account-books/:account_book_id/recurring/:rule_id/occurrences/:sequence/edit", post(recurring::edit_occurrence))
+
+        // 标签路由
+        .route("/account-books/:id/tags", get(tag::list))
+        .route("/account-books/:account_book_id/tags/:tag_id/rename", post(tag::rename))
+        .route("/account-books/:account_book_id/tags/:tag_id/merge", post(tag::merge))
+        .route("/account-books/:account_book_id/tags/:tag_id/delete", post(tag::delete))
+        
+        // 个人设置路由
+        .route("/profile/account", get(profile::account))
+        .route("/profile/email", post(profile::change_email))
+        .route("/profile/email/cancel", post(profile::cancel_email_change))
+        .route("/profile/data/export", get(profile::export_data))
+        .route("/profile/delete", post(profile::delete_account))
+        .route("/profile/delete/cancel", post(profile::cancel_account_deletion))
+        .route("/profile/preferences", get(profile::preferences).post(profile::update_preferences))
+        .route("/profile/sessions", get(profile::sessions))
+        .route("/profile/sessions/revoke-all", post(profile::revoke_all_sessions))
+        .route("/profile/sessions/:session_id/revoke", post(profile::revoke_session))
+        .route("/profile/security", get(profile::security))
+        .route("/profile/security/2fa/setup", post(profile::setup_two_factor))
+        .route("/profile/security/2fa/enable", post(profile::enable_two_factor))
+        .route("/profile/security/2fa/recovery-codes", post(profile::regenerate_recovery_codes))
+        .route("/profile/security/2fa/disable", post(profile::disable_two_factor))
+        .route("/profile/security/identities/:identity_id/unlink", post(profile::unlink_identity))
+        .route("/profile/tokens", get(profile::api_tokens).post(profile::create_api_token))
+        .route("/profile/tokens/:token_id/revoke", post(profile::revoke_api_token))
+        .route("/profile/exchange-rates", get(profile::exchange_rates))
+        .route("/profile/exchange-rates/import", post(profile::import_exchange_rates))
+        .route("/profile/exchange-rates/clear", post(profile::clear_exchange_rates))
         
         // API路由
         .route("/api/preferences/account-book", post(api::update_account_book_preference))
         .route("/api/preferences/account-book/:id", post(api::update_preference_by_path))
+        .route("/api/account-books", get(api::list_account_books))
+        .route("/api/account-books/:id/categories", get(api::list_categories))
+        .route("/api/account-books/:id/wallets", get(api::list_wallets))
+        .route("/api/account-books/:id/tags", get(api::list_tags))
+        .route("/api/account-books/:id/transactions", get(api::list_transactions).post(api::create_transaction))
         
         // 静态文件服务
         .nest_service("/static", ServeDir::new("static"))
@@ -101,4 +195,30 @@ pub async fn create_app() -> anyhow::Result<Router> {
 
 pub fn get_config() -> anyhow::Result<AppConfig> {
     Ok(AppConfig::from_env()?)
-}
\ No newline at end of file
+}
+
+fn spawn_account_purge(app_state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = app_state.auth_service.purge_scheduled_deletions(&app_state.db_pool).await {
+                tracing::warn!("Failed to purge deleted accounts: {}", e);
+            }
+        }
+    });
+}
+
+fn spawn_recurring_transactions(app_state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(15 * 60));
+        loop {
+            interval.tick().await;
+            match RecurringService::materialize_due(&app_state.db_pool).await {
+                Ok(0) => {}
+                Ok(created) => tracing::info!("Created {} recurring transactions", created),
+                Err(e) => tracing::warn!("Failed to create recurring transactions: {}", e),
+            }
+        }
+    });
+}
@@ -1,3 +1,5 @@
+use std::net::SocketAddr;
+
 use accounting_system::{create_app, get_config};
 use tokio::net::TcpListener;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -28,7 +30,7 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Server listening on http://{}", bind_addr);
     
     // 启动服务器
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -1,29 +1,60 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
 use axum::{
     async_trait,
     extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    http::{request::Parts, Method, StatusCode},
     response::{IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::cookie::CookieJar;
 use serde::{Deserialize, Serialize};
 
-use crate::services::AuthService;
-use crate::models::User;
+use crate::services::{AuthService, OidcService};
+use crate::models::{ApiToken, Session, User};
 use crate::database::DbPool;
+use crate::utils::API_TOKEN_PREFIX;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CurrentUser {
     pub id: i64,
     pub email: String,
     pub is_verified: bool,
+    pub session_id: String,
+    // 通过个人访问令牌认证时的授权信息，浏览器会话为None
+    pub api_token: Option<ApiTokenGrant>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiTokenGrant {
+    pub token_id: i64,
+    pub scope: String,
+    pub account_book_id: Option<i64>,
 }
 
-impl From<User> for CurrentUser {
-    fn from(user: User) -> Self {
+impl CurrentUser {
+    pub fn new(user: User, session_id: String) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            is_verified: user.is_verified,
+            session_id,
+            api_token: None,
+        }
+    }
+
+    // 令牌请求没有服务端会话，session_id留空
+    pub fn from_api_token(user: User, api_token: &ApiToken) -> Self {
         Self {
             id: user.id,
             email: user.email,
             is_verified: user.is_verified,
+            session_id: String::new(),
+            api_token: Some(ApiTokenGrant {
+                token_id: api_token.id,
+                scope: api_token.scope.clone(),
+                account_book_id: api_token.account_book_id,
+            }),
         }
     }
 }
@@ -32,6 +63,8 @@ impl From<User> for CurrentUser {
 pub struct AppState {
     pub db_pool: DbPool,
     pub auth_service: AuthService,
+    pub oidc_service: OidcService,
+    pub trusted_proxies: Arc<Vec<IpAddr>>,
 }
 
 #[async_trait]
@@ -62,12 +95,39 @@ impl FromRequestParts<AppState> for CurrentUser
             })
             .ok_or_else(|| Redirect::to("/auth/login").into_response())?;
 
+        // 个人访问令牌：失败时返回状态码而不是跳转登录页
+        if token.starts_with(API_TOKEN_PREFIX) {
+            let (user, api_token) = app_state
+                .auth_service
+                .authenticate_api_token(&app_state.db_pool, token)
+                .await
+                .map_err(|_| (StatusCode::UNAUTHORIZED, "访问令牌无效或已过期").into_response())?;
+
+            if !api_token_permits(&api_token, &parts.method, parts.uri.path()) {
+                return Err((StatusCode::FORBIDDEN, "访问令牌无权执行此操作").into_response());
+            }
+
+            return Ok(CurrentUser::from_api_token(user, &api_token));
+        }
+
         // 验证token
         let claims = app_state
             .auth_service
             .verify_token(token)
             .map_err(|_| Redirect::to("/auth/login").into_response())?;
 
+        // 校验服务端会话，已注销或过期的会话不可再使用
+        let session = Session::find_active(&app_state.db_pool, &claims.sid)
+            .await
+            .ok()
+            .flatten()
+            .filter(|session| session.user_id == claims.sub)
+            .ok_or_else(|| Redirect::to("/auth/login").into_response())?;
+
+        if let Err(e) = Session::touch(&app_state.db_pool, &session.id).await {
+            tracing::warn!("Failed to update session last_seen_at: {}", e);
+        }
+
         // 获取用户信息
         let user = app_state
             .auth_service
@@ -75,10 +135,41 @@ impl FromRequestParts<AppState> for CurrentUser
             .await
             .map_err(|_| Redirect::to("/auth/login").into_response())?;
 
-        Ok(CurrentUser::from(user))
+        if app_state.auth_service.is_token_revoked(&claims, &user) {
+            return Err(Redirect::to("/auth/login?error=登录已失效，请重新登录").into_response());
+        }
+
+        Ok(CurrentUser::new(user, session.id))
+    }
+}
+
+// 按请求方法和路径检查令牌的权限范围。令牌只能调用 /api/ 下的接口：网页表单路由（转账、
+// 成员管理、删除账本等）只按用户在账本中的角色鉴权，无法保证不越出令牌限定的账本
+fn api_token_permits(api_token: &ApiToken, method: &Method, path: &str) -> bool {
+    if !path.starts_with("/api/") {
+        return false;
+    }
+
+    let is_read = *method == Method::GET || *method == Method::HEAD;
+    if !is_read && api_token.is_read_only() {
+        return false;
+    }
+
+    match api_token.account_book_id {
+        Some(account_book_id) => account_book_id_in_path(path) == Some(account_book_id),
+        None => true,
     }
 }
 
+// 从 /api/account-books/:id/... 中取出账本ID
+fn account_book_id_in_path(path: &str) -> Option<i64> {
+    path.strip_prefix("/api/account-books/")?
+        .split('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
 // 可选的认证中间件，不会重定向
 pub struct OptionalCurrentUser(pub Option<CurrentUser>);
 
@@ -93,4 +184,72 @@ impl FromRequestParts<AppState> for OptionalCurrentUser
             Err(_) => Ok(OptionalCurrentUser(None)),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::models::{API_TOKEN_SCOPE_READ, API_TOKEN_SCOPE_WRITE};
+
+    fn token(scope: &str, account_book_id: Option<i64>) -> ApiToken {
+        ApiToken {
+            id: 1,
+            user_id: 1,
+            name: "script".to_string(),
+            token_prefix: "pat_test".to_string(),
+            token_hash: String::new(),
+            scope: scope.to_string(),
+            account_book_id,
+            last_used_at: None,
+            expires_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn account_book_id_only_from_api_paths() {
+        assert_eq!(account_book_id_in_path("/api/account-books/12/transactions"), Some(12));
+        assert_eq!(account_book_id_in_path("/api/account-books/12"), Some(12));
+        assert_eq!(account_book_id_in_path("/api/account-books"), None);
+        assert_eq!(account_book_id_in_path("/api/account-books/abc/transactions"), None);
+        assert_eq!(account_book_id_in_path("/account-books/12/transfers"), None);
+    }
+
+    #[test]
+    fn tokens_cannot_reach_html_routes() {
+        let write = token(API_TOKEN_SCOPE_WRITE, None);
+        for path in [
+            "/account-books/1/transfers",
+            "/account-books/1/delete",
+            "/account-books/1/purge",
+            "/account-books/1/members/2/role",
+            "/invitations/abc/accept",
+            "/profile/tokens",
+            "/auth/logout",
+            "/api",
+        ] {
+            assert!(!api_token_permits(&write, &Method::POST, path), "{}", path);
+            assert!(!api_token_permits(&write, &Method::GET, path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn read_only_tokens_cannot_write() {
+        let read = token(API_TOKEN_SCOPE_READ, None);
+        assert!(api_token_permits(&read, &Method::GET, "/api/account-books"));
+        assert!(api_token_permits(&read, &Method::HEAD, "/api/account-books/3/transactions"));
+        assert!(!api_token_permits(&read, &Method::POST, "/api/account-books/3/transactions"));
+        assert!(!api_token_permits(&read, &Method::DELETE, "/api/account-books/3/transactions"));
+    }
+
+    #[test]
+    fn scoped_tokens_stay_in_their_book() {
+        let scoped = token(API_TOKEN_SCOPE_WRITE, Some(3));
+        assert!(api_token_permits(&scoped, &Method::POST, "/api/account-books/3/transactions"));
+        assert!(api_token_permits(&scoped, &Method::GET, "/api/account-books/3/wallets"));
+        assert!(!api_token_permits(&scoped, &Method::POST, "/api/account-books/4/transactions"));
+        assert!(!api_token_permits(&scoped, &Method::GET, "/api/account-books/30/wallets"));
+        assert!(!api_token_permits(&scoped, &Method::GET, "/api/account-books"));
+    }
+}
@@ -0,0 +1,114 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+
+use crate::middleware::AppState;
+
+// 请求来源信息（IP与User-Agent），用于会话记录
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, app_state: &AppState) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        let user_agent = parts
+            .headers
+            .get("User-Agent")
+            .and_then(|header| header.to_str().ok())
+            .map(|ua| ua.chars().take(500).collect());
+
+        Ok(ClientInfo {
+            ip_address: client_ip(peer, &parts.headers, &app_state.trusted_proxies).map(|ip| ip.to_string()),
+            user_agent,
+        })
+    }
+}
+
+// 客户端的真实IP：只有对端是可信代理时才读取转发头，否则任何人都能伪造IP绕过按IP的限流。
+// X-Forwarded-For 从右往左跳过可信代理，取第一个不可信的地址
+fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(ip) = header("X-Real-IP").and_then(|value| value.trim().parse::<IpAddr>().ok()) {
+        return Some(ip);
+    }
+
+    let forwarded = header("X-Forwarded-For")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|ip| ip.trim().parse::<IpAddr>())
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .and_then(Result::ok)
+        .and_then(|chain| chain.into_iter().rev().find(|ip| !trusted_proxies.contains(ip)));
+
+    Some(forwarded.unwrap_or(peer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn ignores_forwarded_headers_from_untrusted_peer() {
+        let headers = headers(&[("X-Real-IP", "1.1.1.1"), ("X-Forwarded-For", "2.2.2.2")]);
+        assert_eq!(client_ip(Some(ip("9.9.9.9")), &headers, &[]), Some(ip("9.9.9.9")));
+        assert_eq!(client_ip(Some(ip("9.9.9.9")), &headers, &[ip("127.0.0.1")]), Some(ip("9.9.9.9")));
+    }
+
+    #[test]
+    fn uses_real_ip_from_trusted_proxy() {
+        let headers = headers(&[("X-Real-IP", "1.1.1.1")]);
+        assert_eq!(client_ip(Some(ip("127.0.0.1")), &headers, &[ip("127.0.0.1")]), Some(ip("1.1.1.1")));
+    }
+
+    #[test]
+    fn takes_rightmost_untrusted_forwarded_address() {
+        let trusted = [ip("127.0.0.1"), ip("10.0.0.2")];
+        // 客户端自己伪造的最左侧地址不会被采用
+        let headers = headers(&[("X-Forwarded-For", "6.6.6.6, 3.3.3.3, 10.0.0.2")]);
+        assert_eq!(client_ip(Some(ip("127.0.0.1")), &headers, &trusted), Some(ip("3.3.3.3")));
+    }
+
+    #[test]
+    fn falls_back_to_peer_when_headers_missing_or_invalid() {
+        let trusted = [ip("127.0.0.1")];
+        assert_eq!(client_ip(Some(ip("127.0.0.1")), &HeaderMap::new(), &trusted), Some(ip("127.0.0.1")));
+        let headers = headers(&[("X-Forwarded-For", "not-an-ip")]);
+        assert_eq!(client_ip(Some(ip("127.0.0.1")), &headers, &trusted), Some(ip("127.0.0.1")));
+        assert_eq!(client_ip(None, &headers, &trusted), None);
+    }
+}
@@ -1,15 +1,20 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::models::{BookRole, CategorySeed, Transfer};
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct AccountBook {
     pub id: i64,
+    // 创建者，访问权限以 account_book_members 为准
     pub user_id: i64,
     pub name: String,
     pub description: Option<String>,
     pub currency: String,
     pub cycle_start_day: i32,
+    // 已结账截止日期（含），该日期及之前的交易不能新增、修改或删除
+    pub closed_through: Option<NaiveDate>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -22,13 +27,45 @@ pub struct CreateAccountBook {
     pub description: Option<String>,
     pub currency: String,
     pub cycle_start_day: i32,
+    // 按所选分类模板预置的分类，可为空
+    pub categories: Vec<CategorySeed>,
+}
+
+// 复制账本时交易记录的复制范围
+#[derive(Debug, Clone, Copy)]
+pub enum DuplicateTransactions {
+    None,
+    All,
+    Range(NaiveDate, NaiveDate),
+}
+
+// 账本及当前用户在其中的角色
+#[derive(Debug, FromRow)]
+struct MemberBookRow {
+    #[sqlx(flatten)]
+    book: AccountBook,
+    member_role: String,
+}
+
+impl MemberBookRow {
+    fn into_pair(self) -> (AccountBook, BookRole) {
+        let role = BookRole::parse(&self.member_role).unwrap_or(BookRole::Viewer);
+        (self.book, role)
+    }
 }
 
 impl AccountBook {
+    pub fn is_closed(&self, date: NaiveDate) -> bool {
+        self.closed_through.is_some_and(|closed_through| date <= closed_through)
+    }
+
+    // 创建账本，创建者同时成为所有者，模板分类与账本在同一事务中写入
     pub async fn create(
         pool: &crate::database::DbPool,
         create_book: CreateAccountBook,
     ) -> anyhow::Result<AccountBook> {
+        let mut tx = pool.begin().await?;
+
         let result = sqlx::query(
             r#"
             INSERT INTO account_books (user_id, name, description, currency, cycle_start_day)
@@ -40,46 +77,184 @@ impl AccountBook {
         .bind(&create_book.description)
         .bind(&create_book.currency)
         .bind(create_book.cycle_start_day)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
 
         let book_id = result.last_insert_id() as i64;
+
+        sqlx::query("INSERT INTO account_book_members (account_book_id, user_id, role) VALUES (?, ?, ?)")
+            .bind(book_id)
+            .bind(create_book.user_id)
+            .bind(BookRole::Owner.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        let mut income_order = 0;
+        let mut expense_order = 0;
+        for category in &create_book.categories {
+            let sort_order = if category.category_type == "income" {
+                income_order += 1;
+                income_order
+            } else {
+                expense_order += 1;
+                expense_order
+            };
+
+            sqlx::query(
+                r#"
+                INSERT INTO categories (account_book_id, name, `type`, icon, color, sort_order)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(book_id)
+            .bind(&category.name)
+            .bind(&category.category_type)
+            .bind(&category.icon)
+            .bind(&category.color)
+            .bind(sort_order)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
         let book = Self::find_by_id(pool, book_id, create_book.user_id).await?
             .ok_or_else(|| anyhow::anyhow!("Failed to retrieve created account book"))?;
 
         Ok(book)
     }
 
+    // 用户作为成员可以访问的所有账本
     pub async fn find_by_user(
         pool: &crate::database::DbPool,
         user_id: i64,
     ) -> anyhow::Result<Vec<AccountBook>> {
-        let books = sqlx::query_as::<_, AccountBook>(
-            "SELECT * FROM account_books WHERE user_id = ? AND is_active = TRUE ORDER BY created_at DESC"
+        let books = Self::find_by_user_with_role(pool, user_id)
+            .await?
+            .into_iter()
+            .map(|(book, _)| book)
+            .collect();
+
+        Ok(books)
+    }
+
+    pub async fn find_by_user_with_role(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+    ) -> anyhow::Result<Vec<(AccountBook, BookRole)>> {
+        let rows = sqlx::query_as::<_, MemberBookRow>(
+            r#"
+            SELECT ab.*, m.role AS member_role
+            FROM account_books ab
+            JOIN account_book_members m ON m.account_book_id = ab.id
+            WHERE m.user_id = ? AND ab.is_active = TRUE
+            ORDER BY ab.created_at DESC
+            "#
         )
         .bind(user_id)
         .fetch_all(pool)
         .await?;
 
-        Ok(books)
+        Ok(rows.into_iter().map(MemberBookRow::into_pair).collect())
+    }
+
+    // 用户参与的已归档账本
+    pub async fn find_archived_by_user_with_role(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+    ) -> anyhow::Result<Vec<(AccountBook, BookRole)>> {
+        let rows = sqlx::query_as::<_, MemberBookRow>(
+            r#"
+            SELECT ab.*, m.role AS member_role
+            FROM account_books ab
+            JOIN account_book_members m ON m.account_book_id = ab.id
+            WHERE m.user_id = ? AND ab.is_active = FALSE
+            ORDER BY ab.updated_at DESC
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(MemberBookRow::into_pair).collect())
     }
 
+    // 不校验成员身份，供后台任务在事务内读取账本
+    pub async fn find_unchecked(conn: &mut sqlx::MySqlConnection, id: i64) -> anyhow::Result<Option<AccountBook>> {
+        let book = sqlx::query_as::<_, AccountBook>("SELECT * FROM account_books WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(book)
+    }
+
+    // 任意角色的成员均可读取
     pub async fn find_by_id(
         pool: &crate::database::DbPool,
         id: i64,
         user_id: i64,
     ) -> anyhow::Result<Option<AccountBook>> {
-        let book = sqlx::query_as::<_, AccountBook>(
-            "SELECT * FROM account_books WHERE id = ? AND user_id = ? AND is_active = TRUE"
+        Ok(Self::find_with_role(pool, id, user_id).await?.map(|(book, _)| book))
+    }
+
+    pub async fn find_with_role(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<Option<(AccountBook, BookRole)>> {
+        let row = sqlx::query_as::<_, MemberBookRow>(
+            r#"
+            SELECT ab.*, m.role AS member_role
+            FROM account_books ab
+            JOIN account_book_members m ON m.account_book_id = ab.id
+            WHERE ab.id = ? AND m.user_id = ? AND ab.is_active = TRUE
+            "#
         )
         .bind(id)
         .bind(user_id)
         .fetch_optional(pool)
         .await?;
 
-        Ok(book)
+        Ok(row.map(MemberBookRow::into_pair))
+    }
+
+    // 包括已归档账本，供只读的统计报表使用
+    pub async fn find_with_role_including_archived(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<Option<(AccountBook, BookRole)>> {
+        let row = sqlx::query_as::<_, MemberBookRow>(
+            r#"
+            SELECT ab.*, m.role AS member_role
+            FROM account_books ab
+            JOIN account_book_members m ON m.account_book_id = ab.id
+            WHERE ab.id = ? AND m.user_id = ?
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(MemberBookRow::into_pair))
+    }
+
+    // 仅当用户角色不低于 min_role 时返回账本
+    pub async fn find_with_min_role(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+        min_role: BookRole,
+    ) -> anyhow::Result<Option<AccountBook>> {
+        Ok(Self::find_with_role(pool, id, user_id)
+            .await?
+            .filter(|(_, role)| *role >= min_role)
+            .map(|(book, _)| book))
     }
 
+    // 仅所有者可以修改账本信息
     pub async fn update(
         pool: &crate::database::DbPool,
         id: i64,
@@ -91,9 +266,12 @@ impl AccountBook {
     ) -> anyhow::Result<()> {
         sqlx::query(
             r#"
-            UPDATE account_books 
+            UPDATE account_books
             SET name = ?, description = ?, currency = ?, cycle_start_day = ?, updated_at = NOW()
-            WHERE id = ? AND user_id = ?
+            WHERE id = ? AND EXISTS (
+                SELECT 1 FROM account_book_members m
+                WHERE m.account_book_id = account_books.id AND m.user_id = ? AND m.role = 'owner'
+            )
             "#,
         )
         .bind(name)
@@ -108,17 +286,347 @@ impl AccountBook {
         Ok(())
     }
 
-    pub async fn delete(
+    // 归档账本：从账本列表中隐藏，数据保留，可以恢复
+    pub async fn archive(
         pool: &crate::database::DbPool,
         id: i64,
         user_id: i64,
     ) -> anyhow::Result<()> {
-        sqlx::query("UPDATE account_books SET is_active = FALSE WHERE id = ? AND user_id = ?")
-            .bind(id)
+        Self::set_active(pool, id, user_id, false).await
+    }
+
+    pub async fn restore(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<()> {
+        Self::set_active(pool, id, user_id, true).await
+    }
+
+    async fn set_active(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+        is_active: bool,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE account_books SET is_active = ?, updated_at = NOW()
+            WHERE id = ? AND EXISTS (
+                SELECT 1 FROM account_book_members m
+                WHERE m.account_book_id = account_books.id AND m.user_id = ? AND m.role = 'owner'
+            )
+            "#,
+        )
+        .bind(is_active)
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // 复制账本的设置和全部分类（含排序、图标、颜色），可选复制交易记录及账本内的转账，
+    // 复制者成为新账本的所有者；整个复制在同一事务中完成，返回新账本ID
+    pub async fn duplicate(
+        pool: &crate::database::DbPool,
+        source_id: i64,
+        user_id: i64,
+        name: &str,
+        transactions: DuplicateTransactions,
+    ) -> anyhow::Result<i64> {
+        let mut tx = pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO account_books (user_id, name, description, currency, cycle_start_day)
+            SELECT ?, ?, description, currency, cycle_start_day
+            FROM account_books WHERE id = ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(anyhow::anyhow!("账本不存在"));
+        }
+
+        let book_id = result.last_insert_id() as i64;
+
+        sqlx::query("INSERT INTO account_book_members (account_book_id, user_id, role) VALUES (?, ?, ?)")
+            .bind(book_id)
             .bind(user_id)
-            .execute(pool)
+            .bind(BookRole::Owner.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        // 已停用的分类也一并复制，保证被引用的交易记录都能找到对应分类
+        sqlx::query(
+            r#"
+            INSERT INTO categories (account_book_id, name, `type`, icon, color, sort_order, is_active)
+            SELECT ?, name, `type`, icon, color, sort_order, is_active
+            FROM categories WHERE account_book_id = ?
+            "#,
+        )
+        .bind(book_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+
+        // 资金账户连同期初余额一并复制
+        sqlx::query(
+            r#"
+            INSERT INTO wallets (account_book_id, name, `type`, color, opening_balance, sort_order, is_active)
+            SELECT ?, name, `type`, color, opening_balance, sort_order, is_active
+            FROM wallets WHERE account_book_id = ?
+            "#,
+        )
+        .bind(book_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+
+        // 标签与分类一样作为账本的设置复制
+        sqlx::query("INSERT INTO tags (account_book_id, name) SELECT ?, name FROM tags WHERE account_book_id = ?")
+            .bind(book_id)
+            .bind(source_id)
+            .execute(&mut *tx)
             .await?;
 
+        match transactions {
+            DuplicateTransactions::None => {}
+            DuplicateTransactions::All => {
+                Self::copy_transactions(&mut tx, source_id, book_id, None).await?;
+                Self::copy_transfers(&mut tx, source_id, book_id, None).await?;
+
+                // 复制全部交易时余额快照仍然成立，一并复制
+                sqlx::query(
+                    r#"
+                    INSERT INTO wallet_snapshots (wallet_id, snapshot_date, balance, note, created_by)
+                    SELECT nw.id, s.snapshot_date, s.balance, s.note, s.created_by
+                    FROM wallet_snapshots s
+                    JOIN wallets ow ON s.wallet_id = ow.id
+                    JOIN wallets nw ON nw.account_book_id = ? AND nw.name = ow.name
+                    WHERE ow.account_book_id = ?
+                    "#,
+                )
+                .bind(book_id)
+                .bind(source_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            DuplicateTransactions::Range(start, end) => {
+                Self::copy_transactions(&mut tx, source_id, book_id, Some((start, end))).await?;
+                Self::copy_transfers(&mut tx, source_id, book_id, Some((start, end))).await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(book_id)
+    }
+
+    // 分类、账户和标签名称在账本内唯一，按名称把原ID映射到新ID。
+    // 没有标签和拆分的交易批量复制；其余交易逐笔复制，以便把标签和拆分明细关联到新交易
+    async fn copy_transactions(
+        conn: &mut sqlx::MySqlConnection,
+        source_id: i64,
+        book_id: i64,
+        range: Option<(NaiveDate, NaiveDate)>,
+    ) -> anyhow::Result<()> {
+        let date_condition = if range.is_some() { " AND t.transaction_date BETWEEN ? AND ?" } else { "" };
+        let copy_transactions = format!(
+            r#"
+            INSERT INTO transactions
+                (account_book_id, category_id, wallet_id, amount, original_currency, original_amount, exchange_rate,
+                 `type`, description, transaction_date, created_by)
+            SELECT ?, nc.id, nw.id, t.amount, t.original_currency, t.original_amount, t.exchange_rate,
+                   t.`type`, t.description, t.transaction_date, t.created_by
+            FROM transactions t
+            JOIN categories oc ON t.category_id = oc.id
+            JOIN categories nc ON nc.account_book_id = ? AND nc.name = oc.name
+            LEFT JOIN wallets ow ON t.wallet_id = ow.id
+            LEFT JOIN wallets nw ON nw.account_book_id = ? AND nw.name = ow.name
+            WHERE t.account_book_id = ?{}
+            "#,
+            date_condition
+        );
+
+        let copy_plain = format!(
+            r#"{}
+                AND NOT EXISTS (SELECT 1 FROM transaction_tags tt WHERE tt.transaction_id = t.id)
+                AND NOT EXISTS (SELECT 1 FROM transaction_splits ts WHERE ts.transaction_id = t.id)
+            "#,
+            copy_transactions
+        );
+        let mut plain = sqlx::query(&copy_plain)
+        .bind(book_id)
+        .bind(book_id)
+        .bind(book_id)
+        .bind(source_id);
+        if let Some((start, end)) = range {
+            plain = plain.bind(start).bind(end);
+        }
+        plain.execute(&mut *conn).await?;
+
+        let find_detailed = format!(
+            r#"
+            SELECT t.id FROM transactions t
+            WHERE t.account_book_id = ?{}
+                AND (EXISTS (SELECT 1 FROM transaction_tags tt WHERE tt.transaction_id = t.id)
+                     OR EXISTS (SELECT 1 FROM transaction_splits ts WHERE ts.transaction_id = t.id))
+            ORDER BY t.id
+            "#,
+            date_condition
+        );
+        let mut detailed_ids = sqlx::query_scalar::<_, i64>(&find_detailed).bind(source_id);
+        if let Some((start, end)) = range {
+            detailed_ids = detailed_ids.bind(start).bind(end);
+        }
+        let detailed_ids = detailed_ids.fetch_all(&mut *conn).await?;
+
+        let copy_one = format!("{} AND t.id = ?", copy_transactions);
+        for transaction_id in detailed_ids {
+            let mut copy = sqlx::query(&copy_one)
+                .bind(book_id)
+                .bind(book_id)
+                .bind(book_id)
+                .bind(source_id);
+            if let Some((start, end)) = range {
+                copy = copy.bind(start).bind(end);
+            }
+            let result = copy.bind(transaction_id).execute(&mut *conn).await?;
+            if result.rows_affected() == 0 {
+                continue;
+            }
+            let new_transaction_id = result.last_insert_id() as i64;
+
+            sqlx::query(
+                r#"
+                INSERT INTO transaction_tags (transaction_id, tag_id)
+                SELECT ?, ng.id FROM transaction_tags tt
+                JOIN tags og ON tt.tag_id = og.id
+                JOIN tags ng ON ng.account_book_id = ? AND ng.name = og.name
+                WHERE tt.transaction_id = ?
+                "#,
+            )
+            .bind(new_transaction_id)
+            .bind(book_id)
+            .bind(transaction_id)
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO transaction_splits (transaction_id, category_id, amount, original_amount, note, sort_order)
+                SELECT ?, nc.id, ts.amount, ts.original_amount, ts.note, ts.sort_order FROM transaction_splits ts
+                JOIN categories oc ON ts.category_id = oc.id
+                JOIN categories nc ON nc.account_book_id = ? AND nc.name = oc.name
+                WHERE ts.transaction_id = ?
+                "#,
+            )
+            .bind(new_transaction_id)
+            .bind(book_id)
+            .bind(transaction_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    // 复制账本内账户之间的转账，账户按名称映射到新账本。与其他账本之间的转账会同时改变
+    // 对方账本的余额，不复制
+    async fn copy_transfers(
+        conn: &mut sqlx::MySqlConnection,
+        source_id: i64,
+        book_id: i64,
+        range: Option<(NaiveDate, NaiveDate)>,
+    ) -> anyhow::Result<()> {
+        let date_condition = if range.is_some() { " AND t.transfer_date BETWEEN ? AND ?" } else { "" };
+        let copy = format!(
+            r#"
+            INSERT INTO transfers
+                (from_account_book_id, from_wallet_id, to_account_book_id, to_wallet_id,
+                 amount, to_amount, description, transfer_date, created_by)
+            SELECT ?, nfw.id, ?, ntw.id, t.amount, t.to_amount, t.description, t.transfer_date, t.created_by
+            FROM transfers t
+            JOIN wallets ofw ON t.from_wallet_id = ofw.id
+            JOIN wallets nfw ON nfw.account_book_id = ? AND nfw.name = ofw.name
+            JOIN wallets otw ON t.to_wallet_id = otw.id
+            JOIN wallets ntw ON ntw.account_book_id = ? AND ntw.name = otw.name
+            WHERE t.from_account_book_id = ? AND t.to_account_book_id = ?{}
+            ORDER BY t.id
+            "#,
+            date_condition
+        );
+
+        let mut query = sqlx::query(&copy)
+            .bind(book_id)
+            .bind(book_id)
+            .bind(book_id)
+            .bind(book_id)
+            .bind(source_id)
+            .bind(source_id);
+        if let Some((start, end)) = range {
+            query = query.bind(start).bind(end);
+        }
+        query.execute(&mut *conn).await?;
+
         Ok(())
     }
-}
\ No newline at end of file
+
+    // 永久删除已归档的账本及其分类和交易记录，仅所有者可以操作
+    // 交易记录和重复记账规则引用分类，需先于账本删除；分类、成员等随账本级联删除；
+    // 与其他账本之间的转账保留在对方账本中
+    pub async fn purge(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<bool> {
+        let mut tx = pool.begin().await?;
+
+        let archived: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT ab.id FROM account_books ab
+            JOIN account_book_members m ON m.account_book_id = ab.id
+            WHERE ab.id = ? AND ab.is_active = FALSE AND m.user_id = ? AND m.role = 'owner'
+            FOR UPDATE
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if archived.is_none() {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM transactions WHERE account_book_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        Transfer::delete_for_purged_book(&mut tx, id).await?;
+
+        sqlx::query("DELETE FROM recurring_transactions WHERE account_book_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM account_books WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
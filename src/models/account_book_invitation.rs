@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+use crate::models::BookRole;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AccountBookInvitation {
+    pub id: i64,
+    pub account_book_id: i64,
+    pub email: String,
+    pub role: String,
+    pub token_hash: String,
+    pub invited_by: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub accepted_by: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 邀请页面展示用，附带账本名称和邀请人
+#[derive(Debug, FromRow)]
+pub struct AccountBookInvitationWithBook {
+    #[sqlx(flatten)]
+    pub invitation: AccountBookInvitation,
+    pub book_name: String,
+    pub inviter_name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CreateAccountBookInvitation {
+    pub account_book_id: i64,
+    pub email: String,
+    pub role: BookRole,
+    pub token_hash: String,
+    pub invited_by: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AccountBookInvitation {
+    pub fn role(&self) -> BookRole {
+        BookRole::parse(&self.role).unwrap_or(BookRole::Viewer)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.accepted_at.is_none() && self.expires_at > Utc::now()
+    }
+
+    // 同一邮箱在同一账本只保留最新一份未接受的邀请
+    pub async fn create(
+        pool: &crate::database::DbPool,
+        create_invitation: CreateAccountBookInvitation,
+    ) -> anyhow::Result<i64> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM account_book_invitations WHERE account_book_id = ? AND email = ? AND accepted_at IS NULL"
+        )
+        .bind(create_invitation.account_book_id)
+        .bind(&create_invitation.email)
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO account_book_invitations (account_book_id, email, role, token_hash, invited_by, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(create_invitation.account_book_id)
+        .bind(&create_invitation.email)
+        .bind(create_invitation.role.as_str())
+        .bind(&create_invitation.token_hash)
+        .bind(create_invitation.invited_by)
+        .bind(create_invitation.expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.last_insert_id() as i64)
+    }
+
+    pub async fn find_pending_by_account_book(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<Vec<AccountBookInvitation>> {
+        let invitations = sqlx::query_as::<_, AccountBookInvitation>(
+            r#"
+            SELECT * FROM account_book_invitations
+            WHERE account_book_id = ? AND accepted_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(account_book_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(invitations)
+    }
+
+    pub async fn find_pending_by_email(
+        pool: &crate::database::DbPool,
+        email: &str,
+    ) -> anyhow::Result<Vec<AccountBookInvitationWithBook>> {
+        let invitations = sqlx::query_as::<_, AccountBookInvitationWithBook>(
+            r#"
+            SELECT i.*, ab.name AS book_name, COALESCE(NULLIF(p.display_name, ''), u.email) AS inviter_name
+            FROM account_book_invitations i
+            JOIN account_books ab ON i.account_book_id = ab.id
+            LEFT JOIN users u ON i.invited_by = u.id
+            LEFT JOIN user_preferences p ON p.user_id = i.invited_by
+            WHERE i.email = ? AND i.accepted_at IS NULL AND i.expires_at > NOW() AND ab.is_active = TRUE
+            ORDER BY i.created_at
+            "#,
+        )
+        .bind(email)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(invitations)
+    }
+
+    // 按令牌摘要查找，已接受或已过期的邀请同样返回，由调用方给出对应提示
+    pub async fn find_by_token_hash(
+        pool: &crate::database::DbPool,
+        token_hash: &str,
+    ) -> anyhow::Result<Option<AccountBookInvitationWithBook>> {
+        let invitation = sqlx::query_as::<_, AccountBookInvitationWithBook>(
+            r#"
+            SELECT i.*, ab.name AS book_name, COALESCE(NULLIF(p.display_name, ''), u.email) AS inviter_name
+            FROM account_book_invitations i
+            JOIN account_books ab ON i.account_book_id = ab.id
+            LEFT JOIN users u ON i.invited_by = u.id
+            LEFT JOIN user_preferences p ON p.user_id = i.invited_by
+            WHERE i.token_hash = ? AND ab.is_active = TRUE
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    // 接受邀请并加入账本；邀请已被使用或已过期时返回 false
+    // 已经是成员的用户保留原角色
+    pub async fn accept(
+        pool: &crate::database::DbPool,
+        invitation: &AccountBookInvitation,
+        user_id: i64,
+    ) -> anyhow::Result<bool> {
+        let mut tx = pool.begin().await?;
+
+        let rows_affected = sqlx::query(
+            r#"
+            UPDATE account_book_invitations SET accepted_at = NOW(), accepted_by = ?
+            WHERE id = ? AND accepted_at IS NULL AND expires_at > NOW()
+            "#,
+        )
+        .bind(user_id)
+        .bind(invitation.id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT IGNORE INTO account_book_members (account_book_id, user_id, role) VALUES (?, ?, ?)"
+        )
+        .bind(invitation.account_book_id)
+        .bind(user_id)
+        .bind(invitation.role().as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    pub async fn revoke(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        invitation_id: i64,
+    ) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query(
+            "DELETE FROM account_book_invitations WHERE id = ? AND account_book_id = ? AND accepted_at IS NULL"
+        )
+        .bind(invitation_id)
+        .bind(account_book_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+// 账本成员角色：所有者可管理账本和成员，编辑者可记账和管理分类，查看者只读
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum BookRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl BookRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BookRole::Owner => "owner",
+            BookRole::Editor => "editor",
+            BookRole::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<BookRole> {
+        match value {
+            "owner" => Some(BookRole::Owner),
+            "editor" => Some(BookRole::Editor),
+            "viewer" => Some(BookRole::Viewer),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BookRole::Owner => "所有者",
+            BookRole::Editor => "编辑者",
+            BookRole::Viewer => "查看者",
+        }
+    }
+
+    pub fn can_edit(&self) -> bool {
+        *self >= BookRole::Editor
+    }
+
+    pub fn is_owner(&self) -> bool {
+        *self == BookRole::Owner
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AccountBookMember {
+    pub account_book_id: i64,
+    pub user_id: i64,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// 成员列表展示用，附带邮箱和显示名称
+#[derive(Debug, Serialize, FromRow)]
+pub struct AccountBookMemberWithUser {
+    pub account_book_id: i64,
+    pub user_id: i64,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+    pub email: String,
+    pub display_name: Option<String>,
+}
+
+impl AccountBookMemberWithUser {
+    pub fn role(&self) -> BookRole {
+        BookRole::parse(&self.role).unwrap_or(BookRole::Viewer)
+    }
+}
+
+impl AccountBookMember {
+    pub async fn find_role(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<Option<BookRole>> {
+        let role: Option<String> = sqlx::query_scalar(
+            "SELECT role FROM account_book_members WHERE account_book_id = ? AND user_id = ?"
+        )
+        .bind(account_book_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(role.as_deref().and_then(BookRole::parse))
+    }
+
+    pub async fn find_by_account_book(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<Vec<AccountBookMemberWithUser>> {
+        let members = sqlx::query_as::<_, AccountBookMemberWithUser>(
+            r#"
+            SELECT m.*, u.email, p.display_name
+            FROM account_book_members m
+            JOIN users u ON m.user_id = u.id
+            LEFT JOIN user_preferences p ON p.user_id = u.id
+            WHERE m.account_book_id = ?
+            ORDER BY FIELD(m.role, 'owner', 'editor', 'viewer'), m.created_at
+            "#,
+        )
+        .bind(account_book_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(members)
+    }
+
+    pub async fn count_owners(pool: &crate::database::DbPool, account_book_id: i64) -> anyhow::Result<i64> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM account_book_members WHERE account_book_id = ? AND role = 'owner'"
+        )
+        .bind(account_book_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    pub async fn update_role(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        user_id: i64,
+        role: BookRole,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE account_book_members SET role = ? WHERE account_book_id = ? AND user_id = ?")
+            .bind(role.as_str())
+            .bind(account_book_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query(
+            "DELETE FROM account_book_members WHERE account_book_id = ? AND user_id = ?"
+        )
+        .bind(account_book_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+// 令牌权限范围
+pub const API_TOKEN_SCOPE_READ: &str = "read";
+pub const API_TOKEN_SCOPE_WRITE: &str = "write";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    pub token_hash: String,
+    pub scope: String,
+    pub account_book_id: Option<i64>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiToken {
+    pub user_id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    pub token_hash: String,
+    pub scope: String,
+    pub account_book_id: Option<i64>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    pub async fn create(pool: &crate::database::DbPool, create_token: CreateApiToken) -> anyhow::Result<ApiToken> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO api_tokens (user_id, name, token_prefix, token_hash, scope, account_book_id, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(create_token.user_id)
+        .bind(&create_token.name)
+        .bind(&create_token.token_prefix)
+        .bind(&create_token.token_hash)
+        .bind(&create_token.scope)
+        .bind(create_token.account_book_id)
+        .bind(create_token.expires_at)
+        .execute(pool)
+        .await?;
+
+        let token_id = result.last_insert_id() as i64;
+
+        let token = sqlx::query_as::<_, ApiToken>("SELECT * FROM api_tokens WHERE id = ?")
+            .bind(token_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(token)
+    }
+
+    // 按摘要查找未过期的令牌
+    pub async fn find_active_by_hash(
+        pool: &crate::database::DbPool,
+        token_hash: &str,
+    ) -> anyhow::Result<Option<ApiToken>> {
+        let token = sqlx::query_as::<_, ApiToken>(
+            "SELECT * FROM api_tokens WHERE token_hash = ? AND (expires_at IS NULL OR expires_at > NOW())"
+        )
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    pub async fn find_by_user(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<Vec<ApiToken>> {
+        let tokens = sqlx::query_as::<_, ApiToken>(
+            "SELECT * FROM api_tokens WHERE user_id = ? ORDER BY created_at DESC"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    // 记录最近使用时间，一分钟内的重复请求不再写库
+    pub async fn touch(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE api_tokens SET last_used_at = NOW() WHERE id = ? AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL 1 MINUTE)"
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(pool: &crate::database::DbPool, id: i64, user_id: i64) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query("DELETE FROM api_tokens WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    pub async fn delete_all_for_user(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM api_tokens WHERE user_id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.scope == API_TOKEN_SCOPE_READ
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AuthThrottle {
+    pub action: String,
+    pub throttle_key: String,
+    pub failure_count: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_failure_at: DateTime<Utc>,
+}
+
+impl AuthThrottle {
+    pub async fn find(
+        pool: &crate::database::DbPool,
+        action: &str,
+        throttle_key: &str,
+    ) -> anyhow::Result<Option<AuthThrottle>> {
+        let throttle = sqlx::query_as::<_, AuthThrottle>(
+            "SELECT * FROM auth_throttles WHERE action = ? AND throttle_key = ?"
+        )
+        .bind(action)
+        .bind(throttle_key)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(throttle)
+    }
+
+    // 在一条语句内原子地计数：处于锁定期时不计数并返回None；否则累加次数（距上次超过
+    // reset_after_seconds 则重新计数），达到 max_attempts 时同时写入锁定截止时间，返回累加后的次数。
+    // 锁定时长为 base_lockout_seconds × 2^(次数 - max_attempts)，不超过 max_lockout_seconds。
+    // 计数结果通过 LAST_INSERT_ID(expr) 随 OK 包返回，并发请求各自拿到不同的次数
+    pub async fn increment(
+        pool: &crate::database::DbPool,
+        action: &str,
+        throttle_key: &str,
+        reset_after_seconds: i64,
+        max_attempts: i32,
+        base_lockout_seconds: i64,
+        max_lockout_seconds: i64,
+    ) -> anyhow::Result<Option<i32>> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO auth_throttles (action, throttle_key, failure_count, locked_until, last_failure_at)
+            VALUES (
+                ?, ?, LAST_INSERT_ID(1),
+                IF(1 >= ?, NOW() + INTERVAL LEAST(?, ?) SECOND, NULL),
+                NOW()
+            )
+            ON DUPLICATE KEY UPDATE
+                failure_count = IF(
+                    locked_until > NOW(),
+                    failure_count + LAST_INSERT_ID(0),
+                    LAST_INSERT_ID(IF(last_failure_at < NOW() - INTERVAL ? SECOND, 1, failure_count + 1))
+                ),
+                last_failure_at = IF(locked_until > NOW(), last_failure_at, NOW()),
+                locked_until = IF(
+                    locked_until > NOW(),
+                    locked_until,
+                    IF(
+                        failure_count >= ?,
+                        NOW() + INTERVAL LEAST(?, ? * POW(2, LEAST(failure_count - ?, 20))) SECOND,
+                        NULL
+                    )
+                )
+            "#,
+        )
+        .bind(action)
+        .bind(throttle_key)
+        .bind(max_attempts)
+        .bind(max_lockout_seconds)
+        .bind(base_lockout_seconds)
+        .bind(reset_after_seconds)
+        .bind(max_attempts)
+        .bind(max_lockout_seconds)
+        .bind(base_lockout_seconds)
+        .bind(max_attempts)
+        .execute(pool)
+        .await?;
+
+        // 锁定期内 LAST_INSERT_ID 被置为0
+        match result.last_insert_id() {
+            0 => Ok(None),
+            count => Ok(Some(count as i32)),
+        }
+    }
+
+    pub async fn clear(
+        pool: &crate::database::DbPool,
+        action: &str,
+        throttle_key: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM auth_throttles WHERE action = ? AND throttle_key = ?")
+            .bind(action)
+            .bind(throttle_key)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 清理长期没有失败记录的条目
+    pub async fn delete_stale(pool: &crate::database::DbPool, older_than_seconds: i64) -> anyhow::Result<()> {
+        sqlx::query(
+            "DELETE FROM auth_throttles WHERE last_failure_at < NOW() - INTERVAL ? SECOND AND (locked_until IS NULL OR locked_until < NOW())"
+        )
+        .bind(older_than_seconds)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -26,6 +26,15 @@ pub struct CreateCategory {
     pub color: Option<String>,
 }
 
+// 新建账本时按分类模板预置的分类
+#[derive(Debug, Clone, Deserialize)]
+pub struct CategorySeed {
+    pub name: String,
+    pub category_type: String,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+}
+
 impl Category {
     pub async fn create(
         pool: &crate::database::DbPool,
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+use crate::models::CategorySeed;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CategoryTemplate {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CategoryTemplateItem {
+    pub id: i64,
+    pub template_id: i64,
+    pub name: String,
+    #[serde(rename = "type")]
+    #[sqlx(rename = "type")]
+    pub category_type: String,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+    pub sort_order: i32,
+}
+
+impl From<CategoryTemplateItem> for CategorySeed {
+    fn from(item: CategoryTemplateItem) -> Self {
+        Self {
+            name: item.name,
+            category_type: item.category_type,
+            icon: item.icon,
+            color: item.color,
+        }
+    }
+}
+
+impl CategoryTemplate {
+    pub async fn find_by_user(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+    ) -> anyhow::Result<Vec<CategoryTemplate>> {
+        let templates = sqlx::query_as::<_, CategoryTemplate>(
+            "SELECT * FROM category_templates WHERE user_id = ? ORDER BY name"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(templates)
+    }
+
+    // 只返回属于该用户的模板条目，模板不存在时返回 None
+    pub async fn find_items(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<Option<Vec<CategoryTemplateItem>>> {
+        let exists: Option<(i64,)> = sqlx::query_as(
+            "SELECT id FROM category_templates WHERE id = ? AND user_id = ?"
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        let items = sqlx::query_as::<_, CategoryTemplateItem>(
+            "SELECT * FROM category_template_items WHERE template_id = ? ORDER BY sort_order, id"
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Some(items))
+    }
+
+    // 把账本当前的分类保存为用户模板，同名模板会被覆盖；返回保存的分类数量
+    pub async fn save_from_account_book(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        name: &str,
+        account_book_id: i64,
+    ) -> anyhow::Result<u64> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO category_templates (user_id, name) VALUES (?, ?)
+            ON DUPLICATE KEY UPDATE updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+        let (template_id,): (i64,) = sqlx::query_as(
+            "SELECT id FROM category_templates WHERE user_id = ? AND name = ?"
+        )
+        .bind(user_id)
+        .bind(name)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM category_template_items WHERE template_id = ?")
+            .bind(template_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let saved = sqlx::query(
+            r#"
+            INSERT INTO category_template_items (template_id, name, `type`, icon, color, sort_order)
+            SELECT ?, name, `type`, icon, color, sort_order
+            FROM categories
+            WHERE account_book_id = ? AND is_active = TRUE
+            "#,
+        )
+        .bind(template_id)
+        .bind(account_book_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        // 账本没有分类时不保留空模板
+        if saved == 0 {
+            tx.rollback().await?;
+            return Ok(0);
+        }
+
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    pub async fn delete(
+        pool: &crate::database::DbPool,
+        id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query("DELETE FROM category_templates WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExchangeRate {
+    pub id: i64,
+    pub user_id: i64,
+    pub base_currency: String,
+    pub quote_currency: String,
+    // 1单位基础货币兑换的报价货币
+    pub rate: Decimal,
+    pub rate_date: NaiveDate,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateExchangeRate {
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate: Decimal,
+    pub rate_date: NaiveDate,
+}
+
+impl ExchangeRate {
+    // 批量导入，同一货币对同一天的汇率以最后导入的为准
+    pub async fn import(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        rates: &[CreateExchangeRate],
+    ) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+
+        for rate in rates {
+            sqlx::query(
+                r#"
+                INSERT INTO exchange_rates (user_id, base_currency, quote_currency, rate, rate_date)
+                VALUES (?, ?, ?, ?, ?)
+                ON DUPLICATE KEY UPDATE rate = VALUES(rate)
+                "#,
+            )
+            .bind(user_id)
+            .bind(&rate.base_currency)
+            .bind(&rate.quote_currency)
+            .bind(rate.rate)
+            .bind(rate.rate_date)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find_recent_by_user(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        limit: i64,
+    ) -> anyhow::Result<Vec<ExchangeRate>> {
+        let rates = sqlx::query_as::<_, ExchangeRate>(
+            r#"
+            SELECT * FROM exchange_rates
+            WHERE user_id = ?
+            ORDER BY rate_date DESC, base_currency, quote_currency
+            LIMIT ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rates)
+    }
+
+    // 查找指定日期当天或之前最近的汇率，返回1单位 from 兑换的 to；
+    // 只有反向汇率时取倒数
+    pub async fn find_rate(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> anyhow::Result<Option<Decimal>> {
+        let row: Option<(Decimal, String)> = sqlx::query_as(
+            r#"
+            SELECT rate, base_currency FROM exchange_rates
+            WHERE user_id = ? AND rate_date <= ?
+              AND ((base_currency = ? AND quote_currency = ?) OR (base_currency = ? AND quote_currency = ?))
+            ORDER BY rate_date DESC, base_currency = ? DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(date)
+        .bind(from)
+        .bind(to)
+        .bind(to)
+        .bind(from)
+        .bind(from)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.and_then(|(rate, base_currency)| {
+            if base_currency == from {
+                Some(rate)
+            } else if rate > Decimal::ZERO {
+                Some((Decimal::ONE / rate).round_dp(8))
+            } else {
+                None
+            }
+        }))
+    }
+
+    pub async fn delete_all(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<u64> {
+        let rows_affected = sqlx::query("DELETE FROM exchange_rates WHERE user_id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+}
@@ -1,9 +1,43 @@
 pub mod user;
 pub mod account_book;
+pub mod account_book_member;
+pub mod account_book_invitation;
+pub mod period_closing;
 pub mod category;
+pub mod category_template;
+pub mod wallet;
+pub mod wallet_snapshot;
 pub mod transaction;
+pub mod transaction_split;
+pub mod tag;
+pub mod transfer;
+pub mod recurring_transaction;
+pub mod exchange_rate;
+pub mod session;
+pub mod recovery_code;
+pub mod api_token;
+pub mod auth_throttle;
+pub mod user_identity;
+pub mod user_preferences;
 
 pub use user::*;
 pub use account_book::*;
+pub use account_book_member::*;
+pub use account_book_invitation::*;
+pub use period_closing::*;
 pub use category::*;
-pub use transaction::*;
\ No newline at end of file
+pub use category_template::*;
+pub use wallet::*;
+pub use wallet_snapshot::*;
+pub use transaction::*;
+pub use transaction_split::*;
+pub use tag::*;
+pub use transfer::*;
+pub use recurring_transaction::*;
+pub use exchange_rate::*;
+pub use session::*;
+pub use recovery_code::*;
+pub use api_token::*;
+pub use auth_throttle::*;
+pub use user_identity::*;
+pub use user_preferences::*;
\ No newline at end of file
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, NaiveDate, Utc};
+
+// 结账和重新开放的操作记录
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PeriodClosing {
+    pub id: i64,
+    pub account_book_id: i64,
+    pub user_id: Option<i64>,
+    // close 或 reopen
+    pub action: String,
+    // 操作后的结账截止日期，完全重新开放时为空
+    pub closed_through: Option<NaiveDate>,
+    pub previous_closed_through: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct PeriodClosingWithUser {
+    #[sqlx(flatten)]
+    pub closing: PeriodClosing,
+    pub user_name: Option<String>,
+}
+
+impl PeriodClosing {
+    // 把结账截止日期推后到 closed_through，仅所有者可以操作
+    pub async fn close(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        user_id: i64,
+        closed_through: NaiveDate,
+    ) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+
+        let previous = Self::lock_current(&mut tx, account_book_id, user_id).await?;
+        if previous.is_some_and(|previous| closed_through <= previous) {
+            return Err(anyhow::anyhow!("结账日期必须晚于当前的结账截止日期，如需提前请先重新开放"));
+        }
+
+        Self::record(&mut tx, account_book_id, user_id, "close", Some(closed_through), previous).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // 重新开放 closed_through 之后的期间，为空时完全取消结账，仅所有者可以操作
+    pub async fn reopen(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        user_id: i64,
+        closed_through: Option<NaiveDate>,
+    ) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+
+        let previous = match Self::lock_current(&mut tx, account_book_id, user_id).await? {
+            Some(previous) => previous,
+            None => return Err(anyhow::anyhow!("账本当前没有已结账的期间")),
+        };
+        if closed_through.is_some_and(|closed_through| closed_through >= previous) {
+            return Err(anyhow::anyhow!("重新开放后的结账截止日期必须早于当前的结账截止日期"));
+        }
+
+        Self::record(&mut tx, account_book_id, user_id, "reopen", closed_through, Some(previous)).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // 锁定账本行并读取当前的结账截止日期，非所有者返回错误
+    async fn lock_current(
+        tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+        account_book_id: i64,
+        user_id: i64,
+    ) -> anyhow::Result<Option<NaiveDate>> {
+        let row: Option<(Option<NaiveDate>,)> = sqlx::query_as(
+            r#"
+            SELECT ab.closed_through FROM account_books ab
+            JOIN account_book_members m ON m.account_book_id = ab.id
+            WHERE ab.id = ? AND ab.is_active = TRUE AND m.user_id = ? AND m.role = 'owner'
+            FOR UPDATE
+            "#,
+        )
+        .bind(account_book_id)
+        .bind(user_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        match row {
+            Some((closed_through,)) => Ok(closed_through),
+            None => Err(anyhow::anyhow!("账本不存在或仅所有者可以操作")),
+        }
+    }
+
+    async fn record(
+        tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+        account_book_id: i64,
+        user_id: i64,
+        action: &str,
+        closed_through: Option<NaiveDate>,
+        previous_closed_through: Option<NaiveDate>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE account_books SET closed_through = ?, updated_at = NOW() WHERE id = ?")
+            .bind(closed_through)
+            .bind(account_book_id)
+            .execute(&mut **tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO account_book_period_closings (account_book_id, user_id, action, closed_through, previous_closed_through)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_book_id)
+        .bind(user_id)
+        .bind(action)
+        .bind(closed_through)
+        .bind(previous_closed_through)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_account_book(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        limit: i64,
+    ) -> anyhow::Result<Vec<PeriodClosingWithUser>> {
+        let closings = sqlx::query_as::<_, PeriodClosingWithUser>(
+            r#"
+            SELECT c.*, COALESCE(NULLIF(p.display_name, ''), u.email) AS user_name
+            FROM account_book_period_closings c
+            LEFT JOIN users u ON c.user_id = u.id
+            LEFT JOIN user_preferences p ON p.user_id = c.user_id
+            WHERE c.account_book_id = ?
+            ORDER BY c.created_at DESC, c.id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(account_book_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(closings)
+    }
+}
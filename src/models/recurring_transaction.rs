@@ -0,0 +1,553 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlConnection};
+use chrono::{DateTime, Days, Months, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+// 重复周期：(取值, 名称)
+pub const RECURRING_FREQUENCIES: &[(&str, &str)] = &[
+    ("daily", "每天"),
+    ("weekly", "每周"),
+    ("monthly", "每月"),
+    ("yearly", "每年"),
+    ("every_n_days", "每隔N天"),
+];
+
+// 间隔天数的上限
+pub const RECURRING_MAX_INTERVAL_DAYS: i32 = 3650;
+
+pub fn frequency_label(frequency: &str) -> &'static str {
+    RECURRING_FREQUENCIES
+        .iter()
+        .find(|(value, _)| *value == frequency)
+        .map(|(_, label)| *label)
+        .unwrap_or("每天")
+}
+
+// 第 sequence 次（从0开始）的日期，总是从起始日期推算：每月/每年遇到月末不存在的日期时取当月最后一天，
+// 例如31日起始的规则在2月为28日（或29日），3月仍为31日
+pub fn recurrence_date(frequency: &str, interval_days: i32, start_date: NaiveDate, sequence: i32) -> Option<NaiveDate> {
+    let sequence = u32::try_from(sequence).ok()?;
+    match frequency {
+        "daily" => start_date.checked_add_days(Days::new(sequence as u64)),
+        "weekly" => start_date.checked_add_days(Days::new(sequence as u64 * 7)),
+        "every_n_days" => start_date.checked_add_days(Days::new(sequence as u64 * interval_days.max(1) as u64)),
+        "monthly" => start_date.checked_add_months(Months::new(sequence)),
+        "yearly" => start_date.checked_add_months(Months::new(sequence.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+// 重复记账规则：到期后由后台任务按规则生成交易，金额为原始货币，生成时按当日汇率折算
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RecurringTransaction {
+    pub id: i64,
+    pub account_book_id: i64,
+    pub category_id: i64,
+    pub wallet_id: Option<i64>,
+    #[serde(rename = "type")]
+    #[sqlx(rename = "type")]
+    pub transaction_type: String,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    // 为空时使用记录人导入的汇率
+    pub exchange_rate: Option<Decimal>,
+    pub description: Option<String>,
+    // 逗号分隔的标签名称，生成交易时解析
+    pub tags: Option<String>,
+    pub frequency: String,
+    // 仅“每隔N天”使用
+    pub interval_days: i32,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    // 最多生成的次数（跳过的也计入）
+    pub max_occurrences: Option<i32>,
+    // 下一次待处理的序号和日期，规则结束后日期为空
+    pub next_sequence: i32,
+    pub next_date: Option<NaiveDate>,
+    pub is_active: bool,
+    pub created_by: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct RecurringTransactionWithCategory {
+    #[sqlx(flatten)]
+    pub rule: RecurringTransaction,
+    pub category_name: String,
+    pub category_icon: Option<String>,
+    pub category_color: Option<String>,
+    pub wallet_name: Option<String>,
+}
+
+// 规则的单次发生记录：已生成交易、已跳过或因所在期间已结账未生成；同一规则同一序号只有一条，保证不会重复生成
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RecurringOccurrence {
+    pub id: i64,
+    pub recurring_transaction_id: i64,
+    pub sequence: i32,
+    pub occurrence_date: NaiveDate,
+    // created / skipped / blocked
+    pub status: String,
+    // 生成的交易，交易被删除后为空
+    pub transaction_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct CreateRecurringTransaction {
+    pub account_book_id: i64,
+    pub category_id: i64,
+    pub wallet_id: Option<i64>,
+    pub transaction_type: String,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub exchange_rate: Option<Decimal>,
+    pub description: Option<String>,
+    pub tags: Option<String>,
+    pub frequency: String,
+    pub interval_days: i32,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub max_occurrences: Option<i32>,
+    pub created_by: i64,
+}
+
+// 修改规则时可以调整的字段，周期和起始日期创建后不可修改
+#[derive(Debug)]
+pub struct UpdateRecurringTransaction {
+    pub category_id: i64,
+    pub wallet_id: Option<i64>,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub exchange_rate: Option<Decimal>,
+    pub description: Option<String>,
+    pub tags: Option<String>,
+    pub end_date: Option<NaiveDate>,
+    pub max_occurrences: Option<i32>,
+}
+
+impl RecurringTransaction {
+    // 第 sequence 次的日期，超出结束日期或次数时为空
+    pub fn occurrence_date(&self, sequence: i32) -> Option<NaiveDate> {
+        if self.max_occurrences.is_some_and(|max| sequence >= max) {
+            return None;
+        }
+        let date = recurrence_date(&self.frequency, self.interval_days, self.start_date, sequence)?;
+        if self.end_date.is_some_and(|end_date| date > end_date) {
+            return None;
+        }
+        Some(date)
+    }
+
+    pub async fn create(
+        pool: &crate::database::DbPool,
+        create: CreateRecurringTransaction,
+    ) -> anyhow::Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO recurring_transactions
+                (account_book_id, category_id, wallet_id, `type`, original_currency, original_amount, exchange_rate,
+                 description, tags, frequency, interval_days, start_date, end_date, max_occurrences,
+                 next_sequence, next_date, created_by)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0, ?, ?)
+            "#,
+        )
+        .bind(create.account_book_id)
+        .bind(create.category_id)
+        .bind(create.wallet_id)
+        .bind(&create.transaction_type)
+        .bind(&create.original_currency)
+        .bind(create.original_amount)
+        .bind(create.exchange_rate)
+        .bind(&create.description)
+        .bind(&create.tags)
+        .bind(&create.frequency)
+        .bind(create.interval_days)
+        .bind(create.start_date)
+        .bind(create.end_date)
+        .bind(create.max_occurrences)
+        .bind(create.start_date)
+        .bind(create.created_by)
+        .execute(pool)
+        .await?;
+
+        Ok(result.last_insert_id() as i64)
+    }
+
+    pub async fn find_by_id(
+        pool: &crate::database::DbPool,
+        id: i64,
+    ) -> anyhow::Result<Option<RecurringTransaction>> {
+        let rule = sqlx::query_as::<_, RecurringTransaction>("SELECT * FROM recurring_transactions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(rule)
+    }
+
+    // 在调用方的事务内锁定规则，生成交易前调用，避免并发重复生成
+    pub async fn lock(conn: &mut MySqlConnection, id: i64) -> anyhow::Result<Option<RecurringTransaction>> {
+        let rule = sqlx::query_as::<_, RecurringTransaction>("SELECT * FROM recurring_transactions WHERE id = ? FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        Ok(rule)
+    }
+
+    pub async fn find_by_account_book_with_category(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<Vec<RecurringTransactionWithCategory>> {
+        let rules = sqlx::query_as::<_, RecurringTransactionWithCategory>(
+            r#"
+            SELECT r.*, c.name as category_name, c.icon as category_icon, c.color as category_color,
+                   w.name as wallet_name
+            FROM recurring_transactions r
+            JOIN categories c ON r.category_id = c.id
+            LEFT JOIN wallets w ON r.wallet_id = w.id
+            WHERE r.account_book_id = ?
+            ORDER BY r.is_active DESC, r.next_date IS NULL, r.next_date, r.id
+            "#,
+        )
+        .bind(account_book_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rules)
+    }
+
+    // 下一次日期不晚于 date 的规则，已归档账本的规则暂停处理
+    pub async fn find_due(pool: &crate::database::DbPool, date: NaiveDate) -> anyhow::Result<Vec<RecurringTransaction>> {
+        let rules = sqlx::query_as::<_, RecurringTransaction>(
+            r#"
+            SELECT r.* FROM recurring_transactions r
+            JOIN account_books ab ON r.account_book_id = ab.id
+            WHERE r.is_active = TRUE AND r.next_date <= ? AND ab.is_active = TRUE
+            ORDER BY r.next_date, r.id
+            "#,
+        )
+        .bind(date)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rules)
+    }
+
+    // 修改规则后按新的结束条件重新计算下一次日期
+    pub async fn update(
+        pool: &crate::database::DbPool,
+        id: i64,
+        update: UpdateRecurringTransaction,
+    ) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+
+        let mut rule = match Self::lock(&mut tx, id).await? {
+            Some(rule) => rule,
+            None => anyhow::bail!("重复记账规则不存在"),
+        };
+        rule.end_date = update.end_date;
+        rule.max_occurrences = update.max_occurrences;
+        let next_date = rule.occurrence_date(rule.next_sequence);
+
+        sqlx::query(
+            r#"
+            UPDATE recurring_transactions
+            SET category_id = ?, wallet_id = ?, original_currency = ?, original_amount = ?, exchange_rate = ?,
+                description = ?, tags = ?, end_date = ?, max_occurrences = ?, next_date = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(update.category_id)
+        .bind(update.wallet_id)
+        .bind(&update.original_currency)
+        .bind(update.original_amount)
+        .bind(update.exchange_rate)
+        .bind(&update.description)
+        .bind(&update.tags)
+        .bind(update.end_date)
+        .bind(update.max_occurrences)
+        .bind(next_date)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // 记录已处理到的序号；须在调用方的事务内执行
+    pub async fn advance(
+        conn: &mut MySqlConnection,
+        id: i64,
+        next_sequence: i32,
+        next_date: Option<NaiveDate>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE recurring_transactions SET next_sequence = ?, next_date = ? WHERE id = ?")
+            .bind(next_sequence)
+            .bind(next_date)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // 暂停或恢复规则；恢复后暂停期间到期的次数会补生成
+    pub async fn set_active(pool: &crate::database::DbPool, id: i64, is_active: bool) -> anyhow::Result<()> {
+        sqlx::query("UPDATE recurring_transactions SET is_active = ? WHERE id = ?")
+            .bind(is_active)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 记账人失去编辑权限时暂停规则；须在调用方的事务内执行
+    pub async fn pause(conn: &mut MySqlConnection, id: i64) -> anyhow::Result<()> {
+        sqlx::query("UPDATE recurring_transactions SET is_active = FALSE WHERE id = ?")
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // 恢复规则并改由 user_id 记账，原创建者已不是账本的编辑者时使用
+    pub async fn resume_as(pool: &crate::database::DbPool, id: i64, user_id: i64) -> anyhow::Result<()> {
+        sqlx::query("UPDATE recurring_transactions SET is_active = TRUE, created_by = ? WHERE id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 删除规则，已生成的交易保留
+    pub async fn delete(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM recurring_transactions WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl RecurringOccurrence {
+    pub async fn find_by_sequence(
+        conn: &mut MySqlConnection,
+        recurring_transaction_id: i64,
+        sequence: i32,
+    ) -> anyhow::Result<Option<RecurringOccurrence>> {
+        let occurrence = sqlx::query_as::<_, RecurringOccurrence>(
+            "SELECT * FROM recurring_occurrences WHERE recurring_transaction_id = ? AND sequence = ?"
+        )
+        .bind(recurring_transaction_id)
+        .bind(sequence)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        Ok(occurrence)
+    }
+
+    // 序号不小于 from_sequence 的记录，即提前生成或提前跳过的次数
+    pub async fn find_from_sequence(
+        pool: &crate::database::DbPool,
+        recurring_transaction_id: i64,
+        from_sequence: i32,
+    ) -> anyhow::Result<Vec<RecurringOccurrence>> {
+        let occurrences = sqlx::query_as::<_, RecurringOccurrence>(
+            r#"
+            SELECT * FROM recurring_occurrences
+            WHERE recurring_transaction_id = ? AND sequence >= ?
+            ORDER BY sequence
+            "#,
+        )
+        .bind(recurring_transaction_id)
+        .bind(from_sequence)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(occurrences)
+    }
+
+    // 最近处理过的次数，最新的在前
+    pub async fn find_recent(
+        pool: &crate::database::DbPool,
+        recurring_transaction_id: i64,
+        before_sequence: i32,
+        limit: i64,
+    ) -> anyhow::Result<Vec<RecurringOccurrence>> {
+        let occurrences = sqlx::query_as::<_, RecurringOccurrence>(
+            r#"
+            SELECT * FROM recurring_occurrences
+            WHERE recurring_transaction_id = ? AND sequence < ?
+            ORDER BY sequence DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(recurring_transaction_id)
+        .bind(before_sequence)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(occurrences)
+    }
+
+    // 因所在期间已结账而未生成的次数
+    pub async fn count_blocked(
+        pool: &crate::database::DbPool,
+        recurring_transaction_id: i64,
+    ) -> anyhow::Result<i64> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM recurring_occurrences WHERE recurring_transaction_id = ? AND status = 'blocked'"
+        )
+        .bind(recurring_transaction_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    // 记录一次发生；同一序号已有记录时不插入并返回 false。须在调用方的事务内执行
+    pub async fn record(
+        conn: &mut MySqlConnection,
+        recurring_transaction_id: i64,
+        sequence: i32,
+        occurrence_date: NaiveDate,
+        status: &str,
+        transaction_id: Option<i64>,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT IGNORE INTO recurring_occurrences
+                (recurring_transaction_id, sequence, occurrence_date, status, transaction_id)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(recurring_transaction_id)
+        .bind(sequence)
+        .bind(occurrence_date)
+        .bind(status)
+        .bind(transaction_id)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 取消尚未到期的跳过，到期后按规则正常生成
+    pub async fn restore_skipped(
+        pool: &crate::database::DbPool,
+        recurring_transaction_id: i64,
+        sequence: i32,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE o FROM recurring_occurrences o
+            JOIN recurring_transactions r ON o.recurring_transaction_id = r.id
+            WHERE o.recurring_transaction_id = ? AND o.sequence = ? AND o.status = 'skipped'
+                AND o.sequence >= r.next_sequence
+            "#,
+        )
+        .bind(recurring_transaction_id)
+        .bind(sequence)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn rule(frequency: &str, start_date: &str, end_date: Option<&str>, max_occurrences: Option<i32>) -> RecurringTransaction {
+        RecurringTransaction {
+            id: 1,
+            account_book_id: 1,
+            category_id: 1,
+            wallet_id: None,
+            transaction_type: "expense".to_string(),
+            original_currency: "CNY".to_string(),
+            original_amount: Decimal::ONE_HUNDRED,
+            exchange_rate: None,
+            description: None,
+            tags: None,
+            frequency: frequency.to_string(),
+            interval_days: 1,
+            start_date: date(start_date),
+            end_date: end_date.map(date),
+            max_occurrences,
+            next_sequence: 0,
+            next_date: Some(date(start_date)),
+            is_active: true,
+            created_by: Some(1),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn monthly_clamps_to_month_end_without_drifting() {
+        let start = date("2024-01-31");
+        let dates: Vec<NaiveDate> = (0..4)
+            .map(|sequence| recurrence_date("monthly", 1, start, sequence).unwrap())
+            .collect();
+        assert_eq!(dates, vec![date("2024-01-31"), date("2024-02-29"), date("2024-03-31"), date("2024-04-30")]);
+
+        assert_eq!(recurrence_date("monthly", 1, date("2023-01-31"), 1), Some(date("2023-02-28")));
+        assert_eq!(recurrence_date("monthly", 1, date("2023-01-31"), 2), Some(date("2023-03-31")));
+    }
+
+    #[test]
+    fn yearly_on_leap_day_falls_back_to_february_28() {
+        let start = date("2024-02-29");
+        assert_eq!(recurrence_date("yearly", 1, start, 1), Some(date("2025-02-28")));
+        assert_eq!(recurrence_date("yearly", 1, start, 3), Some(date("2027-02-28")));
+        assert_eq!(recurrence_date("yearly", 1, start, 4), Some(date("2028-02-29")));
+    }
+
+    #[test]
+    fn day_based_frequencies() {
+        let start = date("2024-12-30");
+        assert_eq!(recurrence_date("daily", 1, start, 3), Some(date("2025-01-02")));
+        assert_eq!(recurrence_date("weekly", 1, start, 2), Some(date("2025-01-13")));
+        assert_eq!(recurrence_date("every_n_days", 14, start, 2), Some(date("2025-01-27")));
+        assert_eq!(recurrence_date("monthly", 1, start, -1), None);
+        assert_eq!(recurrence_date("hourly", 1, start, 0), None);
+    }
+
+    #[test]
+    fn occurrence_date_stops_at_end_date() {
+        let rule = rule("monthly", "2024-01-31", Some("2024-03-31"), None);
+        assert_eq!(rule.occurrence_date(1), Some(date("2024-02-29")));
+        // 结束日期当天仍会生成
+        assert_eq!(rule.occurrence_date(2), Some(date("2024-03-31")));
+        assert_eq!(rule.occurrence_date(3), None);
+    }
+
+    #[test]
+    fn occurrence_date_stops_after_max_occurrences() {
+        let rule = rule("weekly", "2024-01-01", None, Some(3));
+        assert_eq!(rule.occurrence_date(2), Some(date("2024-01-15")));
+        assert_eq!(rule.occurrence_date(3), None);
+
+        // 两个条件同时设置时先到者生效
+        let rule = self::rule("weekly", "2024-01-01", Some("2024-01-10"), Some(3));
+        assert_eq!(rule.occurrence_date(1), Some(date("2024-01-08")));
+        assert_eq!(rule.occurrence_date(2), None);
+    }
+}
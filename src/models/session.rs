@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Session {
+    pub id: String,
+    pub user_id: i64,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSession {
+    pub id: String,
+    pub user_id: i64,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub async fn create(pool: &crate::database::DbPool, create_session: CreateSession) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO sessions (id, user_id, user_agent, ip_address, expires_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&create_session.id)
+        .bind(create_session.user_id)
+        .bind(&create_session.user_agent)
+        .bind(&create_session.ip_address)
+        .bind(create_session.expires_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_active(pool: &crate::database::DbPool, id: &str) -> anyhow::Result<Option<Session>> {
+        let session = sqlx::query_as::<_, Session>(
+            "SELECT * FROM sessions WHERE id = ? AND expires_at > NOW()"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    pub async fn find_by_user(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<Vec<Session>> {
+        let sessions = sqlx::query_as::<_, Session>(
+            "SELECT * FROM sessions WHERE user_id = ? AND expires_at > NOW() ORDER BY last_seen_at DESC"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    // 更新最后活跃时间，一分钟内的重复请求不再写库
+    pub async fn touch(pool: &crate::database::DbPool, id: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE sessions SET last_seen_at = NOW() WHERE id = ? AND last_seen_at < NOW() - INTERVAL 1 MINUTE"
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(pool: &crate::database::DbPool, id: &str, user_id: i64) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query("DELETE FROM sessions WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    pub async fn delete_all_for_user(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_expired(pool: &crate::database::DbPool) -> anyhow::Result<()> {
+        sqlx::query("CALL CleanExpiredSessions()")
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySql, MySqlConnection, QueryBuilder};
+use chrono::{DateTime, Utc};
+
+// 标签名称的最大长度（字符）
+pub const TAG_NAME_MAX_CHARS: usize = 50;
+
+// 账本内的标签，交易通过 transaction_tags 关联，同一账本内名称唯一
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Tag {
+    pub id: i64,
+    pub account_book_id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TagWithCount {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub transaction_count: i64,
+}
+
+// 交易与标签的关联
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TransactionTag {
+    pub transaction_id: i64,
+    pub tag_id: i64,
+}
+
+// 解析逗号分隔的标签（中英文逗号均可），去除空白和重复，过长的名称截断
+pub fn parse_tag_names(input: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in input.split([',', '，']) {
+        let name: String = name.trim().chars().take(TAG_NAME_MAX_CHARS).collect();
+        let name = name.trim_end().to_string();
+        if !name.is_empty() && !names.iter().any(|existing| existing.to_lowercase() == name.to_lowercase()) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+impl Tag {
+    // 替换交易的全部标签，不存在的标签自动创建；须在调用方的事务内执行
+    pub async fn set_for_transaction(
+        conn: &mut MySqlConnection,
+        account_book_id: i64,
+        transaction_id: i64,
+        names: &[String],
+    ) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM transaction_tags WHERE transaction_id = ?")
+            .bind(transaction_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert_tags = QueryBuilder::<MySql>::new("INSERT IGNORE INTO tags (account_book_id, name) ");
+        insert_tags.push_values(names, |mut row, name| {
+            row.push_bind(account_book_id).push_bind(name.clone());
+        });
+        insert_tags.build().execute(&mut *conn).await?;
+
+        let mut link = QueryBuilder::<MySql>::new("INSERT IGNORE INTO transaction_tags (transaction_id, tag_id) SELECT ");
+        link.push_bind(transaction_id);
+        link.push(", id FROM tags WHERE account_book_id = ");
+        link.push_bind(account_book_id);
+        link.push(" AND name IN (");
+        let mut separated = link.separated(", ");
+        for name in names {
+            separated.push_bind(name.clone());
+        }
+        separated.push_unseparated(")");
+        link.build().execute(&mut *conn).await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(
+        pool: &crate::database::DbPool,
+        id: i64,
+    ) -> anyhow::Result<Option<Tag>> {
+        let tag = sqlx::query_as::<_, Tag>("SELECT * FROM tags WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(tag)
+    }
+
+    pub async fn find_by_name(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        name: &str,
+    ) -> anyhow::Result<Option<Tag>> {
+        let tag = sqlx::query_as::<_, Tag>("SELECT * FROM tags WHERE account_book_id = ? AND name = ?")
+            .bind(account_book_id)
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(tag)
+    }
+
+    // 账本内全部标签及使用次数，常用的排在前面
+    pub async fn find_by_account_book_with_counts(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<Vec<TagWithCount>> {
+        let tags = sqlx::query_as::<_, TagWithCount>(
+            r#"
+            SELECT g.*, COUNT(tt.transaction_id) as transaction_count
+            FROM tags g
+            LEFT JOIN transaction_tags tt ON tt.tag_id = g.id
+            WHERE g.account_book_id = ?
+            GROUP BY g.id
+            ORDER BY transaction_count DESC, g.name
+            "#,
+        )
+        .bind(account_book_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(tags)
+    }
+
+    pub async fn rename(pool: &crate::database::DbPool, id: i64, name: &str) -> anyhow::Result<()> {
+        sqlx::query("UPDATE tags SET name = ? WHERE id = ?")
+            .bind(name)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 把 source 标签的交易并入 target 标签，然后删除 source 标签
+    pub async fn merge(pool: &crate::database::DbPool, source_id: i64, target_id: i64) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT IGNORE INTO transaction_tags (transaction_id, tag_id)
+            SELECT transaction_id, ? FROM transaction_tags WHERE tag_id = ?
+            "#,
+        )
+        .bind(target_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM tags WHERE id = ?")
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // 删除标签，交易本身保留
+    pub async fn delete(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM tags WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -1,20 +1,28 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, MySql, MySqlConnection, QueryBuilder};
 use chrono::{DateTime, Utc, NaiveDate};
 use rust_decimal::Decimal;
 
+use crate::models::{NewTransactionSplit, Tag, TransactionSplit};
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Transaction {
     pub id: i64,
     pub account_book_id: i64,
     pub category_id: i64,
+    // 资金账户，未指定账户时为空
+    pub wallet_id: Option<i64>,
+    // 账本货币金额，统计报表均按此汇总
     pub amount: Decimal,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub exchange_rate: Decimal,
     #[serde(rename = "type")]
     #[sqlx(rename = "type")]
     pub transaction_type: String,
     pub description: Option<String>,
     pub transaction_date: NaiveDate,
-    pub tags: Option<String>,
+    pub created_by: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -24,29 +32,151 @@ pub struct TransactionWithCategory {
     pub id: i64,
     pub account_book_id: i64,
     pub category_id: i64,
+    // 资金账户，未指定账户时为空
+    pub wallet_id: Option<i64>,
+    // 账本货币金额，统计报表均按此汇总
     pub amount: Decimal,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub exchange_rate: Decimal,
     #[serde(rename = "type")]
     #[sqlx(rename = "type")]
     pub transaction_type: String,
     pub description: Option<String>,
     pub transaction_date: NaiveDate,
-    pub tags: Option<String>,
+    pub created_by: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    // 标签名称，逗号分隔，按名称排序
+    pub tags: Option<String>,
+    // 拆分明细的分类名称，按行的顺序以顿号分隔；未拆分时为空
+    pub split_categories: Option<String>,
     pub category_name: String,
     pub category_icon: Option<String>,
     pub category_color: Option<String>,
+    pub wallet_name: Option<String>,
+    // 该笔交易后所在账户的余额，只在分页列表中计算
+    #[sqlx(default)]
+    pub wallet_balance: Option<Decimal>,
+    // 记录人的显示名称（未设置时为邮箱），记录人已注销时为空
+    pub created_by_name: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateTransaction {
     pub account_book_id: i64,
     pub category_id: i64,
+    pub wallet_id: Option<i64>,
     pub amount: Decimal,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub exchange_rate: Decimal,
     pub transaction_type: String,
     pub description: Option<String>,
     pub transaction_date: NaiveDate,
-    pub tags: Option<String>,
+    // 标签名称，见 parse_tag_names
+    pub tags: Vec<String>,
+    // 拆分明细，为空表示不拆分
+    pub splits: Vec<NewTransactionSplit>,
+    pub created_by: i64,
+}
+
+// 修改交易时可以调整的字段
+#[derive(Debug)]
+pub struct UpdateTransaction {
+    pub category_id: i64,
+    pub wallet_id: Option<i64>,
+    pub amount: TransactionAmount,
+    pub description: Option<String>,
+    pub transaction_date: NaiveDate,
+    pub tags: Vec<String>,
+    pub splits: Vec<NewTransactionSplit>,
+}
+
+// 交易列表的筛选条件，未设置的条件不参与筛选
+#[derive(Debug, Default, Clone)]
+pub struct TransactionFilter {
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub transaction_type: Option<String>,
+    // 任选其一即可匹配
+    pub category_ids: Vec<i64>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+    pub tag: Option<String>,
+    // 在描述中模糊匹配
+    pub keyword: Option<String>,
+}
+
+impl TransactionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.start_date.is_none()
+            && self.end_date.is_none()
+            && self.transaction_type.is_none()
+            && self.category_ids.is_empty()
+            && self.min_amount.is_none()
+            && self.max_amount.is_none()
+            && self.tag.is_none()
+            && self.keyword.is_none()
+    }
+
+    // 追加筛选条件，交易表的别名须为 x
+    fn push_conditions(&self, builder: &mut QueryBuilder<'_, MySql>) {
+        if let Some(start_date) = self.start_date {
+            builder.push(" AND x.transaction_date >= ").push_bind(start_date);
+        }
+        if let Some(end_date) = self.end_date {
+            builder.push(" AND x.transaction_date <= ").push_bind(end_date);
+        }
+        if let Some(ref transaction_type) = self.transaction_type {
+            builder.push(" AND x.`type` = ").push_bind(transaction_type.clone());
+        }
+        if !self.category_ids.is_empty() {
+            // 拆分交易的任一行属于所选分类即可匹配
+            builder.push(" AND (x.category_id IN (");
+            let mut separated = builder.separated(", ");
+            for category_id in &self.category_ids {
+                separated.push_bind(*category_id);
+            }
+            separated.push_unseparated(")");
+            builder.push(" OR EXISTS (SELECT 1 FROM transaction_splits fs WHERE fs.transaction_id = x.id AND fs.category_id IN (");
+            let mut separated = builder.separated(", ");
+            for category_id in &self.category_ids {
+                separated.push_bind(*category_id);
+            }
+            separated.push_unseparated(")))");
+        }
+        if let Some(min_amount) = self.min_amount {
+            builder.push(" AND x.amount >= ").push_bind(min_amount);
+        }
+        if let Some(max_amount) = self.max_amount {
+            builder.push(" AND x.amount <= ").push_bind(max_amount);
+        }
+        if let Some(ref tag) = self.tag {
+            builder
+                .push(" AND EXISTS (SELECT 1 FROM transaction_tags ft JOIN tags fg ON ft.tag_id = fg.id WHERE ft.transaction_id = x.id AND fg.name = ")
+                .push_bind(tag.clone())
+                .push(")");
+        }
+        if let Some(ref keyword) = self.keyword {
+            builder.push(" AND x.description LIKE ").push_bind(contains_pattern(keyword));
+        }
+    }
+}
+
+// 转义 LIKE 通配符，按字面包含匹配
+fn contains_pattern(keyword: &str) -> String {
+    let escaped = keyword.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// 原始币种金额及折算结果，新建和修改交易时使用
+#[derive(Debug, Clone)]
+pub struct TransactionAmount {
+    pub amount: Decimal,
+    pub original_currency: String,
+    pub original_amount: Decimal,
+    pub exchange_rate: Decimal,
 }
 
 impl Transaction {
@@ -54,57 +184,192 @@ impl Transaction {
         pool: &crate::database::DbPool,
         create_transaction: CreateTransaction,
     ) -> anyhow::Result<Transaction> {
+        let mut tx = pool.begin().await?;
+        let transaction_id = Self::insert(&mut tx, &create_transaction).await?;
+        tx.commit().await?;
+
+        let transaction = sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = ?")
+            .bind(transaction_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(transaction)
+    }
+
+    // 写入交易及其标签和拆分明细，返回交易ID；须在调用方的事务内执行
+    pub async fn insert(
+        conn: &mut MySqlConnection,
+        create_transaction: &CreateTransaction,
+    ) -> anyhow::Result<i64> {
         let result = sqlx::query(
             r#"
-            INSERT INTO transactions (account_book_id, category_id, amount, `type`, description, transaction_date, tags)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO transactions
+                (account_book_id, category_id, wallet_id, amount, original_currency, original_amount, exchange_rate,
+                 `type`, description, transaction_date, created_by)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(create_transaction.account_book_id)
         .bind(create_transaction.category_id)
+        .bind(create_transaction.wallet_id)
         .bind(create_transaction.amount)
+        .bind(&create_transaction.original_currency)
+        .bind(create_transaction.original_amount)
+        .bind(create_transaction.exchange_rate)
         .bind(&create_transaction.transaction_type)
         .bind(&create_transaction.description)
         .bind(create_transaction.transaction_date)
-        .bind(&create_transaction.tags)
-        .execute(pool)
+        .bind(create_transaction.created_by)
+        .execute(&mut *conn)
         .await?;
 
         let transaction_id = result.last_insert_id() as i64;
 
-        let transaction = sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = ?")
-            .bind(transaction_id)
-            .fetch_one(pool)
-            .await?;
+        Tag::set_for_transaction(&mut *conn, create_transaction.account_book_id, transaction_id, &create_transaction.tags).await?;
+        TransactionSplit::replace_for_transaction(&mut *conn, transaction_id, &create_transaction.splits).await?;
 
-        Ok(transaction)
+        Ok(transaction_id)
     }
 
+    // wallet_balance 为该笔交易后的账户余额：按流水推算，交易日之前有余额快照时
+    // 再加上最近一次快照与当日推算余额的差额，即以快照为起点
     pub async fn find_by_account_book_with_category(
         pool: &crate::database::DbPool,
         account_book_id: i64,
+        filter: &TransactionFilter,
         limit: i64,
         offset: i64,
     ) -> anyhow::Result<Vec<TransactionWithCategory>> {
-        let transactions = sqlx::query_as::<_, TransactionWithCategory>(
+        // 先按账本计算账户余额，再在外层筛选，筛选不影响每笔交易后的余额
+        let mut builder = QueryBuilder::<MySql>::new(
             r#"
-            SELECT t.*, c.name as category_name, c.icon as category_icon, c.color as category_color
-            FROM transactions t
-            JOIN categories c ON t.category_id = c.id
-            WHERE t.account_book_id = ?
-            ORDER BY t.transaction_date DESC, t.created_at DESC
-            LIMIT ? OFFSET ?
-            "#,
-        )
-        .bind(account_book_id)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(pool)
-        .await?;
+            SELECT x.* FROM (
+                SELECT t.*, c.name as category_name, c.icon as category_icon, c.color as category_color,
+                       w.name as wallet_name,
+                       (SELECT GROUP_CONCAT(g.name ORDER BY g.name SEPARATOR ',')
+                           FROM transaction_tags tg JOIN tags g ON tg.tag_id = g.id
+                           WHERE tg.transaction_id = t.id) as tags,
+                       (SELECT GROUP_CONCAT(sc.name ORDER BY sp.sort_order, sp.id SEPARATOR '、')
+                           FROM transaction_splits sp JOIN categories sc ON sp.category_id = sc.id
+                           WHERE sp.transaction_id = t.id) as split_categories,
+                       w.opening_balance + SUM(CASE WHEN t.`type` = 'income' THEN t.amount ELSE -t.amount END)
+                           OVER (PARTITION BY t.wallet_id ORDER BY t.transaction_date, t.created_at, t.id)
+                           + COALESCE((
+                               SELECT SUM(CASE WHEN tr.to_wallet_id = t.wallet_id THEN tr.to_amount ELSE -tr.amount END)
+                               FROM transfers tr
+                               WHERE (tr.from_wallet_id = t.wallet_id OR tr.to_wallet_id = t.wallet_id)
+                                   AND tr.transfer_date <= t.transaction_date
+                           ), 0)
+                           + COALESCE((
+                               SELECT s.balance - w.opening_balance
+                                   - COALESCE((
+                                       SELECT SUM(CASE WHEN st.`type` = 'income' THEN st.amount ELSE -st.amount END)
+                                       FROM transactions st
+                                       WHERE st.wallet_id = s.wallet_id AND st.transaction_date <= s.snapshot_date
+                                   ), 0)
+                                   - COALESCE((
+                                       SELECT SUM(CASE WHEN tr.to_wallet_id = s.wallet_id THEN tr.to_amount ELSE -tr.amount END)
+                                       FROM transfers tr
+                                       WHERE (tr.from_wallet_id = s.wallet_id OR tr.to_wallet_id = s.wallet_id)
+                                           AND tr.transfer_date <= s.snapshot_date
+                                   ), 0)
+                               FROM wallet_snapshots s
+                               WHERE s.wallet_id = t.wallet_id AND s.snapshot_date < t.transaction_date
+                               ORDER BY s.snapshot_date DESC
+                               LIMIT 1
+                           ), 0) as wallet_balance,
+                       COALESCE(NULLIF(p.display_name, ''), u.email) as created_by_name
+                FROM transactions t
+                JOIN categories c ON t.category_id = c.id
+                LEFT JOIN wallets w ON t.wallet_id = w.id
+                LEFT JOIN users u ON t.created_by = u.id
+                LEFT JOIN user_preferences p ON p.user_id = t.created_by
+                WHERE t.account_book_id = "#,
+        );
+        builder.push_bind(account_book_id);
+        builder.push(") x WHERE 1 = 1");
+        filter.push_conditions(&mut builder);
+        builder.push(" ORDER BY x.transaction_date DESC, x.created_at DESC LIMIT ");
+        builder.push_bind(limit);
+        builder.push(" OFFSET ");
+        builder.push_bind(offset);
+
+        let transactions = builder
+            .build_query_as::<TransactionWithCategory>()
+            .fetch_all(pool)
+            .await?;
 
         Ok(transactions)
     }
 
+    // 符合筛选条件的收入合计、支出合计和笔数
+    pub async fn get_filtered_summary(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        filter: &TransactionFilter,
+    ) -> anyhow::Result<(Decimal, Decimal, i64)> {
+        if !filter.category_ids.is_empty() {
+            return Self::get_filtered_category_summary(pool, account_book_id, filter).await;
+        }
+
+        let mut builder = QueryBuilder::<MySql>::new(
+            r#"
+            SELECT
+                COALESCE(SUM(CASE WHEN x.`type` = 'income' THEN x.amount ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN x.`type` = 'expense' THEN x.amount ELSE 0 END), 0),
+                COUNT(*)
+            FROM transactions x
+            WHERE x.account_book_id = "#,
+        );
+        builder.push_bind(account_book_id);
+        filter.push_conditions(&mut builder);
+
+        let summary = builder
+            .build_query_as::<(Decimal, Decimal, i64)>()
+            .fetch_one(pool)
+            .await?;
+
+        Ok(summary)
+    }
+
+    // 按分类筛选时，拆分交易只计入所选分类的行，与分类统计的口径一致
+    async fn get_filtered_category_summary(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        filter: &TransactionFilter,
+    ) -> anyhow::Result<(Decimal, Decimal, i64)> {
+        let mut builder = QueryBuilder::<MySql>::new(
+            r#"
+            SELECT
+                COALESCE(SUM(CASE WHEN x.`type` = 'income' THEN l.amount ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN x.`type` = 'expense' THEN l.amount ELSE 0 END), 0),
+                COUNT(DISTINCT x.id)
+            FROM transactions x
+            JOIN (
+                SELECT t.id, COALESCE(s.category_id, t.category_id) as category_id, COALESCE(s.amount, t.amount) as amount
+                FROM transactions t
+                LEFT JOIN transaction_splits s ON s.transaction_id = t.id
+                WHERE t.account_book_id = "#,
+        );
+        builder.push_bind(account_book_id);
+        builder.push(") l ON l.id = x.id AND l.category_id IN (");
+        let mut separated = builder.separated(", ");
+        for category_id in &filter.category_ids {
+            separated.push_bind(*category_id);
+        }
+        separated.push_unseparated(")");
+        builder.push(" WHERE x.account_book_id = ");
+        builder.push_bind(account_book_id);
+        filter.push_conditions(&mut builder);
+
+        let summary = builder
+            .build_query_as::<(Decimal, Decimal, i64)>()
+            .fetch_one(pool)
+            .await?;
+
+        Ok(summary)
+    }
+
     pub async fn find_by_date_range_with_category(
         pool: &crate::database::DbPool,
         account_book_id: i64,
@@ -113,9 +378,20 @@ impl Transaction {
     ) -> anyhow::Result<Vec<TransactionWithCategory>> {
         let transactions = sqlx::query_as::<_, TransactionWithCategory>(
             r#"
-            SELECT t.*, c.name as category_name, c.icon as category_icon, c.color as category_color
+            SELECT t.*, c.name as category_name, c.icon as category_icon, c.color as category_color,
+                   w.name as wallet_name,
+                   (SELECT GROUP_CONCAT(g.name ORDER BY g.name SEPARATOR ',')
+                       FROM transaction_tags tg JOIN tags g ON tg.tag_id = g.id
+                       WHERE tg.transaction_id = t.id) as tags,
+                   (SELECT GROUP_CONCAT(sc.name ORDER BY sp.sort_order, sp.id SEPARATOR '、')
+                       FROM transaction_splits sp JOIN categories sc ON sp.category_id = sc.id
+                       WHERE sp.transaction_id = t.id) as split_categories,
+                   COALESCE(NULLIF(p.display_name, ''), u.email) as created_by_name
             FROM transactions t
             JOIN categories c ON t.category_id = c.id
+            LEFT JOIN wallets w ON t.wallet_id = w.id
+            LEFT JOIN users u ON t.created_by = u.id
+            LEFT JOIN user_preferences p ON p.user_id = t.created_by
             WHERE t.account_book_id = ? AND t.transaction_date BETWEEN ? AND ?
             ORDER BY t.transaction_date DESC, t.created_at DESC
             "#,
@@ -144,31 +420,55 @@ impl Transaction {
     pub async fn update(
         pool: &crate::database::DbPool,
         id: i64,
-        category_id: i64,
-        amount: Decimal,
-        description: Option<&str>,
-        transaction_date: NaiveDate,
-        tags: Option<&str>,
+        update_transaction: UpdateTransaction,
     ) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+
+        let account_book_id: i64 = sqlx::query_scalar("SELECT account_book_id FROM transactions WHERE id = ?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
         sqlx::query(
             r#"
             UPDATE transactions 
-            SET category_id = ?, amount = ?, description = ?, transaction_date = ?, tags = ?, updated_at = NOW()
+            SET category_id = ?, wallet_id = ?, amount = ?, original_currency = ?, original_amount = ?, exchange_rate = ?,
+                description = ?, transaction_date = ?, updated_at = NOW()
             WHERE id = ?
             "#,
         )
-        .bind(category_id)
-        .bind(amount)
-        .bind(description)
-        .bind(transaction_date)
-        .bind(tags)
+        .bind(update_transaction.category_id)
+        .bind(update_transaction.wallet_id)
+        .bind(update_transaction.amount.amount)
+        .bind(&update_transaction.amount.original_currency)
+        .bind(update_transaction.amount.original_amount)
+        .bind(update_transaction.amount.exchange_rate)
+        .bind(&update_transaction.description)
+        .bind(update_transaction.transaction_date)
         .bind(id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
 
+        Tag::set_for_transaction(&mut tx, account_book_id, id, &update_transaction.tags).await?;
+        TransactionSplit::replace_for_transaction(&mut tx, id, &update_transaction.splits).await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
+    pub async fn exists_in_account_book(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<bool> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM transactions WHERE account_book_id = ? LIMIT 1")
+            .bind(account_book_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
     pub async fn delete(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
         sqlx::query("DELETE FROM transactions WHERE id = ?")
             .bind(id)
@@ -205,4 +505,17 @@ impl Transaction {
             expense.unwrap_or(Decimal::ZERO),
         ))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_like_wildcards_in_keyword() {
+        assert_eq!(contains_pattern("房租"), "%房租%");
+        assert_eq!(contains_pattern("100%"), "%100\\%%");
+        assert_eq!(contains_pattern("a_b"), "%a\\_b%");
+        assert_eq!(contains_pattern("C:\\x"), "%C:\\\\x%");
+    }
+}
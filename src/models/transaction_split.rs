@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySql, MySqlConnection, QueryBuilder};
+use rust_decimal::Decimal;
+
+use crate::models::TransactionAmount;
+
+// 交易的拆分明细：一笔交易拆成多行，每行有自己的分类、金额和备注，合计等于交易金额。
+// 拆分后交易的 category_id 为第一行的分类，分类统计按各行归集
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TransactionSplit {
+    pub id: i64,
+    pub transaction_id: i64,
+    pub category_id: i64,
+    // 账本货币金额
+    pub amount: Decimal,
+    // 与交易相同的原始货币金额
+    pub original_amount: Decimal,
+    pub note: Option<String>,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TransactionSplitWithCategory {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub split: TransactionSplit,
+    pub category_name: String,
+    pub category_icon: Option<String>,
+    pub category_color: Option<String>,
+}
+
+// 用户填写的一行拆分，金额为交易的原始货币
+#[derive(Debug, Clone, Deserialize)]
+pub struct SplitLine {
+    pub category_id: i64,
+    pub amount: Decimal,
+    pub note: Option<String>,
+}
+
+// 折算后待保存的一行拆分
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTransactionSplit {
+    pub category_id: i64,
+    pub amount: Decimal,
+    pub original_amount: Decimal,
+    pub note: Option<String>,
+}
+
+impl TransactionSplit {
+    // 校验各行金额合计等于交易的原始金额，并按交易汇率折算为账本货币；
+    // 折算的尾差计入最后一行，使各行合计与交易的账本货币金额一致
+    pub fn allocate(amount: &TransactionAmount, lines: &[SplitLine]) -> anyhow::Result<Vec<NewTransactionSplit>> {
+        if lines.len() < 2 {
+            anyhow::bail!("拆分至少需要两行");
+        }
+        if lines.iter().any(|line| line.amount <= Decimal::ZERO) {
+            anyhow::bail!("拆分金额必须大于0");
+        }
+
+        let total: Decimal = lines.iter().map(|line| line.amount).sum();
+        if total != amount.original_amount {
+            anyhow::bail!(
+                "拆分金额合计 {} 与交易金额 {} 不一致",
+                total.normalize(),
+                amount.original_amount.normalize()
+            );
+        }
+
+        let mut splits = Vec::with_capacity(lines.len());
+        let mut allocated = Decimal::ZERO;
+        for (index, line) in lines.iter().enumerate() {
+            let converted = if index + 1 == lines.len() {
+                amount.amount - allocated
+            } else {
+                (line.amount * amount.exchange_rate).round_dp(2)
+            };
+            // 小额行折算后可能舍入为0，尾差也可能让最后一行变为0或负数
+            if converted <= Decimal::ZERO {
+                anyhow::bail!("第{}行拆分金额折算为账本货币后不足0.01，请调整拆分金额", index + 1);
+            }
+            allocated += converted;
+            splits.push(NewTransactionSplit {
+                category_id: line.category_id,
+                amount: converted,
+                original_amount: line.amount,
+                note: line.note.as_ref().map(|note| note.trim().to_string()).filter(|note| !note.is_empty()),
+            });
+        }
+
+        Ok(splits)
+    }
+
+    // 替换交易的全部拆分明细，为空表示不拆分；须在调用方的事务内执行
+    pub async fn replace_for_transaction(
+        conn: &mut MySqlConnection,
+        transaction_id: i64,
+        splits: &[NewTransactionSplit],
+    ) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM transaction_splits WHERE transaction_id = ?")
+            .bind(transaction_id)
+            .execute(&mut *conn)
+            .await?;
+
+        if splits.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder = QueryBuilder::<MySql>::new(
+            "INSERT INTO transaction_splits (transaction_id, category_id, amount, original_amount, note, sort_order) "
+        );
+        builder.push_values(splits.iter().enumerate(), |mut row, (index, split)| {
+            row.push_bind(transaction_id)
+                .push_bind(split.category_id)
+                .push_bind(split.amount)
+                .push_bind(split.original_amount)
+                .push_bind(split.note.clone())
+                .push_bind(index as i32);
+        });
+        builder.build().execute(&mut *conn).await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_transaction(
+        pool: &crate::database::DbPool,
+        transaction_id: i64,
+    ) -> anyhow::Result<Vec<TransactionSplitWithCategory>> {
+        let splits = sqlx::query_as::<_, TransactionSplitWithCategory>(
+            r#"
+            SELECT s.*, c.name as category_name, c.icon as category_icon, c.color as category_color
+            FROM transaction_splits s
+            JOIN categories c ON s.category_id = c.id
+            WHERE s.transaction_id = ?
+            ORDER BY s.sort_order, s.id
+            "#,
+        )
+        .bind(transaction_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(splits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn line(category_id: i64, amount: &str) -> SplitLine {
+        SplitLine { category_id, amount: dec(amount), note: None }
+    }
+
+    fn converted(original_amount: &str, exchange_rate: &str) -> TransactionAmount {
+        let original_amount = dec(original_amount);
+        let exchange_rate = dec(exchange_rate);
+        TransactionAmount {
+            amount: (original_amount * exchange_rate).round_dp(2),
+            original_currency: "USD".to_string(),
+            original_amount,
+            exchange_rate,
+        }
+    }
+
+    #[test]
+    fn puts_rounding_remainder_on_last_line() {
+        // 10.00 * 0.333 = 3.33，前两行 3.33 * 0.333 ≈ 1.11，最后一行取剩余的 1.11
+        let amount = converted("10.00", "0.333");
+        let splits = TransactionSplit::allocate(&amount, &[line(1, "3.33"), line(2, "3.33"), line(3, "3.34")]).unwrap();
+
+        let amounts: Vec<Decimal> = splits.iter().map(|split| split.amount).collect();
+        assert_eq!(amounts, vec![dec("1.11"), dec("1.11"), dec("1.11")]);
+        assert_eq!(amounts.iter().copied().sum::<Decimal>(), amount.amount);
+        assert_eq!(splits[2].original_amount, dec("3.34"));
+    }
+
+    #[test]
+    fn keeps_amounts_when_currency_matches_book() {
+        let amount = converted("100.00", "1");
+        let splits = TransactionSplit::allocate(&amount, &[line(1, "60.50"), line(2, "39.50")]).unwrap();
+        assert_eq!(splits[0].amount, dec("60.50"));
+        assert_eq!(splits[1].amount, dec("39.50"));
+    }
+
+    #[test]
+    fn rejects_lines_that_convert_to_nothing() {
+        // 0.01 * 0.3 舍入为 0
+        let amount = converted("10.01", "0.3");
+        assert!(TransactionSplit::allocate(&amount, &[line(1, "0.01"), line(2, "10.00")]).is_err());
+
+        // 0.04 * 0.75 = 0.03，前两行舍入为 0.02 和 0.01，最后一行只剩0
+        let amount = converted("0.04", "0.75");
+        assert!(TransactionSplit::allocate(&amount, &[line(1, "0.02"), line(2, "0.01"), line(3, "0.01")]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let amount = converted("10.00", "1");
+        assert!(TransactionSplit::allocate(&amount, &[line(1, "10.00")]).is_err());
+        assert!(TransactionSplit::allocate(&amount, &[line(1, "10.00"), line(2, "0")]).is_err());
+        assert!(TransactionSplit::allocate(&amount, &[line(1, "5.00"), line(2, "4.00")]).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySqlConnection};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+// 资金账户之间的转账，可以跨账本；不计入收支统计，只影响账户余额。
+// 跨账本转账的一端账本被彻底删除后，该端的账本和账户为空，另一端保留不变
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Transfer {
+    pub id: i64,
+    pub from_account_book_id: Option<i64>,
+    pub from_wallet_id: Option<i64>,
+    pub to_account_book_id: Option<i64>,
+    pub to_wallet_id: Option<i64>,
+    // 转出金额（转出账本货币）
+    pub amount: Decimal,
+    // 转入金额（转入账本货币），同币种时与转出金额相同
+    pub to_amount: Decimal,
+    pub description: Option<String>,
+    pub transfer_date: NaiveDate,
+    pub created_by: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 转账列表展示用，附带两端的账本和账户名称
+#[derive(Debug, Serialize, FromRow)]
+pub struct TransferWithWallets {
+    #[sqlx(flatten)]
+    pub transfer: Transfer,
+    pub from_book_name: String,
+    pub from_book_currency: String,
+    pub from_wallet_name: String,
+    pub to_book_name: String,
+    pub to_book_currency: String,
+    pub to_wallet_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTransfer {
+    pub from_account_book_id: i64,
+    pub from_wallet_id: i64,
+    pub to_account_book_id: i64,
+    pub to_wallet_id: i64,
+    pub amount: Decimal,
+    pub to_amount: Decimal,
+    pub description: Option<String>,
+    pub transfer_date: NaiveDate,
+    pub created_by: i64,
+}
+
+impl Transfer {
+    pub async fn create(
+        pool: &crate::database::DbPool,
+        create_transfer: CreateTransfer,
+    ) -> anyhow::Result<Transfer> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO transfers (from_account_book_id, from_wallet_id, to_account_book_id, to_wallet_id,
+                                   amount, to_amount, description, transfer_date, created_by)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(create_transfer.from_account_book_id)
+        .bind(create_transfer.from_wallet_id)
+        .bind(create_transfer.to_account_book_id)
+        .bind(create_transfer.to_wallet_id)
+        .bind(create_transfer.amount)
+        .bind(create_transfer.to_amount)
+        .bind(&create_transfer.description)
+        .bind(create_transfer.transfer_date)
+        .bind(create_transfer.created_by)
+        .execute(pool)
+        .await?;
+
+        let transfer_id = result.last_insert_id() as i64;
+        let transfer = Self::find_by_id(pool, transfer_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to retrieve created transfer"))?;
+
+        Ok(transfer)
+    }
+
+    pub async fn find_by_id(
+        pool: &crate::database::DbPool,
+        id: i64,
+    ) -> anyhow::Result<Option<Transfer>> {
+        let transfer = sqlx::query_as::<_, Transfer>("SELECT * FROM transfers WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(transfer)
+    }
+
+    // 转出或转入该账本的全部转账
+    pub async fn find_by_account_book(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<TransferWithWallets>> {
+        let transfers = sqlx::query_as::<_, TransferWithWallets>(
+            r#"
+            SELECT tr.*,
+                   COALESCE(fb.name, '已删除的账本') AS from_book_name, COALESCE(fb.currency, '') AS from_book_currency,
+                   COALESCE(fw.name, '已删除的账户') AS from_wallet_name,
+                   COALESCE(tb.name, '已删除的账本') AS to_book_name, COALESCE(tb.currency, '') AS to_book_currency,
+                   COALESCE(tw.name, '已删除的账户') AS to_wallet_name
+            FROM transfers tr
+            LEFT JOIN account_books fb ON tr.from_account_book_id = fb.id
+            LEFT JOIN wallets fw ON tr.from_wallet_id = fw.id
+            LEFT JOIN account_books tb ON tr.to_account_book_id = tb.id
+            LEFT JOIN wallets tw ON tr.to_wallet_id = tw.id
+            WHERE tr.from_account_book_id = ? OR tr.to_account_book_id = ?
+            ORDER BY tr.transfer_date DESC, tr.created_at DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(account_book_id)
+        .bind(account_book_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(transfers)
+    }
+
+    pub async fn delete(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM transfers WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 彻底删除账本前调用，须在调用方的事务内执行：删除两端都属于该账本（或另一端已删除）的转账；
+    // 跨账本转账保留，账本删除后外键把该端置空，另一端账本的账户余额和已结账期间不受影响
+    pub async fn delete_for_purged_book(conn: &mut MySqlConnection, account_book_id: i64) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            DELETE FROM transfers
+            WHERE (from_account_book_id = ? AND (to_account_book_id = ? OR to_account_book_id IS NULL))
+               OR (to_account_book_id = ? AND from_account_book_id IS NULL)
+            "#,
+        )
+        .bind(account_book_id)
+        .bind(account_book_id)
+        .bind(account_book_id)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+}
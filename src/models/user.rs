@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc};
 
+use crate::models::Transfer;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: i64,
@@ -9,7 +11,16 @@ pub struct User {
     pub password_hash: String,
     pub is_verified: bool,
     pub verification_token: Option<String>,
+    pub verification_expires_at: Option<DateTime<Utc>>,
+    pub pending_email: Option<String>,
     pub last_selected_account_book_id: Option<i64>,
+    pub password_reset_token: Option<String>,
+    pub password_reset_expires_at: Option<DateTime<Utc>>,
+    pub password_changed_at: Option<DateTime<Utc>>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub totp_last_step: Option<i64>,
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -37,20 +48,23 @@ impl From<User> for UserResponse {
 pub struct CreateUser {
     pub email: String,
     pub password_hash: String,
+    // 验证令牌的SHA-256摘要
     pub verification_token: String,
+    pub verification_expires_at: DateTime<Utc>,
 }
 
 impl User {
     pub async fn create(pool: &crate::database::DbPool, create_user: CreateUser) -> anyhow::Result<User> {
         let result = sqlx::query(
             r#"
-            INSERT INTO users (email, password_hash, verification_token)
-            VALUES (?, ?, ?)
+            INSERT INTO users (email, password_hash, verification_token, verification_expires_at)
+            VALUES (?, ?, ?, ?)
             "#,
         )
         .bind(&create_user.email)
         .bind(&create_user.password_hash)
         .bind(&create_user.verification_token)
+        .bind(create_user.verification_expires_at)
         .execute(pool)
         .await?;
 
@@ -79,11 +93,56 @@ impl User {
         Ok(user)
     }
 
-    pub async fn verify_email(pool: &crate::database::DbPool, token: &str) -> anyhow::Result<bool> {
+    // 不检查有效期，便于区分链接过期和链接无效
+    pub async fn find_by_verification_token(
+        pool: &crate::database::DbPool,
+        token_hash: &str,
+    ) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE verification_token = ?")
+            .bind(token_hash)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(user)
+    }
+
+    // 验证通过后，如有待确认的新邮箱则同时切换邮箱
+    pub async fn verify_email(pool: &crate::database::DbPool, token_hash: &str) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query(
+            r#"
+            UPDATE users
+            SET is_verified = TRUE, email = COALESCE(pending_email, email), pending_email = NULL,
+                verification_token = NULL, verification_expires_at = NULL
+            WHERE verification_token = ? AND verification_expires_at > NOW()
+            "#
+        )
+        .bind(token_hash)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    // 外部身份提供方已验证过邮箱时接管未验证的账户：标记为已验证，并换掉注册时设置的密码及
+    // 其他凭据，防止他人抢先用该邮箱注册后凭自己设置的密码登录
+    pub async fn claim_unverified(
+        pool: &crate::database::DbPool,
+        id: i64,
+        password_hash: &str,
+    ) -> anyhow::Result<bool> {
         let rows_affected = sqlx::query(
-            "UPDATE users SET is_verified = TRUE, verification_token = NULL WHERE verification_token = ?"
+            r#"
+            UPDATE users
+            SET is_verified = TRUE, verification_token = NULL, verification_expires_at = NULL,
+                password_hash = ?, password_changed_at = NOW(),
+                password_reset_token = NULL, password_reset_expires_at = NULL,
+                pending_email = NULL, totp_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL
+            WHERE id = ? AND is_verified = FALSE
+            "#,
         )
-        .bind(token)
+        .bind(password_hash)
+        .bind(id)
         .execute(pool)
         .await?
         .rows_affected();
@@ -91,13 +150,41 @@ impl User {
         Ok(rows_affected > 0)
     }
 
+    // 记录待确认的新邮箱，确认链接复用 verification_token
+    pub async fn set_pending_email(
+        pool: &crate::database::DbPool,
+        id: i64,
+        pending_email: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE users SET pending_email = ? WHERE id = ?")
+            .bind(pending_email)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn cancel_pending_email(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE users SET pending_email = NULL, verification_token = NULL, verification_expires_at = NULL WHERE id = ? AND pending_email IS NOT NULL"
+        )
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn update_verification_token(
         pool: &crate::database::DbPool,
         email: &str,
-        token: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
     ) -> anyhow::Result<()> {
-        sqlx::query("UPDATE users SET verification_token = ? WHERE email = ?")
-            .bind(token)
+        sqlx::query("UPDATE users SET verification_token = ?, verification_expires_at = ? WHERE email = ?")
+            .bind(token_hash)
+            .bind(expires_at)
             .bind(email)
             .execute(pool)
             .await?;
@@ -105,6 +192,224 @@ impl User {
         Ok(())
     }
 
+    pub async fn set_password_reset_token(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE users SET password_reset_token = ?, password_reset_expires_at = ? WHERE id = ?")
+            .bind(token_hash)
+            .bind(expires_at)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 仅返回令牌未过期的用户
+    pub async fn find_by_password_reset_token(
+        pool: &crate::database::DbPool,
+        token_hash: &str,
+    ) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE password_reset_token = ? AND password_reset_expires_at > NOW()"
+        )
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    // 更新密码并清除重置令牌，令牌只能使用一次
+    pub async fn reset_password(
+        pool: &crate::database::DbPool,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = ?, password_reset_token = NULL, password_reset_expires_at = NULL,
+                password_changed_at = NOW()
+            WHERE password_reset_token = ? AND password_reset_expires_at > NOW()
+            "#,
+        )
+        .bind(password_hash)
+        .bind(token_hash)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    // 保存待确认的TOTP密钥，确认前不会启用两步验证
+    pub async fn set_totp_secret(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        secret: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE users SET totp_secret = ?, totp_enabled = FALSE, totp_last_step = NULL WHERE id = ?")
+            .bind(secret)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn enable_totp(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<()> {
+        sqlx::query("UPDATE users SET totp_enabled = TRUE WHERE id = ? AND totp_secret IS NOT NULL")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 记录已使用的TOTP时间步，时间步不晚于上次记录时返回false（验证码重放）
+    pub async fn use_totp_step(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        step: u64,
+    ) -> anyhow::Result<bool> {
+        let step = step as i64;
+        let rows_affected = sqlx::query(
+            "UPDATE users SET totp_last_step = ? WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)"
+        )
+        .bind(step)
+        .bind(user_id)
+        .bind(step)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    pub async fn disable_totp(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<()> {
+        sqlx::query("UPDATE users SET totp_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL WHERE id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 申请注销后进入冷静期，到期前登录可撤销
+    pub async fn schedule_deletion(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        scheduled_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE users SET deletion_scheduled_at = ? WHERE id = ?")
+            .bind(scheduled_at)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn cancel_deletion(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query(
+            "UPDATE users SET deletion_scheduled_at = NULL WHERE id = ? AND deletion_scheduled_at IS NOT NULL"
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    pub async fn find_due_for_deletion(pool: &crate::database::DbPool) -> anyhow::Result<Vec<User>> {
+        let users = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE deletion_scheduled_at IS NOT NULL AND deletion_scheduled_at <= NOW()"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    // 彻底删除用户及其全部数据。交易记录引用分类，需先于账本删除
+    // 删除前在事务内再次确认冷静期已到，避免与撤销注销并发
+    pub async fn hard_delete(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<bool> {
+        let mut tx = pool.begin().await?;
+
+        let due: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM users WHERE id = ? AND deletion_scheduled_at <= NOW() FOR UPDATE"
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if due.is_none() {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        // 用户是唯一所有者的账本随用户一起删除
+        let sole_owned: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT m.account_book_id FROM account_book_members m
+            WHERE m.user_id = ? AND m.role = 'owner' AND NOT EXISTS (
+                SELECT 1 FROM account_book_members o
+                WHERE o.account_book_id = m.account_book_id AND o.role = 'owner' AND o.user_id <> m.user_id
+            )
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for book_id in sole_owned {
+            sqlx::query("DELETE FROM transactions WHERE account_book_id = ?")
+                .bind(book_id)
+                .execute(&mut *tx)
+                .await?;
+            Transfer::delete_for_purged_book(&mut tx, book_id).await?;
+            sqlx::query("DELETE FROM recurring_transactions WHERE account_book_id = ?")
+                .bind(book_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM account_books WHERE id = ?")
+                .bind(book_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // 还有其他所有者的共享账本保留下来，创建者改为最早加入的其他所有者
+        sqlx::query(
+            r#"
+            UPDATE account_books ab
+            SET ab.user_id = (
+                SELECT m.user_id FROM account_book_members m
+                WHERE m.account_book_id = ab.id AND m.role = 'owner' AND m.user_id <> ?
+                ORDER BY m.created_at, m.user_id
+                LIMIT 1
+            )
+            WHERE ab.user_id = ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
     pub async fn update_last_selected_account_book(
         pool: &crate::database::DbPool,
         user_id: i64,
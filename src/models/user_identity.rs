@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+// 外部身份提供方账号与本地用户的关联
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UserIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UserIdentity {
+    pub async fn create(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_identities (user_id, provider, subject, email, last_login_at)
+            VALUES (?, ?, ?, ?, NOW())
+            "#,
+        )
+        .bind(user_id)
+        .bind(provider)
+        .bind(subject)
+        .bind(email)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_subject(
+        pool: &crate::database::DbPool,
+        provider: &str,
+        subject: &str,
+    ) -> anyhow::Result<Option<UserIdentity>> {
+        let identity = sqlx::query_as::<_, UserIdentity>(
+            "SELECT * FROM user_identities WHERE provider = ? AND subject = ?"
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(identity)
+    }
+
+    pub async fn find_by_user(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<Vec<UserIdentity>> {
+        let identities = sqlx::query_as::<_, UserIdentity>(
+            "SELECT * FROM user_identities WHERE user_id = ? ORDER BY created_at"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(identities)
+    }
+
+    pub async fn touch(pool: &crate::database::DbPool, id: i64, email: Option<&str>) -> anyhow::Result<()> {
+        sqlx::query("UPDATE user_identities SET last_login_at = NOW(), email = COALESCE(?, email) WHERE id = ?")
+            .bind(email)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(pool: &crate::database::DbPool, id: i64, user_id: i64) -> anyhow::Result<bool> {
+        let rows_affected = sqlx::query("DELETE FROM user_identities WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+}
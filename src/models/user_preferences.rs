@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+
+pub const DEFAULT_TIMEZONE: &str = "Asia/Shanghai";
+pub const DEFAULT_LOCALE: &str = "zh-CN";
+pub const DEFAULT_CURRENCY: &str = "CNY";
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+// 设置页可选的时区（保存时仍按IANA时区名校验）
+pub const TIMEZONE_OPTIONS: &[(&str, &str)] = &[
+    ("Asia/Shanghai", "中国标准时间（北京）"),
+    ("Asia/Hong_Kong", "香港"),
+    ("Asia/Taipei", "台北"),
+    ("Asia/Tokyo", "东京"),
+    ("Asia/Singapore", "新加坡"),
+    ("Europe/London", "伦敦"),
+    ("Europe/Berlin", "柏林"),
+    ("America/New_York", "纽约"),
+    ("America/Los_Angeles", "洛杉矶"),
+    ("Australia/Sydney", "悉尼"),
+    ("UTC", "协调世界时（UTC）"),
+];
+
+pub const LOCALE_OPTIONS: &[(&str, &str)] = &[
+    ("zh-CN", "简体中文"),
+    ("zh-TW", "繁體中文"),
+    ("en-US", "English"),
+];
+
+pub const DATE_FORMAT_OPTIONS: &[(&str, &str)] = &[
+    ("%Y-%m-%d", "2024-01-31"),
+    ("%Y/%m/%d", "2024/01/31"),
+    ("%Y年%m月%d日", "2024年01月31日"),
+    ("%d/%m/%Y", "31/01/2024"),
+    ("%m/%d/%Y", "01/31/2024"),
+];
+
+// 0=周日，1=周一，6=周六
+pub const WEEK_START_OPTIONS: &[(i8, &str)] = &[
+    (1, "周一"),
+    (0, "周日"),
+    (6, "周六"),
+];
+
+// 用户个人偏好，未保存过时使用默认值
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserPreferences {
+    pub user_id: i64,
+    pub display_name: Option<String>,
+    pub timezone: String,
+    pub locale: String,
+    pub default_currency: String,
+    pub date_format: String,
+    pub week_start: i8,
+    pub default_account_book_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct UpdateUserPreferences {
+    pub display_name: Option<String>,
+    pub timezone: String,
+    pub locale: String,
+    pub default_currency: String,
+    pub date_format: String,
+    pub week_start: i8,
+    pub default_account_book_id: Option<i64>,
+}
+
+impl UserPreferences {
+    pub fn defaults(user_id: i64) -> Self {
+        let now = Utc::now();
+        Self {
+            user_id,
+            display_name: None,
+            timezone: DEFAULT_TIMEZONE.to_string(),
+            locale: DEFAULT_LOCALE.to_string(),
+            default_currency: DEFAULT_CURRENCY.to_string(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            week_start: 1,
+            default_account_book_id: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub async fn find_by_user(pool: &crate::database::DbPool, user_id: i64) -> anyhow::Result<UserPreferences> {
+        let preferences = sqlx::query_as::<_, UserPreferences>(
+            "SELECT * FROM user_preferences WHERE user_id = ?"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(preferences.unwrap_or_else(|| Self::defaults(user_id)))
+    }
+
+    // 读取失败时退回默认值，避免偏好问题影响页面展示
+    pub async fn load(pool: &crate::database::DbPool, user_id: i64) -> UserPreferences {
+        match Self::find_by_user(pool, user_id).await {
+            Ok(preferences) => preferences,
+            Err(e) => {
+                tracing::warn!("Failed to load preferences for user {}: {}", user_id, e);
+                Self::defaults(user_id)
+            }
+        }
+    }
+
+    pub async fn save(
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        update: &UpdateUserPreferences,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_preferences
+                (user_id, display_name, timezone, locale, default_currency, date_format, week_start, default_account_book_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                display_name = VALUES(display_name),
+                timezone = VALUES(timezone),
+                locale = VALUES(locale),
+                default_currency = VALUES(default_currency),
+                date_format = VALUES(date_format),
+                week_start = VALUES(week_start),
+                default_account_book_id = VALUES(default_account_book_id)
+            "#,
+        )
+        .bind(user_id)
+        .bind(&update.display_name)
+        .bind(&update.timezone)
+        .bind(&update.locale)
+        .bind(&update.default_currency)
+        .bind(&update.date_format)
+        .bind(update.week_start)
+        .bind(update.default_account_book_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(chrono_tz::Asia::Shanghai)
+    }
+
+    // 用户所在时区的今天
+    pub fn today(&self) -> NaiveDate {
+        Utc::now().with_timezone(&self.tz()).date_naive()
+    }
+
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    pub fn week_start_day(&self) -> Weekday {
+        match self.week_start {
+            0 => Weekday::Sun,
+            6 => Weekday::Sat,
+            _ => Weekday::Mon,
+        }
+    }
+
+    // 包含指定日期的一周（按用户的每周起始日）
+    pub fn week_range(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let start_day = self.week_start_day().num_days_from_sunday() as i64;
+        let offset = (date.weekday().num_days_from_sunday() as i64 - start_day).rem_euclid(7);
+        let start = date - Duration::days(offset);
+        (start, start + Duration::days(6))
+    }
+
+    pub fn month_name(&self, month: u32) -> String {
+        const ZH: [&str; 12] = [
+            "一月", "二月", "三月", "四月", "五月", "六月",
+            "七月", "八月", "九月", "十月", "十一月", "十二月",
+        ];
+        const EN: [&str; 12] = [
+            "January", "February", "March", "April", "May", "June",
+            "July", "August", "September", "October", "November", "December",
+        ];
+
+        let names = if self.locale.starts_with("en") { &EN } else { &ZH };
+        names
+            .get(month.saturating_sub(1) as usize)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| "未知".to_string())
+    }
+
+    // 未设置昵称时显示邮箱
+    pub fn display_name_or<'a>(&'a self, fallback: &'a str) -> &'a str {
+        match self.display_name.as_deref() {
+            Some(name) if !name.is_empty() => name,
+            _ => fallback,
+        }
+    }
+}
+
+pub fn is_valid_timezone(timezone: &str) -> bool {
+    timezone.parse::<Tz>().is_ok()
+}
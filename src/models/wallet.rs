@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::models::WalletSnapshot;
+
+// 账户类型：(取值, 名称, 图标)
+pub const WALLET_TYPES: &[(&str, &str, &str)] = &[
+    ("cash", "现金", "cash-coin"),
+    ("bank", "银行卡", "bank"),
+    ("ewallet", "支付宝/微信", "phone"),
+    ("credit_card", "信用卡", "credit-card"),
+    ("loan", "贷款", "cash-stack"),
+    ("other", "其他", "wallet2"),
+];
+
+pub fn wallet_type_label(wallet_type: &str) -> &'static str {
+    WALLET_TYPES
+        .iter()
+        .find(|(value, _, _)| *value == wallet_type)
+        .map(|(_, label, _)| *label)
+        .unwrap_or("其他")
+}
+
+// 信用卡和贷款属于负债，余额通常为负数
+pub fn is_liability_type(wallet_type: &str) -> bool {
+    matches!(wallet_type, "credit_card" | "loan")
+}
+
+pub fn wallet_type_icon(wallet_type: &str) -> &'static str {
+    WALLET_TYPES
+        .iter()
+        .find(|(value, _, _)| *value == wallet_type)
+        .map(|(_, _, icon)| *icon)
+        .unwrap_or("wallet2")
+}
+
+// 账本内的资金账户（银行卡、支付宝、现金等），金额均为账本货币
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Wallet {
+    pub id: i64,
+    pub account_book_id: i64,
+    pub name: String,
+    #[serde(rename = "type")]
+    #[sqlx(rename = "type")]
+    pub wallet_type: String,
+    pub color: Option<String>,
+    pub opening_balance: Decimal,
+    pub sort_order: i32,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct WalletWithBalance {
+    #[sqlx(flatten)]
+    pub wallet: Wallet,
+    // 最近一次快照（没有快照时为期初余额）加上之后的收入和转入、减去支出和转出
+    pub balance: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWallet {
+    pub account_book_id: i64,
+    pub name: String,
+    pub wallet_type: String,
+    pub color: Option<String>,
+    pub opening_balance: Decimal,
+}
+
+impl Wallet {
+    pub async fn create(
+        pool: &crate::database::DbPool,
+        create_wallet: CreateWallet,
+    ) -> anyhow::Result<Wallet> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO wallets (account_book_id, name, `type`, color, opening_balance, sort_order)
+            SELECT ?, ?, ?, ?, ?, COALESCE(MAX(sort_order), 0) + 1
+            FROM wallets WHERE account_book_id = ?
+            "#,
+        )
+        .bind(create_wallet.account_book_id)
+        .bind(&create_wallet.name)
+        .bind(&create_wallet.wallet_type)
+        .bind(&create_wallet.color)
+        .bind(create_wallet.opening_balance)
+        .bind(create_wallet.account_book_id)
+        .execute(pool)
+        .await?;
+
+        let wallet_id = result.last_insert_id() as i64;
+        let wallet = Self::find_by_id(pool, wallet_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Failed to retrieve created wallet"))?;
+
+        Ok(wallet)
+    }
+
+    pub async fn find_by_id(
+        pool: &crate::database::DbPool,
+        id: i64,
+    ) -> anyhow::Result<Option<Wallet>> {
+        let wallet = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE id = ? AND is_active = TRUE")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(wallet)
+    }
+
+    pub async fn find_by_account_book(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<Vec<Wallet>> {
+        let wallets = sqlx::query_as::<_, Wallet>(
+            "SELECT * FROM wallets WHERE account_book_id = ? AND is_active = TRUE ORDER BY sort_order, id"
+        )
+        .bind(account_book_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(wallets)
+    }
+
+    // 账本内各账户的当前余额
+    pub async fn find_balances(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<Vec<WalletWithBalance>> {
+        let wallets = sqlx::query_as::<_, WalletWithBalance>(
+            r#"
+            SELECT w.*, COALESCE(s.balance, w.opening_balance) + COALESCE((
+                SELECT SUM(CASE WHEN t.`type` = 'income' THEN t.amount ELSE -t.amount END)
+                FROM transactions t
+                WHERE t.wallet_id = w.id AND (s.snapshot_date IS NULL OR t.transaction_date > s.snapshot_date)
+            ), 0) + COALESCE((
+                SELECT SUM(tr.to_amount) FROM transfers tr
+                WHERE tr.to_wallet_id = w.id AND (s.snapshot_date IS NULL OR tr.transfer_date > s.snapshot_date)
+            ), 0) - COALESCE((
+                SELECT SUM(tr.amount) FROM transfers tr
+                WHERE tr.from_wallet_id = w.id AND (s.snapshot_date IS NULL OR tr.transfer_date > s.snapshot_date)
+            ), 0) AS balance
+            FROM wallets w
+            LEFT JOIN wallet_snapshots s ON s.id = (
+                SELECT ls.id FROM wallet_snapshots ls
+                WHERE ls.wallet_id = w.id
+                ORDER BY ls.snapshot_date DESC
+                LIMIT 1
+            )
+            WHERE w.account_book_id = ? AND w.is_active = TRUE
+            ORDER BY w.sort_order, w.id
+            "#,
+        )
+        .bind(account_book_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(wallets)
+    }
+
+    // 截至指定日期（含）各账户每天的交易和转账合计：(账户ID, 日期, 合计)
+    pub async fn daily_net_changes(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        end_date: NaiveDate,
+    ) -> anyhow::Result<Vec<(i64, NaiveDate, Decimal)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT wallet_id, change_date, SUM(net_amount) FROM (
+                SELECT wallet_id, transaction_date AS change_date,
+                       CASE WHEN `type` = 'income' THEN amount ELSE -amount END AS net_amount
+                FROM transactions
+                WHERE account_book_id = ? AND wallet_id IS NOT NULL AND transaction_date <= ?
+                UNION ALL
+                SELECT from_wallet_id, transfer_date, -amount FROM transfers
+                WHERE from_account_book_id = ? AND transfer_date <= ?
+                UNION ALL
+                SELECT to_wallet_id, transfer_date, to_amount FROM transfers
+                WHERE to_account_book_id = ? AND transfer_date <= ?
+            ) changes
+            GROUP BY wallet_id, change_date
+            ORDER BY change_date
+            "#,
+        )
+        .bind(account_book_id)
+        .bind(end_date)
+        .bind(account_book_id)
+        .bind(end_date)
+        .bind(account_book_id)
+        .bind(end_date)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn update(
+        pool: &crate::database::DbPool,
+        id: i64,
+        name: &str,
+        wallet_type: &str,
+        color: Option<&str>,
+        opening_balance: Decimal,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE wallets SET name = ?, `type` = ?, color = ?, opening_balance = ? WHERE id = ?")
+            .bind(name)
+            .bind(wallet_type)
+            .bind(color)
+            .bind(opening_balance)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // 停用账户：已关联的交易记录保留账户信息，引用该账户的重复记账规则改为不指定账户
+    pub async fn delete(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("UPDATE wallets SET is_active = FALSE WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE recurring_transactions SET wallet_id = NULL WHERE wallet_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// 计算账户历史余额所需的数据：期初余额、余额快照和每日变动（交易和转账）。
+// 某日的日终余额 = 该日及之前最近一次快照（没有快照时为期初余额）+ 快照之后到该日的变动
+pub struct WalletLedger {
+    pub wallets: Vec<Wallet>,
+    snapshots: HashMap<i64, Vec<(NaiveDate, Decimal)>>,
+    changes: HashMap<i64, Vec<(NaiveDate, Decimal)>>,
+}
+
+impl WalletLedger {
+    // 加载账本内全部启用账户截至指定日期的数据
+    pub async fn load(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+        end_date: NaiveDate,
+    ) -> anyhow::Result<WalletLedger> {
+        let wallets = Wallet::find_by_account_book(pool, account_book_id).await?;
+
+        let mut snapshots: HashMap<i64, Vec<(NaiveDate, Decimal)>> = HashMap::new();
+        for snapshot in WalletSnapshot::find_by_account_book(pool, account_book_id).await? {
+            if snapshot.snapshot_date <= end_date {
+                snapshots.entry(snapshot.wallet_id).or_default().push((snapshot.snapshot_date, snapshot.balance));
+            }
+        }
+
+        let mut changes: HashMap<i64, Vec<(NaiveDate, Decimal)>> = HashMap::new();
+        for (wallet_id, date, amount) in Wallet::daily_net_changes(pool, account_book_id, end_date).await? {
+            changes.entry(wallet_id).or_default().push((date, amount));
+        }
+
+        Ok(WalletLedger { wallets, snapshots, changes })
+    }
+
+    // 账户在指定日期的日终余额
+    pub fn balance_at(&self, wallet: &Wallet, date: NaiveDate) -> Decimal {
+        let anchor = self.latest_snapshot(wallet.id, date);
+        let base = anchor.map(|(_, balance)| balance).unwrap_or(wallet.opening_balance);
+        let since = anchor.map(|(snapshot_date, _)| snapshot_date);
+
+        let change: Decimal = self
+            .changes
+            .get(&wallet.id)
+            .map(|changes| {
+                changes
+                    .iter()
+                    .filter(|(change_date, _)| *change_date <= date && since.is_none_or(|since| *change_date > since))
+                    .map(|(_, amount)| *amount)
+                    .sum()
+            })
+            .unwrap_or_default();
+
+        base + change
+    }
+
+    // 指定日期及之前最近一次快照：(日期, 余额)
+    pub fn latest_snapshot(&self, wallet_id: i64, date: NaiveDate) -> Option<(NaiveDate, Decimal)> {
+        self.snapshots
+            .get(&wallet_id)?
+            .iter()
+            .filter(|(snapshot_date, _)| *snapshot_date <= date)
+            .max_by_key(|(snapshot_date, _)| *snapshot_date)
+            .copied()
+    }
+
+    // 区间内余额可能变化的日期（有交易、转账或快照），含区间起止日
+    pub fn change_dates(&self, start_date: NaiveDate, end_date: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates: Vec<NaiveDate> = self
+            .changes
+            .values()
+            .chain(self.snapshots.values())
+            .flatten()
+            .map(|(date, _)| *date)
+            .filter(|date| *date >= start_date && *date <= end_date)
+            .collect();
+        dates.push(start_date);
+        dates.push(end_date);
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+}
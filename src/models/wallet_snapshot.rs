@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+// 资金账户在某日的实际余额（日终），之后的余额以最近一次快照为起点计算
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WalletSnapshot {
+    pub id: i64,
+    pub wallet_id: i64,
+    pub snapshot_date: NaiveDate,
+    pub balance: Decimal,
+    pub note: Option<String>,
+    pub created_by: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WalletSnapshot {
+    // 同一账户同一天只保留一条快照，重复记录时覆盖
+    pub async fn upsert(
+        pool: &crate::database::DbPool,
+        wallet_id: i64,
+        snapshot_date: NaiveDate,
+        balance: Decimal,
+        note: Option<&str>,
+        created_by: i64,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO wallet_snapshots (wallet_id, snapshot_date, balance, note, created_by)
+            VALUES (?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE balance = VALUES(balance), note = VALUES(note), created_by = VALUES(created_by)
+            "#,
+        )
+        .bind(wallet_id)
+        .bind(snapshot_date)
+        .bind(balance)
+        .bind(note)
+        .bind(created_by)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(
+        pool: &crate::database::DbPool,
+        id: i64,
+    ) -> anyhow::Result<Option<WalletSnapshot>> {
+        let snapshot = sqlx::query_as::<_, WalletSnapshot>("SELECT * FROM wallet_snapshots WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(snapshot)
+    }
+
+    pub async fn find_by_wallet(
+        pool: &crate::database::DbPool,
+        wallet_id: i64,
+    ) -> anyhow::Result<Vec<WalletSnapshot>> {
+        let snapshots = sqlx::query_as::<_, WalletSnapshot>(
+            "SELECT * FROM wallet_snapshots WHERE wallet_id = ? ORDER BY snapshot_date DESC"
+        )
+        .bind(wallet_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    // 账本内全部账户的快照，按日期升序
+    pub async fn find_by_account_book(
+        pool: &crate::database::DbPool,
+        account_book_id: i64,
+    ) -> anyhow::Result<Vec<WalletSnapshot>> {
+        let snapshots = sqlx::query_as::<_, WalletSnapshot>(
+            r#"
+            SELECT s.* FROM wallet_snapshots s
+            JOIN wallets w ON s.wallet_id = w.id
+            WHERE w.account_book_id = ?
+            ORDER BY s.snapshot_date, s.id
+            "#,
+        )
+        .bind(account_book_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    pub async fn delete(pool: &crate::database::DbPool, id: i64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM wallet_snapshots WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
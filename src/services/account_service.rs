@@ -1,6 +1,7 @@
-use crate::models::{AccountBook, CreateAccountBook};
+use crate::models::{AccountBook, CategorySeed, CategoryTemplate, CreateAccountBook};
 use crate::database::DbPool;
-use anyhow::Result;
+use crate::utils::find_builtin_category_template;
+use anyhow::{anyhow, Result};
 
 pub struct AccountService;
 
@@ -12,6 +13,7 @@ impl AccountService {
         description: Option<String>,
         currency: String,
         cycle_start_day: i32,
+        categories: Vec<CategorySeed>,
     ) -> Result<AccountBook> {
         let create_book = CreateAccountBook {
             user_id,
@@ -19,11 +21,50 @@ impl AccountService {
             description,
             currency,
             cycle_start_day,
+            categories,
         };
 
         AccountBook::create(pool, create_book).await
     }
 
+    // 解析新建账本时选择的分类模板：空值表示不预置分类，
+    // "builtin:<key>" 为内置模板，"user:<id>" 为用户保存的模板
+    pub async fn resolve_category_template(
+        pool: &DbPool,
+        user_id: i64,
+        selection: &str,
+    ) -> Result<Vec<CategorySeed>> {
+        if selection.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if let Some(key) = selection.strip_prefix("builtin:") {
+            let template = find_builtin_category_template(key)
+                .ok_or_else(|| anyhow!("分类模板不存在"))?;
+            let seeds = template
+                .categories
+                .iter()
+                .map(|(name, category_type, icon, color)| CategorySeed {
+                    name: name.to_string(),
+                    category_type: category_type.to_string(),
+                    icon: Some(icon.to_string()),
+                    color: Some(color.to_string()),
+                })
+                .collect();
+            return Ok(seeds);
+        }
+
+        let template_id = selection
+            .strip_prefix("user:")
+            .and_then(|id| id.parse::<i64>().ok())
+            .ok_or_else(|| anyhow!("分类模板不存在"))?;
+        let items = CategoryTemplate::find_items(pool, template_id, user_id)
+            .await?
+            .ok_or_else(|| anyhow!("分类模板不存在"))?;
+
+        Ok(items.into_iter().map(CategorySeed::from).collect())
+    }
+
     pub async fn get_user_account_books(
         pool: &DbPool,
         user_id: i64,
@@ -59,11 +100,11 @@ impl AccountService {
         ).await
     }
 
-    pub async fn delete_account_book(
+    pub async fn archive_account_book(
         pool: &DbPool,
         id: i64,
         user_id: i64,
     ) -> Result<()> {
-        AccountBook::delete(pool, id, user_id).await
+        AccountBook::archive(pool, id, user_id).await
     }
 }
\ No newline at end of file
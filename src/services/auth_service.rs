@@ -1,12 +1,44 @@
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use anyhow::{anyhow, Result};
 use uuid::Uuid;
 
-use crate::models::{User, CreateUser};
-use crate::utils::{hash_password, verify_password};
-use crate::services::EmailService;
+use crate::models::{
+    User, CreateUser, Session, CreateSession, RecoveryCode, ApiToken, CreateApiToken, UserIdentity,
+    AccountBook, AccountBookInvitation, AccountBookInvitationWithBook, AccountBookMember, BookRole,
+    CreateAccountBookInvitation, UserPreferences, API_TOKEN_SCOPE_READ, API_TOKEN_SCOPE_WRITE,
+};
+use crate::middleware::ClientInfo;
+use crate::utils::{
+    hash_password, verify_password, hash_token, generate_recovery_code, normalize_recovery_code,
+    generate_totp_secret, totp_provisioning_uri, verify_totp_code, generate_api_token,
+};
+use crate::services::{
+    EmailService, OidcIdentity, ThrottledError, ThrottlePolicy, ThrottleService, format_wait, email_key, LOGIN_ACCOUNT_POLICY, LOGIN_IP_POLICY,
+    RESEND_VERIFICATION_EMAIL_POLICY, RESEND_VERIFICATION_IP_POLICY, PASSWORD_RESET_EMAIL_POLICY,
+    PASSWORD_RESET_IP_POLICY, EMAIL_CHANGE_POLICY,
+};
+
+// 密码重置链接有效期（小时），与邮件中的说明保持一致
+const PASSWORD_RESET_TTL_HOURS: i64 = 24;
+
+// 邮箱验证（注册和修改邮箱）链接有效期（小时），与邮件中的说明保持一致
+const VERIFICATION_TTL_HOURS: i64 = 24;
+
+// 登录会话有效期（小时），JWT与会话记录同时过期
+const SESSION_TTL_HOURS: i64 = 24;
+
+// 密码验证通过后，完成两步验证的时限（分钟）
+const TWO_FACTOR_TTL_MINUTES: i64 = 5;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+// 申请注销后的冷静期（天），期间登录可撤销
+pub const ACCOUNT_DELETION_GRACE_DAYS: i64 = 14;
+
+// 账本邀请链接有效期（天），与邮件中的说明保持一致
+pub const INVITATION_TTL_DAYS: i64 = 7;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -14,6 +46,31 @@ pub struct Claims {
     pub email: String,
     pub exp: i64, // expiration time
     pub iat: i64, // issued at
+    pub sid: String, // session id
+}
+
+// 密码验证通过、等待两步验证时签发的临时令牌
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorClaims {
+    pub sub: i64,
+    pub exp: i64,
+    pub iat: i64,
+    pub purpose: String,
+}
+
+// 邮箱验证链接的用途
+#[derive(Debug)]
+pub enum EmailVerification {
+    // joined_books 为验证后自动加入的受邀账本名称
+    Registration { joined_books: Vec<String> },
+    EmailChanged { new_email: String },
+}
+
+// 验证链接已过期；注册验证可按邮箱重新发送，修改邮箱需重新申请
+#[derive(Debug, thiserror::Error)]
+#[error("验证链接已过期")]
+pub struct VerificationExpiredError {
+    pub resend_email: Option<String>,
 }
 
 #[derive(Clone)]
@@ -37,21 +94,22 @@ impl AuthService {
         password: String,
     ) -> Result<()> {
         // 检查邮箱是否已存在
-        if let Some(_) = User::find_by_email(pool, &email).await? {
+        if User::find_by_email(pool, &email).await?.is_some() {
             return Err(anyhow!("该邮箱已被注册"));
         }
 
         // 生成密码哈希
         let password_hash = hash_password(&password)?;
 
-        // 生成验证令牌
+        // 生成验证令牌，数据库只保存摘要
         let verification_token = Uuid::new_v4().to_string();
 
         // 创建用户
         let create_user = CreateUser {
             email: email.clone(),
             password_hash,
-            verification_token: verification_token.clone(),
+            verification_token: hash_token(&verification_token),
+            verification_expires_at: Utc::now() + Duration::hours(VERIFICATION_TTL_HOURS),
         };
 
         User::create(pool, create_user).await?;
@@ -74,32 +132,300 @@ impl AuthService {
         pool: &crate::database::DbPool,
         email: String,
         password: String,
-    ) -> Result<(User, String)> {
-        // 查找用户
-        let user = User::find_by_email(pool, &email)
-            .await?
-            .ok_or_else(|| anyhow!("邮箱或密码错误"))?;
+        client: &ClientInfo,
+    ) -> Result<User> {
+        self.check_login_allowed(pool, &email, client).await?;
 
-        // 验证密码
-        if !verify_password(&password, &user.password_hash)? {
-            return Err(anyhow!("邮箱或密码错误"));
-        }
+        // 查找用户（不存在的邮箱同样计入失败次数）
+        let user = match User::find_by_email(pool, &email).await? {
+            Some(user) if verify_password(&password, &user.password_hash)? => user,
+            _ => {
+                self.record_login_failure(pool, &email, client).await;
+                return Err(anyhow!("邮箱或密码错误"));
+            }
+        };
 
         // 检查邮箱是否已验证
         if !user.is_verified {
             return Err(anyhow!("请先验证您的邮箱"));
         }
 
-        // 生成JWT
-        let token = self.generate_token(&user)?;
+        // 两步验证通过前不清除失败记录
+        if !user.totp_enabled {
+            self.clear_login_failures(pool, &email).await;
+        }
 
-        Ok((user, token))
+        Ok(user)
     }
 
-    pub async fn verify_email(&self, pool: &crate::database::DbPool, token: &str) -> Result<()> {
-        if !User::verify_email(pool, token).await? {
+    // 外部身份登录：优先按已关联的sub查找，否则按已验证的邮箱关联到现有用户
+    pub async fn login_with_oidc(
+        &self,
+        pool: &crate::database::DbPool,
+        identity: &OidcIdentity,
+        allow_signup: bool,
+    ) -> Result<User> {
+        if let Some(linked) = UserIdentity::find_by_subject(pool, &identity.provider, &identity.subject).await? {
+            UserIdentity::touch(pool, linked.id, identity.email.as_deref()).await?;
+            return self.get_current_user(pool, linked.user_id).await;
+        }
+
+        let email = identity.email.as_deref()
+            .ok_or_else(|| anyhow!("身份提供方未返回邮箱地址"))?;
+        if !identity.email_verified {
+            return Err(anyhow!("该邮箱未经身份提供方验证，无法关联账户"));
+        }
+
+        let user = match User::find_by_email(pool, email).await? {
+            Some(user) => user,
+            None if allow_signup => {
+                // 自动注册的账户没有可用密码，需要时可通过找回密码设置
+                let password_hash = hash_password(&Uuid::new_v4().to_string())?;
+                User::create(pool, CreateUser {
+                    email: email.to_string(),
+                    password_hash,
+                    verification_token: hash_token(&Uuid::new_v4().to_string()),
+                    verification_expires_at: Utc::now(),
+                }).await?
+            }
+            None => return Err(anyhow!("该邮箱尚未注册账户")),
+        };
+
+        // 未验证的账户可能是他人抢先注册的，关联前作废其密码和已有的登录凭据，
+        // 之后如需密码登录可通过找回密码设置
+        if !user.is_verified {
+            let password_hash = hash_password(&Uuid::new_v4().to_string())?;
+            if User::claim_unverified(pool, user.id, &password_hash).await? {
+                Session::delete_all_for_user(pool, user.id).await?;
+                ApiToken::delete_all_for_user(pool, user.id).await?;
+                RecoveryCode::delete_for_user(pool, user.id).await?;
+            }
+            self.accept_pending_invitations(pool, &user).await;
+        }
+        UserIdentity::create(pool, user.id, &identity.provider, &identity.subject, Some(email)).await?;
+
+        self.get_current_user(pool, user.id).await
+    }
+
+    // 账户或IP处于锁定期时拒绝登录
+    pub async fn check_login_allowed(
+        &self,
+        pool: &crate::database::DbPool,
+        email: &str,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        ThrottleService::check(pool, LOGIN_ACCOUNT_POLICY, &email_key(email)).await?;
+        if let Some(ip_address) = &client.ip_address {
+            ThrottleService::check(pool, LOGIN_IP_POLICY, ip_address).await?;
+        }
+        Ok(())
+    }
+
+    // 记录登录失败（密码或两步验证码错误），账户被锁定时通知本人
+    pub async fn record_login_failure(
+        &self,
+        pool: &crate::database::DbPool,
+        email: &str,
+        client: &ClientInfo,
+    ) {
+        if let Some(ip_address) = &client.ip_address {
+            match ThrottleService::record(pool, LOGIN_IP_POLICY, ip_address).await {
+                Err(e) if !e.is::<ThrottledError>() => tracing::warn!("Failed to record login failure for ip: {}", e),
+                _ => {}
+            }
+        }
+
+        // 并发请求已经触发锁定时不再重复通知
+        let lockout = match ThrottleService::record(pool, LOGIN_ACCOUNT_POLICY, &email_key(email)).await {
+            Ok(lockout) => lockout,
+            Err(e) if e.is::<ThrottledError>() => return,
+            Err(e) => {
+                tracing::warn!("Failed to record login failure for account: {}", e);
+                return;
+            }
+        };
+
+        if let Some(lockout_seconds) = lockout {
+            if let Ok(Some(user)) = User::find_by_email(pool, email).await {
+                let ip_address = client.ip_address.as_deref().unwrap_or("未知");
+                if let Err(e) = self
+                    .email_service
+                    .send_account_locked_email(&user.email, &format_wait(lockout_seconds), ip_address)
+                    .await
+                {
+                    tracing::warn!("Failed to send account locked email: {}", e);
+                }
+            }
+        }
+    }
+
+    pub async fn clear_login_failures(&self, pool: &crate::database::DbPool, email: &str) {
+        if let Err(e) = ThrottleService::clear(pool, LOGIN_ACCOUNT_POLICY, &email_key(email)).await {
+            tracing::warn!("Failed to clear login failures: {}", e);
+        }
+    }
+
+    // 发送邮件类请求按邮箱和IP分别计数
+    async fn throttle_email_request(
+        &self,
+        pool: &crate::database::DbPool,
+        email_policy: ThrottlePolicy,
+        ip_policy: ThrottlePolicy,
+        email: &str,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        if let Some(ip_address) = &client.ip_address {
+            ThrottleService::hit(pool, ip_policy, ip_address).await?;
+        }
+        ThrottleService::hit(pool, email_policy, &email_key(email)).await
+    }
+
+    // 为已通过认证的用户创建服务端会话并签发JWT
+    pub async fn create_session(
+        &self,
+        pool: &crate::database::DbPool,
+        user: &User,
+        client: &ClientInfo,
+    ) -> Result<String> {
+        // 顺便清理过期会话和限流记录
+        if let Err(e) = Session::delete_expired(pool).await {
+            tracing::warn!("Failed to clean expired sessions: {}", e);
+        }
+        if let Err(e) = ThrottleService::clean_stale(pool).await {
+            tracing::warn!("Failed to clean stale throttles: {}", e);
+        }
+
+        let session_id = Uuid::new_v4().to_string();
+        let create_session = CreateSession {
+            id: session_id.clone(),
+            user_id: user.id,
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.clone(),
+            expires_at: Utc::now() + Duration::hours(SESSION_TTL_HOURS),
+        };
+        Session::create(pool, create_session).await?;
+
+        self.generate_token(user, &session_id)
+    }
+
+    pub async fn verify_email(&self, pool: &crate::database::DbPool, token: &str) -> Result<EmailVerification> {
+        let token_hash = hash_token(token);
+        let user = User::find_by_verification_token(pool, &token_hash)
+            .await?
+            .ok_or_else(|| anyhow!("无效的验证令牌"))?;
+
+        if user.verification_expires_at.is_none_or(|expires_at| expires_at <= Utc::now()) {
+            return Err(VerificationExpiredError {
+                resend_email: user.pending_email.is_none().then(|| user.email.clone()),
+            }.into());
+        }
+
+        // 新邮箱在等待确认期间被其他账户注册
+        if let Some(pending_email) = &user.pending_email {
+            if User::find_by_email(pool, pending_email).await?.is_some() {
+                User::cancel_pending_email(pool, user.id).await?;
+                return Err(anyhow!("该邮箱已被其他账户使用"));
+            }
+        }
+
+        if !User::verify_email(pool, &token_hash).await? {
             return Err(anyhow!("无效的验证令牌"));
         }
+
+        let Some(new_email) = user.pending_email else {
+            let joined_books = self.accept_pending_invitations(pool, &user).await;
+            return Ok(EmailVerification::Registration { joined_books });
+        };
+
+        // 通知原邮箱，便于发现非本人操作
+        if let Err(e) = self.email_service
+            .send_email_changed_notice(&user.email, &new_email)
+            .await
+        {
+            tracing::warn!("Failed to send email change notice to {}: {}", user.email, e);
+        }
+
+        Ok(EmailVerification::EmailChanged { new_email })
+    }
+
+    // 修改邮箱：向新邮箱发送确认链接，确认前仍使用原邮箱登录
+    pub async fn request_email_change(
+        &self,
+        pool: &crate::database::DbPool,
+        user: &User,
+        new_email: &str,
+        password: &str,
+    ) -> Result<()> {
+        if !verify_password(password, &user.password_hash)? {
+            return Err(anyhow!("密码错误"));
+        }
+
+        let new_email = new_email.trim().to_lowercase();
+        if new_email == user.email.to_lowercase() {
+            return Err(anyhow!("新邮箱与当前邮箱相同"));
+        }
+        if User::find_by_email(pool, &new_email).await?.is_some() {
+            return Err(anyhow!("该邮箱已被其他账户使用"));
+        }
+
+        ThrottleService::hit(pool, EMAIL_CHANGE_POLICY, &user.id.to_string()).await?;
+
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + Duration::hours(VERIFICATION_TTL_HOURS);
+        User::set_pending_email(pool, user.id, &new_email).await?;
+        User::update_verification_token(pool, &user.email, &hash_token(&token), expires_at).await?;
+
+        self.email_service
+            .send_email_change_verification(&new_email, &token)
+            .await?;
+
+        Ok(())
+    }
+
+    // 注销账户：再次确认身份后进入冷静期，同时注销所有会话和访问令牌
+    pub async fn request_account_deletion(
+        &self,
+        pool: &crate::database::DbPool,
+        user: &User,
+        password: &str,
+        code: &str,
+    ) -> Result<DateTime<Utc>> {
+        if !verify_password(password, &user.password_hash)? {
+            return Err(anyhow!("密码错误"));
+        }
+
+        if user.totp_enabled && !self.verify_second_factor(pool, user, code).await? {
+            return Err(anyhow!("验证码错误"));
+        }
+
+        let scheduled_at = Utc::now() + Duration::days(ACCOUNT_DELETION_GRACE_DAYS);
+        User::schedule_deletion(pool, user.id, scheduled_at).await?;
+        Session::delete_all_for_user(pool, user.id).await?;
+        ApiToken::delete_all_for_user(pool, user.id).await?;
+
+        if let Err(e) = self.email_service
+            .send_account_deletion_scheduled(&user.email, &scheduled_at.format("%Y-%m-%d %H:%M UTC").to_string())
+            .await
+        {
+            tracing::warn!("Failed to send account deletion notice to {}: {}", user.email, e);
+        }
+
+        Ok(scheduled_at)
+    }
+
+    // 彻底删除冷静期已过的账户，由后台任务定期调用
+    pub async fn purge_scheduled_deletions(&self, pool: &crate::database::DbPool) -> Result<()> {
+        for user in User::find_due_for_deletion(pool).await? {
+            if !User::hard_delete(pool, user.id).await? {
+                continue;
+            }
+            tracing::info!("Deleted account {} after grace period", user.id);
+
+            if let Err(e) = self.email_service.send_account_deleted_notice(&user.email).await {
+                tracing::warn!("Failed to send account deleted notice to {}: {}", user.email, e);
+            }
+        }
+
         Ok(())
     }
 
@@ -107,7 +433,16 @@ impl AuthService {
         &self,
         pool: &crate::database::DbPool,
         email: String,
+        client: &ClientInfo,
     ) -> Result<()> {
+        self.throttle_email_request(
+            pool,
+            RESEND_VERIFICATION_EMAIL_POLICY,
+            RESEND_VERIFICATION_IP_POLICY,
+            &email,
+            client,
+        ).await?;
+
         let user = User::find_by_email(pool, &email)
             .await?
             .ok_or_else(|| anyhow!("用户不存在"))?;
@@ -116,9 +451,10 @@ impl AuthService {
             return Err(anyhow!("邮箱已验证"));
         }
 
-        // 生成新的验证令牌
+        // 生成新的验证令牌，旧链接随之失效
         let new_token = Uuid::new_v4().to_string();
-        User::update_verification_token(pool, &email, &new_token).await?;
+        let expires_at = Utc::now() + Duration::hours(VERIFICATION_TTL_HOURS);
+        User::update_verification_token(pool, &email, &hash_token(&new_token), expires_at).await?;
 
         // 发送验证邮件
         self.email_service
@@ -128,15 +464,374 @@ impl AuthService {
         Ok(())
     }
 
-    pub fn generate_token(&self, user: &User) -> Result<String> {
+    pub async fn request_password_reset(
+        &self,
+        pool: &crate::database::DbPool,
+        email: String,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        self.throttle_email_request(
+            pool,
+            PASSWORD_RESET_EMAIL_POLICY,
+            PASSWORD_RESET_IP_POLICY,
+            &email,
+            client,
+        ).await?;
+
+        // 邮箱不存在时静默返回，避免泄露注册信息
+        let user = match User::find_by_email(pool, &email).await? {
+            Some(user) => user,
+            None => return Ok(()),
+        };
+
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + Duration::hours(PASSWORD_RESET_TTL_HOURS);
+        User::set_password_reset_token(pool, user.id, &hash_token(&token), expires_at).await?;
+
+        let username = email.split('@').next().unwrap_or(&email);
+        self.email_service
+            .send_password_reset_email(&email, username, &token)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn validate_password_reset_token(
+        &self,
+        pool: &crate::database::DbPool,
+        token: &str,
+    ) -> Result<User> {
+        User::find_by_password_reset_token(pool, &hash_token(token))
+            .await?
+            .ok_or_else(|| anyhow!("重置链接无效或已过期"))
+    }
+
+    pub async fn reset_password(
+        &self,
+        pool: &crate::database::DbPool,
+        token: &str,
+        password: String,
+    ) -> Result<()> {
+        let user = self.validate_password_reset_token(pool, token).await?;
+        let password_hash = hash_password(&password)?;
+
+        if !User::reset_password(pool, &hash_token(token), &password_hash).await? {
+            return Err(anyhow!("重置链接无效或已过期"));
+        }
+
+        // 密码修改后注销所有设备
+        Session::delete_all_for_user(pool, user.id).await?;
+
+        Ok(())
+    }
+
+    // 邀请他人加入账本，重复邀请同一邮箱时旧链接作废
+    pub async fn invite_to_account_book(
+        &self,
+        pool: &crate::database::DbPool,
+        account_book: &AccountBook,
+        inviter: &User,
+        email: &str,
+        role: BookRole,
+    ) -> Result<()> {
+        let email = email.trim().to_lowercase();
+
+        if let Some(existing) = User::find_by_email(pool, &email).await? {
+            if AccountBookMember::find_role(pool, account_book.id, existing.id).await?.is_some() {
+                return Err(anyhow!("该用户已经是账本成员"));
+            }
+        }
+
+        let token = Uuid::new_v4().to_string();
+        AccountBookInvitation::create(pool, CreateAccountBookInvitation {
+            account_book_id: account_book.id,
+            email: email.clone(),
+            role,
+            token_hash: hash_token(&token),
+            invited_by: inviter.id,
+            expires_at: Utc::now() + Duration::days(INVITATION_TTL_DAYS),
+        }).await?;
+
+        let preferences = UserPreferences::load(pool, inviter.id).await;
+        self.email_service
+            .send_account_book_invitation(
+                &email,
+                preferences.display_name_or(&inviter.email),
+                &account_book.name,
+                role.label(),
+                &token,
+                INVITATION_TTL_DAYS,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_invitation(
+        &self,
+        pool: &crate::database::DbPool,
+        token: &str,
+    ) -> Result<AccountBookInvitationWithBook> {
+        AccountBookInvitation::find_by_token_hash(pool, &hash_token(token))
+            .await?
+            .ok_or_else(|| anyhow!("邀请链接无效或账本已被删除"))
+    }
+
+    // 登录用户通过邀请链接加入账本，返回账本ID。邀请只能由收件邮箱对应的账户接受
+    pub async fn accept_invitation(
+        &self,
+        pool: &crate::database::DbPool,
+        token: &str,
+        user_id: i64,
+        user_email: &str,
+    ) -> Result<i64> {
+        let found = self.find_invitation(pool, token).await?;
+        let invitation = found.invitation;
+
+        if !invitation.email.eq_ignore_ascii_case(user_email) {
+            return Err(anyhow!("这份邀请是发给 {} 的，请使用该邮箱的账户登录后接受", invitation.email));
+        }
+        if invitation.accepted_at.is_some() {
+            return Err(anyhow!("该邀请已被使用"));
+        }
+        if !invitation.is_pending() {
+            return Err(anyhow!("邀请链接已过期，请联系账本所有者重新邀请"));
+        }
+        if !AccountBookInvitation::accept(pool, &invitation, user_id).await? {
+            return Err(anyhow!("该邀请已被使用或已过期"));
+        }
+
+        User::update_last_selected_account_book(pool, user_id, Some(invitation.account_book_id)).await?;
+
+        Ok(invitation.account_book_id)
+    }
+
+    // 新用户验证邮箱后自动接受发给该邮箱的全部邀请，并把最近加入的账本设为当前账本
+    async fn accept_pending_invitations(&self, pool: &crate::database::DbPool, user: &User) -> Vec<String> {
+        let invitations = match AccountBookInvitation::find_pending_by_email(pool, &user.email).await {
+            Ok(invitations) => invitations,
+            Err(e) => {
+                tracing::warn!("Failed to load invitations for {}: {}", user.email, e);
+                return Vec::new();
+            }
+        };
+
+        let mut joined_books = Vec::new();
+        let mut last_book_id = None;
+        for found in invitations {
+            match AccountBookInvitation::accept(pool, &found.invitation, user.id).await {
+                Ok(true) => {
+                    last_book_id = Some(found.invitation.account_book_id);
+                    joined_books.push(found.book_name);
+                }
+                Ok(false) => {}
+                Err(e) => tracing::warn!("Failed to accept invitation {}: {}", found.invitation.id, e),
+            }
+        }
+
+        if last_book_id.is_some() {
+            if let Err(e) = User::update_last_selected_account_book(pool, user.id, last_book_id).await {
+                tracing::warn!("Failed to select invited account book: {}", e);
+            }
+        }
+
+        joined_books
+    }
+
+    pub fn generate_two_factor_token(&self, user: &User) -> Result<String> {
         let now = Utc::now();
-        let exp = now + Duration::hours(24); // 24小时过期
+        let claims = TwoFactorClaims {
+            sub: user.id,
+            exp: (now + Duration::minutes(TWO_FACTOR_TTL_MINUTES)).timestamp(),
+            iat: now.timestamp(),
+            purpose: "2fa".to_string(),
+        };
+
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_ref()),
+        )?;
+
+        Ok(token)
+    }
+
+    pub async fn verify_two_factor_token(
+        &self,
+        pool: &crate::database::DbPool,
+        token: &str,
+    ) -> Result<User> {
+        let claims = decode::<TwoFactorClaims>(
+            token,
+            &DecodingKey::from_secret(self.jwt_secret.as_ref()),
+            &Validation::default(),
+        )
+        .map_err(|_| anyhow!("验证已超时，请重新登录"))?
+        .claims;
+
+        if claims.purpose != "2fa" {
+            return Err(anyhow!("验证已超时，请重新登录"));
+        }
+
+        self.get_current_user(pool, claims.sub).await
+    }
+
+    // 校验动态验证码，或消耗一个恢复码
+    pub async fn verify_second_factor(
+        &self,
+        pool: &crate::database::DbPool,
+        user: &User,
+        code: &str,
+    ) -> Result<bool> {
+        let secret = match (&user.totp_secret, user.totp_enabled) {
+            (Some(secret), true) => secret,
+            _ => return Ok(false),
+        };
+
+        if let Some(step) = verify_totp_code(secret, &user.email, code)? {
+            return User::use_totp_step(pool, user.id, step).await;
+        }
+
+        let normalized = normalize_recovery_code(code);
+        if normalized.len() != 12 {
+            return Ok(false);
+        }
+        RecoveryCode::consume(pool, user.id, &hash_token(&normalized)).await
+    }
+
+    // 生成新的TOTP密钥，返回密钥和otpauth链接
+    pub async fn begin_totp_enrollment(
+        &self,
+        pool: &crate::database::DbPool,
+        user: &User,
+    ) -> Result<(String, String)> {
+        if user.totp_enabled {
+            return Err(anyhow!("两步验证已启用"));
+        }
+
+        let secret = generate_totp_secret();
+        let uri = totp_provisioning_uri(&secret, &user.email)?;
+        User::set_totp_secret(pool, user.id, &secret).await?;
+
+        Ok((secret, uri))
+    }
+
+    // 用第一个动态验证码确认绑定，返回明文恢复码（仅展示一次）
+    pub async fn confirm_totp_enrollment(
+        &self,
+        pool: &crate::database::DbPool,
+        user: &User,
+        code: &str,
+    ) -> Result<Vec<String>> {
+        let secret = user.totp_secret.as_deref()
+            .ok_or_else(|| anyhow!("请先生成两步验证密钥"))?;
+
+        let step = verify_totp_code(secret, &user.email, code)?
+            .ok_or_else(|| anyhow!("验证码错误，请确认手机时间准确后重试"))?;
+        if !User::use_totp_step(pool, user.id, step).await? {
+            return Err(anyhow!("验证码已使用过，请等待下一个验证码"));
+        }
+
+        User::enable_totp(pool, user.id).await?;
+        self.regenerate_recovery_codes(pool, user.id).await
+    }
+
+    pub async fn regenerate_recovery_codes(
+        &self,
+        pool: &crate::database::DbPool,
+        user_id: i64,
+    ) -> Result<Vec<String>> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+        let code_hashes: Vec<String> = codes
+            .iter()
+            .map(|code| hash_token(&normalize_recovery_code(code)))
+            .collect();
+
+        RecoveryCode::replace_for_user(pool, user_id, &code_hashes).await?;
+
+        Ok(codes)
+    }
+
+    pub async fn disable_totp(
+        &self,
+        pool: &crate::database::DbPool,
+        user: &User,
+        password: &str,
+        code: &str,
+    ) -> Result<()> {
+        if !verify_password(password, &user.password_hash)? {
+            return Err(anyhow!("密码错误"));
+        }
+
+        if !self.verify_second_factor(pool, user, code).await? {
+            return Err(anyhow!("验证码错误"));
+        }
+
+        User::disable_totp(pool, user.id).await?;
+        RecoveryCode::delete_for_user(pool, user.id).await?;
+
+        Ok(())
+    }
+
+    // 创建个人访问令牌，明文令牌只在此时返回一次
+    pub async fn create_api_token(
+        &self,
+        pool: &crate::database::DbPool,
+        user_id: i64,
+        name: &str,
+        scope: &str,
+        account_book_id: Option<i64>,
+        expires_in_days: Option<i64>,
+    ) -> Result<(ApiToken, String)> {
+        if scope != API_TOKEN_SCOPE_READ && scope != API_TOKEN_SCOPE_WRITE {
+            return Err(anyhow!("权限范围无效"));
+        }
+
+        let token = generate_api_token();
+        let api_token = ApiToken::create(pool, CreateApiToken {
+            user_id,
+            name: name.trim().to_string(),
+            token_prefix: token.chars().take(12).collect(),
+            token_hash: hash_token(&token),
+            scope: scope.to_string(),
+            account_book_id,
+            expires_at: expires_in_days.map(|days| Utc::now() + Duration::days(days)),
+        }).await?;
+
+        Ok((api_token, token))
+    }
+
+    // 校验个人访问令牌并返回所属用户
+    pub async fn authenticate_api_token(
+        &self,
+        pool: &crate::database::DbPool,
+        token: &str,
+    ) -> Result<(User, ApiToken)> {
+        let api_token = ApiToken::find_active_by_hash(pool, &hash_token(token))
+            .await?
+            .ok_or_else(|| anyhow!("访问令牌无效或已过期"))?;
+
+        let user = self.get_current_user(pool, api_token.user_id).await?;
+
+        if let Err(e) = ApiToken::touch(pool, api_token.id).await {
+            tracing::warn!("Failed to update api token last_used_at: {}", e);
+        }
+
+        Ok((user, api_token))
+    }
+
+    pub fn generate_token(&self, user: &User, session_id: &str) -> Result<String> {
+        let now = Utc::now();
+        let exp = now + Duration::hours(SESSION_TTL_HOURS);
 
         let claims = Claims {
             sub: user.id,
             email: user.email.clone(),
             exp: exp.timestamp(),
             iat: now.timestamp(),
+            sid: session_id.to_string(),
         };
 
         let token = encode(
@@ -163,4 +858,11 @@ impl AuthService {
             .await?
             .ok_or_else(|| anyhow!("用户不存在"))
     }
+
+    // 修改密码之前签发的令牌一律失效
+    pub fn is_token_revoked(&self, claims: &Claims, user: &User) -> bool {
+        user.password_changed_at
+            .map(|changed_at| claims.iat < changed_at.timestamp())
+            .unwrap_or(false)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,303 @@
+use std::io::{Cursor, Write};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::database::DbPool;
+use crate::models::{AccountBook, ApiToken, Category, CategoryTemplate, CategoryTemplateItem, ExchangeRate, RecurringOccurrence, RecurringTransaction, Session, Tag, Transaction, TransactionSplit, TransactionTag, Transfer, User, UserIdentity, UserPreferences, Wallet, WalletSnapshot};
+
+// 导出的用户信息，不包含密码哈希、各类令牌和两步验证密钥
+#[derive(Debug, Serialize)]
+struct ExportedUser {
+    id: i64,
+    email: String,
+    is_verified: bool,
+    pending_email: Option<String>,
+    totp_enabled: bool,
+    deletion_scheduled_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    preferences: UserPreferences,
+    identities: Vec<UserIdentity>,
+    sessions: Vec<ExportedSession>,
+    api_tokens: Vec<ExportedApiToken>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedSession {
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedApiToken {
+    name: String,
+    token_prefix: String,
+    scope: String,
+    account_book_id: Option<i64>,
+    last_used_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+pub struct DataExportService;
+
+impl DataExportService {
+    // 打包用户的全部数据：每类数据各一份JSON和CSV
+    pub async fn build_archive(pool: &DbPool, user_id: i64) -> Result<Vec<u8>> {
+        let user = User::find_by_id(pool, user_id)
+            .await?
+            .ok_or_else(|| anyhow!("用户不存在"))?;
+
+        let preferences = UserPreferences::find_by_user(pool, user_id).await?;
+        let identities = UserIdentity::find_by_user(pool, user_id).await?;
+        let sessions = Session::find_by_user(pool, user_id)
+            .await?
+            .into_iter()
+            .map(|session| ExportedSession {
+                user_agent: session.user_agent,
+                ip_address: session.ip_address,
+                created_at: session.created_at,
+                last_seen_at: session.last_seen_at,
+                expires_at: session.expires_at,
+            })
+            .collect();
+        let api_tokens = ApiToken::find_by_user(pool, user_id)
+            .await?
+            .into_iter()
+            .map(|token| ExportedApiToken {
+                name: token.name,
+                token_prefix: token.token_prefix,
+                scope: token.scope,
+                account_book_id: token.account_book_id,
+                last_used_at: token.last_used_at,
+                expires_at: token.expires_at,
+                created_at: token.created_at,
+            })
+            .collect();
+
+        let exported_user = ExportedUser {
+            id: user.id,
+            email: user.email,
+            is_verified: user.is_verified,
+            pending_email: user.pending_email,
+            totp_enabled: user.totp_enabled,
+            deletion_scheduled_at: user.deletion_scheduled_at,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            preferences,
+            identities,
+            sessions,
+            api_tokens,
+        };
+
+        // 用户参与的全部账本，已删除（停用）的账本同样属于用户数据
+        let account_books = sqlx::query_as::<_, AccountBook>(
+            r#"
+            SELECT ab.* FROM account_books ab
+            INNER JOIN account_book_members m ON m.account_book_id = ab.id
+            WHERE m.user_id = ?
+            ORDER BY ab.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let categories = sqlx::query_as::<_, Category>(
+            r#"
+            SELECT c.* FROM categories c
+            INNER JOIN account_book_members m ON m.account_book_id = c.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY c.account_book_id, c.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let wallets = sqlx::query_as::<_, Wallet>(
+            r#"
+            SELECT w.* FROM wallets w
+            INNER JOIN account_book_members m ON m.account_book_id = w.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY w.account_book_id, w.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let wallet_snapshots = sqlx::query_as::<_, WalletSnapshot>(
+            r#"
+            SELECT s.* FROM wallet_snapshots s
+            INNER JOIN wallets w ON w.id = s.wallet_id
+            INNER JOIN account_book_members m ON m.account_book_id = w.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY s.wallet_id, s.snapshot_date
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let transactions = sqlx::query_as::<_, Transaction>(
+            r#"
+            SELECT t.* FROM transactions t
+            INNER JOIN account_book_members m ON m.account_book_id = t.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY t.account_book_id, t.transaction_date, t.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let tags = sqlx::query_as::<_, Tag>(
+            r#"
+            SELECT g.* FROM tags g
+            INNER JOIN account_book_members m ON m.account_book_id = g.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY g.account_book_id, g.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let transaction_tags = sqlx::query_as::<_, TransactionTag>(
+            r#"
+            SELECT tt.* FROM transaction_tags tt
+            INNER JOIN tags g ON g.id = tt.tag_id
+            INNER JOIN account_book_members m ON m.account_book_id = g.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY tt.transaction_id, tt.tag_id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let transaction_splits = sqlx::query_as::<_, TransactionSplit>(
+            r#"
+            SELECT ts.* FROM transaction_splits ts
+            INNER JOIN transactions t ON t.id = ts.transaction_id
+            INNER JOIN account_book_members m ON m.account_book_id = t.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY ts.transaction_id, ts.sort_order, ts.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        // 转出或转入用户参与的账本的转账
+        let transfers = sqlx::query_as::<_, Transfer>(
+            r#"
+            SELECT tr.* FROM transfers tr
+            WHERE EXISTS (
+                SELECT 1 FROM account_book_members m
+                WHERE m.user_id = ? AND m.account_book_id IN (tr.from_account_book_id, tr.to_account_book_id)
+            )
+            ORDER BY tr.transfer_date, tr.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let recurring_transactions = sqlx::query_as::<_, RecurringTransaction>(
+            r#"
+            SELECT r.* FROM recurring_transactions r
+            INNER JOIN account_book_members m ON m.account_book_id = r.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY r.account_book_id, r.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let recurring_occurrences = sqlx::query_as::<_, RecurringOccurrence>(
+            r#"
+            SELECT o.* FROM recurring_occurrences o
+            INNER JOIN recurring_transactions r ON r.id = o.recurring_transaction_id
+            INNER JOIN account_book_members m ON m.account_book_id = r.account_book_id
+            WHERE m.user_id = ?
+            ORDER BY o.recurring_transaction_id, o.sequence
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let category_templates = CategoryTemplate::find_by_user(pool, user_id).await?;
+        let category_template_items = sqlx::query_as::<_, CategoryTemplateItem>(
+            r#"
+            SELECT i.* FROM category_template_items i
+            INNER JOIN category_templates t ON t.id = i.template_id
+            WHERE t.user_id = ?
+            ORDER BY i.template_id, i.sort_order, i.id
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let exchange_rates = sqlx::query_as::<_, ExchangeRate>(
+            "SELECT * FROM exchange_rates WHERE user_id = ? ORDER BY rate_date, base_currency, quote_currency"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        zip.start_file("user.json", options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&exported_user)?)?;
+
+        write_table(&mut zip, options, "account_books", &account_books)?;
+        write_table(&mut zip, options, "categories", &categories)?;
+        write_table(&mut zip, options, "wallets", &wallets)?;
+        write_table(&mut zip, options, "wallet_snapshots", &wallet_snapshots)?;
+        write_table(&mut zip, options, "transactions", &transactions)?;
+        write_table(&mut zip, options, "tags", &tags)?;
+        write_table(&mut zip, options, "transaction_tags", &transaction_tags)?;
+        write_table(&mut zip, options, "transaction_splits", &transaction_splits)?;
+        write_table(&mut zip, options, "transfers", &transfers)?;
+        write_table(&mut zip, options, "recurring_transactions", &recurring_transactions)?;
+        write_table(&mut zip, options, "recurring_occurrences", &recurring_occurrences)?;
+        write_table(&mut zip, options, "category_templates", &category_templates)?;
+        write_table(&mut zip, options, "category_template_items", &category_template_items)?;
+        write_table(&mut zip, options, "exchange_rates", &exchange_rates)?;
+
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+fn write_table<T: Serialize>(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    options: SimpleFileOptions,
+    name: &str,
+    rows: &[T],
+) -> Result<()> {
+    zip.start_file(format!("{}.json", name), options)?;
+    zip.write_all(&serde_json::to_vec_pretty(rows)?)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    zip.start_file(format!("{}.csv", name), options)?;
+    // 加BOM，方便Excel正确识别UTF-8中文
+    zip.write_all(b"\xEF\xBB\xBF")?;
+    zip.write_all(&writer.into_inner()?)?;
+
+    Ok(())
+}
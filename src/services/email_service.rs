@@ -78,7 +78,7 @@ impl EmailService {
                             <a href="{}">{}</a>
                         </p>
                         
-                        <p>如果您没有注册过此账户，请忽略这封邮件。</p>
+                        <p>此链接将在24小时后失效，过期后可在验证页面重新发送。如果您没有注册过此账户，请忽略这封邮件。</p>
                         
                         <hr style="border: none; border-top: 1px solid #eee; margin: 30px 0;">
                         <p style="color: #666; font-size: 12px;">
@@ -149,4 +149,297 @@ impl EmailService {
         self.mailer.send(&email)?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub async fn send_account_locked_email(&self, to_email: &str, wait: &str, ip_address: &str) -> Result<()> {
+        let reset_url = format!("{}/auth/forgot-password", self.app_url);
+
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to_email.parse()?)
+            .subject("您的账户已被临时锁定")
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                r#"
+                <!DOCTYPE html>
+                <html>
+                <head>
+                    <meta charset="UTF-8">
+                    <title>账户临时锁定</title>
+                </head>
+                <body style="font-family: Arial, sans-serif; line-height: 1.6; color: #333;">
+                    <div style="max-width: 600px; margin: 0 auto; padding: 20px;">
+                        <h2 style="color: #dc3545;">账户临时锁定通知</h2>
+                        
+                        <p>您好，</p>
+                        
+                        <p>您的账户连续多次登录失败，为保护账户安全，登录已被临时锁定 <strong>{}</strong>。</p>
+                        
+                        <p>最近一次失败的登录来自 IP：<strong>{}</strong></p>
+                        
+                        <p style="color: #856404; background-color: #fff3cd; padding: 10px; border-radius: 5px; border-left: 4px solid #ffc107;">
+                            <strong>注意：</strong>如果这些登录尝试不是您本人操作，建议尽快<a href="{}">重置密码</a>并启用两步验证。
+                        </p>
+                        
+                        <hr style="border: none; border-top: 1px solid #eee; margin: 30px 0;">
+                        <p style="color: #666; font-size: 12px;">
+                            此邮件由系统自动发送，请勿回复。
+                        </p>
+                    </div>
+                </body>
+                </html>
+                "#,
+                wait, ip_address, reset_url
+            ))?;
+
+        self.mailer.send(&email)?;
+        Ok(())
+    }
+
+    pub async fn send_email_change_verification(&self, to_email: &str, token: &str) -> Result<()> {
+        let verification_url = format!("{}/auth/verify/{}", self.app_url, token);
+
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to_email.parse()?)
+            .subject("请确认您的新邮箱地址")
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                r#"
+                <!DOCTYPE html>
+                <html>
+                <head>
+                    <meta charset="UTF-8">
+                    <title>确认新邮箱</title>
+                </head>
+                <body style="font-family: Arial, sans-serif; line-height: 1.6; color: #333;">
+                    <div style="max-width: 600px; margin: 0 auto; padding: 20px;">
+                        <h2 style="color: #007bff;">确认新邮箱地址</h2>
+                        
+                        <p>您好，</p>
+                        
+                        <p>您正在将记账系统账户的登录邮箱修改为此地址。请点击下面的链接完成确认：</p>
+                        
+                        <div style="text-align: center; margin: 30px 0;">
+                            <a href="{}" 
+                               style="background-color: #007bff; color: white; padding: 12px 30px; text-decoration: none; border-radius: 5px; display: inline-block;">
+                                确认新邮箱
+                            </a>
+                        </div>
+                        
+                        <p>或者复制以下链接到浏览器中打开：</p>
+                        <p style="word-break: break-all; background-color: #f8f9fa; padding: 10px; border-radius: 5px;">
+                            <a href="{}">{}</a>
+                        </p>
+                        
+                        <p>此链接将在24小时后失效。确认之前，账户仍使用原邮箱登录。如果您没有申请修改邮箱，请忽略这封邮件。</p>
+                        
+                        <hr style="border: none; border-top: 1px solid #eee; margin: 30px 0;">
+                        <p style="color: #666; font-size: 12px;">
+                            此邮件由系统自动发送，请勿回复。
+                        </p>
+                    </div>
+                </body>
+                </html>
+                "#,
+                verification_url, verification_url, verification_url
+            ))?;
+
+        self.mailer.send(&email)?;
+        Ok(())
+    }
+
+    pub async fn send_email_changed_notice(&self, to_email: &str, new_email: &str) -> Result<()> {
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to_email.parse()?)
+            .subject("您的登录邮箱已修改")
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                r#"
+                <!DOCTYPE html>
+                <html>
+                <head>
+                    <meta charset="UTF-8">
+                    <title>登录邮箱已修改</title>
+                </head>
+                <body style="font-family: Arial, sans-serif; line-height: 1.6; color: #333;">
+                    <div style="max-width: 600px; margin: 0 auto; padding: 20px;">
+                        <h2 style="color: #dc3545;">登录邮箱已修改</h2>
+                        
+                        <p>您好，</p>
+                        
+                        <p>您的记账系统账户登录邮箱已修改为 <strong>{}</strong>，此后请使用新邮箱登录。本邮箱将不再接收该账户的通知。</p>
+                        
+                        <p style="color: #856404; background-color: #fff3cd; padding: 10px; border-radius: 5px; border-left: 4px solid #ffc107;">
+                            <strong>注意：</strong>如果这不是您本人的操作，说明账户密码可能已经泄露，请立即联系管理员处理。
+                        </p>
+                        
+                        <hr style="border: none; border-top: 1px solid #eee; margin: 30px 0;">
+                        <p style="color: #666; font-size: 12px;">
+                            此邮件由系统自动发送，请勿回复。
+                        </p>
+                    </div>
+                </body>
+                </html>
+                "#,
+                new_email
+            ))?;
+
+        self.mailer.send(&email)?;
+        Ok(())
+    }
+
+    pub async fn send_account_book_invitation(
+        &self,
+        to_email: &str,
+        inviter: &str,
+        book_name: &str,
+        role_label: &str,
+        token: &str,
+        valid_days: i64,
+    ) -> Result<()> {
+        let invitation_url = format!("{}/invitations/{}", self.app_url, token);
+
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to_email.parse()?)
+            .subject(format!("{} 邀请您加入账本「{}」", inviter, book_name))
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                r#"
+                <!DOCTYPE html>
+                <html>
+                <head>
+                    <meta charset="UTF-8">
+                    <title>账本邀请</title>
+                </head>
+                <body style="font-family: Arial, sans-serif; line-height: 1.6; color: #333;">
+                    <div style="max-width: 600px; margin: 0 auto; padding: 20px;">
+                        <h2 style="color: #007bff;">您收到一份账本邀请</h2>
+                        
+                        <p>您好，</p>
+                        
+                        <p><strong>{}</strong> 邀请您以<strong>{}</strong>身份加入记账系统中的账本「<strong>{}</strong>」。</p>
+                        
+                        <div style="text-align: center; margin: 30px 0;">
+                            <a href="{}" 
+                               style="background-color: #007bff; color: white; padding: 12px 30px; text-decoration: none; border-radius: 5px; display: inline-block;">
+                                查看邀请
+                            </a>
+                        </div>
+                        
+                        <p>或者复制以下链接到浏览器中打开：</p>
+                        <p style="word-break: break-all; background-color: #f8f9fa; padding: 10px; border-radius: 5px;">
+                            <a href="{}">{}</a>
+                        </p>
+                        
+                        <p>如果您还没有账户，请使用本邮箱注册，验证邮箱后会自动加入该账本。此邀请将在{}天后失效，且只能使用一次。</p>
+                        
+                        <hr style="border: none; border-top: 1px solid #eee; margin: 30px 0;">
+                        <p style="color: #666; font-size: 12px;">
+                            此邮件由系统自动发送，请勿回复。
+                        </p>
+                    </div>
+                </body>
+                </html>
+                "#,
+                escape_html(inviter), role_label, escape_html(book_name),
+                invitation_url, invitation_url, invitation_url, valid_days
+            ))?;
+
+        self.mailer.send(&email)?;
+        Ok(())
+    }
+
+    pub async fn send_account_deletion_scheduled(&self, to_email: &str, scheduled_at: &str) -> Result<()> {
+        let login_url = format!("{}/auth/login", self.app_url);
+
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to_email.parse()?)
+            .subject("您的账户将被注销")
+            .header(ContentType::TEXT_HTML)
+            .body(format!(
+                r#"
+                <!DOCTYPE html>
+                <html>
+                <head>
+                    <meta charset="UTF-8">
+                    <title>账户注销申请</title>
+                </head>
+                <body style="font-family: Arial, sans-serif; line-height: 1.6; color: #333;">
+                    <div style="max-width: 600px; margin: 0 auto; padding: 20px;">
+                        <h2 style="color: #dc3545;">账户注销申请已提交</h2>
+                        
+                        <p>您好，</p>
+                        
+                        <p>我们已收到您的账户注销申请。您的账户及全部账本、分类和交易记录将于 <strong>{}</strong> 被彻底删除，删除后无法恢复。</p>
+                        
+                        <p>在此之前，您可以<a href="{}">登录</a>后在"账户信息"页面撤销注销。</p>
+                        
+                        <p style="color: #856404; background-color: #fff3cd; padding: 10px; border-radius: 5px; border-left: 4px solid #ffc107;">
+                            <strong>注意：</strong>如果这不是您本人的操作，请立即登录撤销注销并修改密码。
+                        </p>
+                        
+                        <hr style="border: none; border-top: 1px solid #eee; margin: 30px 0;">
+                        <p style="color: #666; font-size: 12px;">
+                            此邮件由系统自动发送，请勿回复。
+                        </p>
+                    </div>
+                </body>
+                </html>
+                "#,
+                scheduled_at, login_url
+            ))?;
+
+        self.mailer.send(&email)?;
+        Ok(())
+    }
+
+    pub async fn send_account_deleted_notice(&self, to_email: &str) -> Result<()> {
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to_email.parse()?)
+            .subject("您的账户已注销")
+            .header(ContentType::TEXT_HTML)
+            .body(
+                r#"
+                <!DOCTYPE html>
+                <html>
+                <head>
+                    <meta charset="UTF-8">
+                    <title>账户已注销</title>
+                </head>
+                <body style="font-family: Arial, sans-serif; line-height: 1.6; color: #333;">
+                    <div style="max-width: 600px; margin: 0 auto; padding: 20px;">
+                        <h2 style="color: #333;">账户已注销</h2>
+                        
+                        <p>您好，</p>
+                        
+                        <p>您的记账系统账户及其全部数据已按您的申请彻底删除。感谢您的使用。</p>
+                        
+                        <hr style="border: none; border-top: 1px solid #eee; margin: 30px 0;">
+                        <p style="color: #666; font-size: 12px;">
+                            此邮件由系统自动发送，请勿回复。
+                        </p>
+                    </div>
+                </body>
+                </html>
+                "#
+                .to_string(),
+            )?;
+
+        self.mailer.send(&email)?;
+        Ok(())
+    }
+}
+
+// 用户填写的内容（账本名称、显示名称）写入邮件正文前转义
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::database::DbPool;
+use crate::models::{CreateExchangeRate, ExchangeRate, TransactionAmount};
+use crate::utils::is_valid_currency_code;
+
+// 单次导入的最大行数
+pub const MAX_EXCHANGE_RATE_IMPORT_ROWS: usize = 5000;
+
+pub struct ExchangeRateService;
+
+impl ExchangeRateService {
+    // CSV 每行为：日期,基础货币,报价货币,汇率，例如 2024-03-01,USD,CNY,7.1932；首行可以是表头
+    pub fn parse_csv(content: &str) -> Result<Vec<CreateExchangeRate>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(content.as_bytes());
+
+        let mut rates = Vec::new();
+        for (index, record) in reader.records().enumerate() {
+            let line = index + 1;
+            let record = record.map_err(|_| anyhow!("第{}行无法解析", line))?;
+            if record.iter().all(|field| field.is_empty()) {
+                continue;
+            }
+
+            let rate_date = match NaiveDate::parse_from_str(record.get(0).unwrap_or_default(), "%Y-%m-%d") {
+                Ok(date) => date,
+                // 首行日期无法解析时视为表头
+                Err(_) if index == 0 => continue,
+                Err(_) => return Err(anyhow!("第{}行日期格式错误，应为YYYY-MM-DD", line)),
+            };
+
+            let base_currency = record.get(1).unwrap_or_default().to_uppercase();
+            let quote_currency = record.get(2).unwrap_or_default().to_uppercase();
+            if !is_valid_currency_code(&base_currency) || !is_valid_currency_code(&quote_currency) {
+                return Err(anyhow!("第{}行货币代码无效", line));
+            }
+            if base_currency == quote_currency {
+                return Err(anyhow!("第{}行基础货币和报价货币不能相同", line));
+            }
+
+            let rate = match record.get(3).unwrap_or_default().parse::<Decimal>() {
+                Ok(rate) if rate > Decimal::ZERO => rate,
+                _ => return Err(anyhow!("第{}行汇率必须为大于0的数字", line)),
+            };
+
+            rates.push(CreateExchangeRate { base_currency, quote_currency, rate, rate_date });
+
+            if rates.len() > MAX_EXCHANGE_RATE_IMPORT_ROWS {
+                return Err(anyhow!("单次最多导入{}条汇率", MAX_EXCHANGE_RATE_IMPORT_ROWS));
+            }
+        }
+
+        if rates.is_empty() {
+            return Err(anyhow!("没有可导入的汇率"));
+        }
+
+        Ok(rates)
+    }
+
+    pub async fn import_csv(pool: &DbPool, user_id: i64, content: &str) -> Result<usize> {
+        let rates = Self::parse_csv(content)?;
+        ExchangeRate::import(pool, user_id, &rates).await?;
+        Ok(rates.len())
+    }
+
+    // 把原始金额折算为账本货币，未手动填写汇率时使用交易日期当天或之前最近导入的汇率
+    pub async fn convert(
+        pool: &DbPool,
+        user_id: i64,
+        book_currency: &str,
+        original_currency: &str,
+        original_amount: Decimal,
+        manual_rate: Option<Decimal>,
+        date: NaiveDate,
+    ) -> Result<TransactionAmount> {
+        if original_currency == book_currency {
+            return Ok(TransactionAmount {
+                amount: original_amount,
+                original_currency: original_currency.to_string(),
+                original_amount,
+                exchange_rate: Decimal::ONE,
+            });
+        }
+
+        if !is_valid_currency_code(original_currency) {
+            return Err(anyhow!("货币代码无效"));
+        }
+
+        let rate = match manual_rate {
+            Some(rate) if rate > Decimal::ZERO => rate,
+            Some(_) => return Err(anyhow!("汇率必须大于0")),
+            None => ExchangeRate::find_rate(pool, user_id, original_currency, book_currency, date)
+                .await?
+                .ok_or_else(|| anyhow!(
+                    "未找到{}兑{}在{}或之前的汇率，请先导入汇率或手动填写",
+                    original_currency, book_currency, date.format("%Y-%m-%d")
+                ))?,
+        };
+
+        let amount = (original_amount * rate).round_dp(2);
+        if amount <= Decimal::ZERO {
+            return Err(anyhow!("折算后的金额必须大于0"));
+        }
+
+        Ok(TransactionAmount {
+            amount,
+            original_currency: original_currency.to_string(),
+            original_amount,
+            exchange_rate: rate,
+        })
+    }
+}
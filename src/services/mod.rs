@@ -1,7 +1,17 @@
 pub mod email_service;
 pub mod auth_service;
 pub mod account_service;
+pub mod throttle_service;
+pub mod oidc_service;
+pub mod data_export_service;
+pub mod exchange_rate_service;
+pub mod recurring_service;
 
 pub use email_service::*;
 pub use auth_service::*;
-pub use account_service::*;
\ No newline at end of file
+pub use account_service::*;
+pub use throttle_service::*;
+pub use oidc_service::*;
+pub use data_export_service::*;
+pub use exchange_rate_service::*;
+pub use recurring_service::*;
\ No newline at end of file
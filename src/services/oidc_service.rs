@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, decode_header, encode, jwk::{Jwk, JwkSet}, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use crate::config::OidcProviderConfig;
+
+// 从跳转到身份提供方到回调完成的时限（分钟）
+const OIDC_FLOW_TTL_MINUTES: i64 = 10;
+
+// 发现文档中用到的字段
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    email: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_bool_or_string")]
+    email_verified: Option<bool>,
+    nonce: Option<String>,
+}
+
+// 授权请求的 state、nonce 和 PKCE verifier，签名后存放在cookie中
+#[derive(Debug, Serialize, Deserialize)]
+struct OidcFlowClaims {
+    provider: String,
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    exp: i64,
+}
+
+// 身份提供方返回的已验证身份
+#[derive(Debug, Clone)]
+pub struct OidcIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OidcProviderLink {
+    pub id: String,
+    pub display_name: String,
+}
+
+#[derive(Clone)]
+pub struct OidcService {
+    http: reqwest::Client,
+    providers: Arc<Vec<OidcProviderConfig>>,
+    metadata: Arc<RwLock<HashMap<String, ProviderMetadata>>>,
+    jwks: Arc<RwLock<HashMap<String, JwkSet>>>,
+    app_url: String,
+    jwt_secret: String,
+}
+
+impl OidcService {
+    pub fn new(providers: Vec<OidcProviderConfig>, app_url: String, jwt_secret: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            providers: Arc::new(providers),
+            metadata: Arc::new(RwLock::new(HashMap::new())),
+            jwks: Arc::new(RwLock::new(HashMap::new())),
+            app_url,
+            jwt_secret,
+        }
+    }
+
+    pub fn provider_links(&self) -> Vec<OidcProviderLink> {
+        self.providers
+            .iter()
+            .map(|provider| OidcProviderLink {
+                id: provider.id.clone(),
+                display_name: provider.display_name.clone(),
+            })
+            .collect()
+    }
+
+    pub fn provider(&self, provider_id: &str) -> Result<&OidcProviderConfig> {
+        self.providers
+            .iter()
+            .find(|provider| provider.id == provider_id)
+            .ok_or_else(|| anyhow!("未配置该登录方式"))
+    }
+
+    fn redirect_uri(&self, provider_id: &str) -> String {
+        format!("{}/auth/oidc/{}/callback", self.app_url.trim_end_matches('/'), provider_id)
+    }
+
+    // 生成授权跳转地址，同时返回需要写入cookie的流程状态
+    pub async fn authorization_url(&self, provider_id: &str) -> Result<(String, String)> {
+        let provider = self.provider(provider_id)?;
+        let metadata = self.metadata(provider).await?;
+
+        let state = random_string(32);
+        let nonce = random_string(32);
+        let code_verifier = random_string(64);
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri(provider_id))
+            .append_pair("scope", &provider.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        let flow = OidcFlowClaims {
+            provider: provider.id.clone(),
+            state,
+            nonce,
+            code_verifier,
+            exp: (Utc::now() + Duration::minutes(OIDC_FLOW_TTL_MINUTES)).timestamp(),
+        };
+        let flow_token = encode(
+            &Header::default(),
+            &flow,
+            &EncodingKey::from_secret(self.jwt_secret.as_ref()),
+        )?;
+
+        Ok((url.to_string(), flow_token))
+    }
+
+    // 校验回调参数，用授权码换取并验证 ID Token
+    pub async fn complete(
+        &self,
+        provider_id: &str,
+        code: &str,
+        state: &str,
+        flow_token: &str,
+    ) -> Result<OidcIdentity> {
+        let flow = decode::<OidcFlowClaims>(
+            flow_token,
+            &DecodingKey::from_secret(self.jwt_secret.as_ref()),
+            &Validation::default(),
+        )
+        .map_err(|_| anyhow!("登录请求已过期，请重试"))?
+        .claims;
+
+        if flow.provider != provider_id || flow.state != state {
+            return Err(anyhow!("登录请求无效，请重试"));
+        }
+
+        let provider = self.provider(provider_id)?;
+        let metadata = self.metadata(provider).await?;
+
+        let redirect_uri = self.redirect_uri(provider_id);
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", flow.code_verifier.as_str()),
+        ];
+        if let Some(client_secret) = &provider.client_secret {
+            params.push(("client_secret", client_secret.as_str()));
+        }
+
+        let response = self.http
+            .post(&metadata.token_endpoint)
+            .form(&params)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            tracing::warn!("OIDC token request to {} failed: {} {}", provider.id, status, body);
+            return Err(anyhow!("身份提供方拒绝了登录请求"));
+        }
+
+        let id_token = response
+            .json::<TokenResponse>()
+            .await?
+            .id_token
+            .ok_or_else(|| anyhow!("身份提供方未返回ID Token"))?;
+
+        let claims = self.verify_id_token(provider, &metadata, &id_token).await?;
+
+        if claims.nonce.as_deref() != Some(flow.nonce.as_str()) {
+            return Err(anyhow!("登录请求无效，请重试"));
+        }
+
+        Ok(OidcIdentity {
+            provider: provider.id.clone(),
+            subject: claims.sub,
+            email: claims.email.map(|email| email.trim().to_lowercase()),
+            email_verified: claims.email_verified.unwrap_or(false),
+        })
+    }
+
+    // 签名算法必须在提供方的允许列表中，且与签名密钥声明的算法一致，不采信 ID Token 头部自称的算法
+    async fn verify_id_token(
+        &self,
+        provider: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims> {
+        let header = decode_header(id_token)?;
+        if !provider.algorithms.contains(&header.alg) {
+            return Err(anyhow!("ID Token签名算法{:?}不在允许列表中", header.alg));
+        }
+
+        let jwk = self.signing_key(provider, metadata, header.kid.as_deref(), header.alg).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = vec![header.alg];
+        validation.set_audience(&[provider.client_id.as_str()]);
+        validation.set_issuer(&[metadata.issuer.as_str()]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &DecodingKey::from_jwk(&jwk)?, &validation)
+            .map_err(|e| anyhow!("ID Token校验失败: {}", e))?
+            .claims;
+
+        Ok(claims)
+    }
+
+    // 从缓存的 JWKS 中查找签名密钥，找不到时（身份提供方轮换了密钥）重新获取一次
+    async fn signing_key(
+        &self,
+        provider: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        kid: Option<&str>,
+        alg: Algorithm,
+    ) -> Result<Jwk> {
+        if let Some(jwks) = self.jwks.read().await.get(&provider.id) {
+            if let Some(jwk) = find_signing_key(jwks, kid, alg) {
+                return Ok(jwk.clone());
+            }
+        }
+
+        let jwks = self.http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| anyhow!("读取身份提供方签名密钥失败: {}", e))?;
+        let jwk = find_signing_key(&jwks, kid, alg).cloned();
+        self.jwks.write().await.insert(provider.id.clone(), jwks);
+
+        jwk.ok_or_else(|| anyhow!("找不到ID Token的签名密钥"))
+    }
+
+    // 读取发现文档，成功后缓存
+    async fn metadata(&self, provider: &OidcProviderConfig) -> Result<ProviderMetadata> {
+        if let Some(metadata) = self.metadata.read().await.get(&provider.id) {
+            return Ok(metadata.clone());
+        }
+
+        let discovery_url = format!("{}/.well-known/openid-configuration", provider.issuer);
+        let metadata = self.http
+            .get(&discovery_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ProviderMetadata>()
+            .await
+            .map_err(|e| anyhow!("读取身份提供方配置失败: {}", e))?;
+
+        if metadata.issuer.trim_end_matches('/') != provider.issuer {
+            return Err(anyhow!("身份提供方issuer与配置不一致"));
+        }
+
+        self.metadata.write().await.insert(provider.id.clone(), metadata.clone());
+
+        Ok(metadata)
+    }
+}
+
+// 按 kid 查找密钥（没有 kid 时取第一个可用的密钥）；密钥声明了算法时必须与 ID Token 的算法一致
+fn find_signing_key<'a>(jwks: &'a JwkSet, kid: Option<&str>, alg: Algorithm) -> Option<&'a Jwk> {
+    let algorithm_matches = |jwk: &&Jwk| match jwk.common.key_algorithm {
+        Some(key_algorithm) => key_algorithm.to_string().parse::<Algorithm>().ok() == Some(alg),
+        None => true,
+    };
+
+    match kid {
+        Some(kid) => jwks.find(kid).filter(algorithm_matches),
+        None => jwks.keys.iter().find(algorithm_matches),
+    }
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+// 部分身份提供方把 email_verified 返回为字符串
+fn deserialize_bool_or_string<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrString {
+        Bool(bool),
+        String(String),
+    }
+
+    Ok(match Option::<BoolOrString>::deserialize(deserializer)? {
+        Some(BoolOrString::Bool(value)) => Some(value),
+        Some(BoolOrString::String(value)) => Some(value == "true"),
+        None => None,
+    })
+}
@@ -0,0 +1,218 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDate, Utc};
+use sqlx::MySqlConnection;
+
+use crate::database::DbPool;
+use crate::models::{
+    parse_tag_names, AccountBook, AccountBookMember, CreateTransaction, RecurringOccurrence, RecurringTransaction, Transaction,
+    UserPreferences,
+};
+use crate::services::ExchangeRateService;
+
+// 单条规则每次最多补生成的次数，起始日期很早的每日规则分多次补齐
+const MAX_OCCURRENCES_PER_RUN: i32 = 100;
+
+pub struct RecurringService;
+
+impl RecurringService {
+    // 生成所有规则中已到期的交易，返回生成的笔数；单条规则失败不影响其他规则。
+    // “今天”按规则创建者设置的时区计算，先取所有时区中最晚的日期筛选候选规则
+    pub async fn materialize_due(pool: &DbPool) -> Result<usize> {
+        let latest_today = (Utc::now() + Duration::hours(14)).date_naive();
+        let mut created = 0;
+        for rule in RecurringTransaction::find_due(pool, latest_today).await? {
+            let today = match rule.created_by {
+                Some(user_id) => UserPreferences::load(pool, user_id).await.today(),
+                None => UserPreferences::defaults(0).today(),
+            };
+            match Self::materialize_rule(pool, rule.id, today).await {
+                Ok(count) => created += count,
+                Err(e) => tracing::warn!("Failed to materialize recurring transaction {}: {}", rule.id, e),
+            }
+        }
+
+        Ok(created)
+    }
+
+    // 按顺序处理日期不晚于 today 的次数：已提前生成或跳过的直接越过，落在已结账期间的记为 blocked，
+    // 在规则详情中提示。记账人已不是账本的编辑者时暂停规则。
+    // 规则行在事务内加锁，发生记录按序号唯一，重复执行不会重复生成
+    pub async fn materialize_rule(pool: &DbPool, rule_id: i64, today: NaiveDate) -> Result<usize> {
+        let mut tx = pool.begin().await?;
+
+        let rule = match RecurringTransaction::lock(&mut tx, rule_id).await? {
+            Some(rule) if rule.is_active => rule,
+            _ => {
+                tx.rollback().await?;
+                return Ok(0);
+            }
+        };
+        let book = AccountBook::find_unchecked(&mut tx, rule.account_book_id)
+            .await?
+            .ok_or_else(|| anyhow!("账本不存在"))?;
+        let created_by = match Self::booking_user(pool, &rule, &book).await? {
+            Some(user_id) => user_id,
+            None => {
+                RecurringTransaction::pause(&mut tx, rule.id).await?;
+                tx.commit().await?;
+                tracing::info!("Paused recurring transaction {}: creator can no longer edit the book", rule.id);
+                return Ok(0);
+            }
+        };
+
+        let mut sequence = rule.next_sequence;
+        let mut created = 0;
+        let mut failure = None;
+        while let Some(date) = rule.occurrence_date(sequence) {
+            if date > today || sequence - rule.next_sequence >= MAX_OCCURRENCES_PER_RUN {
+                break;
+            }
+
+            if RecurringOccurrence::find_by_sequence(&mut tx, rule.id, sequence).await?.is_none() {
+                if book.is_closed(date) {
+                    RecurringOccurrence::record(&mut tx, rule.id, sequence, date, "blocked", None).await?;
+                } else {
+                    // 汇率缺失等无法生成时停在这一次，下次重试
+                    let create_transaction = match Self::build_transaction(pool, &rule, &book, date, created_by).await {
+                        Ok(create_transaction) => create_transaction,
+                        Err(e) => {
+                            failure = Some(e);
+                            break;
+                        }
+                    };
+                    Self::insert_occurrence(&mut tx, &rule, sequence, date, &create_transaction).await?;
+                    created += 1;
+                }
+            }
+            sequence += 1;
+        }
+
+        RecurringTransaction::advance(&mut tx, rule.id, sequence, rule.occurrence_date(sequence)).await?;
+        tx.commit().await?;
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(created),
+        }
+    }
+
+    // 提前生成某一次的交易，以便单独修改这一次；已生成时返回原交易ID
+    pub async fn generate_occurrence(pool: &DbPool, rule_id: i64, sequence: i32) -> Result<i64> {
+        let mut tx = pool.begin().await?;
+
+        let rule = RecurringTransaction::lock(&mut tx, rule_id)
+            .await?
+            .ok_or_else(|| anyhow!("重复记账规则不存在"))?;
+        let date = rule
+            .occurrence_date(sequence)
+            .ok_or_else(|| anyhow!("该次不在规则的周期范围内"))?;
+
+        if let Some(occurrence) = RecurringOccurrence::find_by_sequence(&mut tx, rule.id, sequence).await? {
+            tx.rollback().await?;
+            return match occurrence.transaction_id {
+                Some(transaction_id) => Ok(transaction_id),
+                None if occurrence.status == "skipped" => Err(anyhow!("该次已跳过")),
+                None if occurrence.status == "blocked" => Err(anyhow!("该次所在期间已结账，未生成交易")),
+                None => Err(anyhow!("该次生成的交易已删除")),
+            };
+        }
+        if sequence < rule.next_sequence {
+            return Err(anyhow!("该次已处理"));
+        }
+
+        let book = AccountBook::find_unchecked(&mut tx, rule.account_book_id)
+            .await?
+            .ok_or_else(|| anyhow!("账本不存在"))?;
+        if book.is_closed(date) {
+            return Err(anyhow!("{} 所在期间已结账，不能生成交易", date.format("%Y-%m-%d")));
+        }
+        let created_by = Self::booking_user(pool, &rule, &book)
+            .await?
+            .ok_or_else(|| anyhow!("规则创建者已不是账本的编辑者，请先恢复规则"))?;
+
+        let create_transaction = Self::build_transaction(pool, &rule, &book, date, created_by).await?;
+        let transaction_id = Self::insert_occurrence(&mut tx, &rule, sequence, date, &create_transaction).await?;
+        tx.commit().await?;
+
+        Ok(transaction_id)
+    }
+
+    // 跳过尚未处理的某一次，到期后不再生成
+    pub async fn skip_occurrence(pool: &DbPool, rule_id: i64, sequence: i32) -> Result<()> {
+        let mut tx = pool.begin().await?;
+
+        let rule = RecurringTransaction::lock(&mut tx, rule_id)
+            .await?
+            .ok_or_else(|| anyhow!("重复记账规则不存在"))?;
+        let date = rule
+            .occurrence_date(sequence)
+            .ok_or_else(|| anyhow!("该次不在规则的周期范围内"))?;
+        if sequence < rule.next_sequence {
+            return Err(anyhow!("该次已处理，如不需要请直接删除生成的交易"));
+        }
+
+        if !RecurringOccurrence::record(&mut tx, rule.id, sequence, date, "skipped", None).await? {
+            return Err(anyhow!("该次已生成或已跳过"));
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // 生成的交易记为规则的创建者，创建者已注销时记为账本创建者；
+    // 该用户已被移出账本或降为查看者时返回 None，不能再以其名义记账
+    pub async fn booking_user(pool: &DbPool, rule: &RecurringTransaction, book: &AccountBook) -> Result<Option<i64>> {
+        let user_id = rule.created_by.unwrap_or(book.user_id);
+        let role = AccountBookMember::find_role(pool, book.id, user_id).await?;
+
+        Ok(role.filter(|role| role.can_edit()).map(|_| user_id))
+    }
+
+    // 使用记账人导入的汇率折算
+    async fn build_transaction(
+        pool: &DbPool,
+        rule: &RecurringTransaction,
+        book: &AccountBook,
+        date: NaiveDate,
+        created_by: i64,
+    ) -> Result<CreateTransaction> {
+        let amount = ExchangeRateService::convert(
+            pool,
+            created_by,
+            &book.currency,
+            &rule.original_currency,
+            rule.original_amount,
+            rule.exchange_rate,
+            date,
+        ).await?;
+
+        Ok(CreateTransaction {
+            account_book_id: rule.account_book_id,
+            category_id: rule.category_id,
+            wallet_id: rule.wallet_id,
+            amount: amount.amount,
+            original_currency: amount.original_currency,
+            original_amount: amount.original_amount,
+            exchange_rate: amount.exchange_rate,
+            transaction_type: rule.transaction_type.clone(),
+            description: rule.description.clone(),
+            transaction_date: date,
+            tags: parse_tag_names(rule.tags.as_deref().unwrap_or_default()),
+            splits: Vec::new(),
+            created_by,
+        })
+    }
+
+    async fn insert_occurrence(
+        conn: &mut MySqlConnection,
+        rule: &RecurringTransaction,
+        sequence: i32,
+        date: NaiveDate,
+        create_transaction: &CreateTransaction,
+    ) -> Result<i64> {
+        let transaction_id = Transaction::insert(&mut *conn, create_transaction).await?;
+        RecurringOccurrence::record(&mut *conn, rule.id, sequence, date, "created", Some(transaction_id)).await?;
+
+        Ok(transaction_id)
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use chrono::Utc;
+
+use crate::database::DbPool;
+use crate::models::AuthThrottle;
+
+// 失败记录在最后一次失败后保留的时长（秒），超过后重新计数
+const THROTTLE_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+
+// 限流策略：允许的次数用完后，每多一次锁定时间翻倍，直到上限
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottlePolicy {
+    pub action: &'static str,
+    pub max_attempts: i32,
+    pub base_lockout_seconds: i64,
+    pub max_lockout_seconds: i64,
+}
+
+pub const LOGIN_ACCOUNT_POLICY: ThrottlePolicy = ThrottlePolicy {
+    action: "login_account",
+    max_attempts: 5,
+    base_lockout_seconds: 60,
+    max_lockout_seconds: 60 * 60,
+};
+
+pub const LOGIN_IP_POLICY: ThrottlePolicy = ThrottlePolicy {
+    action: "login_ip",
+    max_attempts: 20,
+    base_lockout_seconds: 60,
+    max_lockout_seconds: 60 * 60,
+};
+
+pub const RESEND_VERIFICATION_EMAIL_POLICY: ThrottlePolicy = ThrottlePolicy {
+    action: "resend_verification_email",
+    max_attempts: 3,
+    base_lockout_seconds: 5 * 60,
+    max_lockout_seconds: 24 * 60 * 60,
+};
+
+pub const RESEND_VERIFICATION_IP_POLICY: ThrottlePolicy = ThrottlePolicy {
+    action: "resend_verification_ip",
+    max_attempts: 10,
+    base_lockout_seconds: 5 * 60,
+    max_lockout_seconds: 24 * 60 * 60,
+};
+
+pub const PASSWORD_RESET_EMAIL_POLICY: ThrottlePolicy = ThrottlePolicy {
+    action: "password_reset_email",
+    max_attempts: 3,
+    base_lockout_seconds: 5 * 60,
+    max_lockout_seconds: 24 * 60 * 60,
+};
+
+pub const PASSWORD_RESET_IP_POLICY: ThrottlePolicy = ThrottlePolicy {
+    action: "password_reset_ip",
+    max_attempts: 10,
+    base_lockout_seconds: 5 * 60,
+    max_lockout_seconds: 24 * 60 * 60,
+};
+
+pub const EMAIL_CHANGE_POLICY: ThrottlePolicy = ThrottlePolicy {
+    action: "email_change",
+    max_attempts: 3,
+    base_lockout_seconds: 5 * 60,
+    max_lockout_seconds: 24 * 60 * 60,
+};
+
+// 处于锁定期时返回的错误，handler可以据此提示剩余时间
+#[derive(Debug, thiserror::Error)]
+#[error("尝试次数过多，请在{}后重试", format_wait(*retry_after_seconds))]
+pub struct ThrottledError {
+    pub retry_after_seconds: i64,
+}
+
+pub struct ThrottleService;
+
+impl ThrottleService {
+    // 检查是否处于锁定期
+    pub async fn check(pool: &DbPool, policy: ThrottlePolicy, key: &str) -> Result<()> {
+        if let Some(throttle) = AuthThrottle::find(pool, policy.action, key).await? {
+            if let Some(locked_until) = throttle.locked_until {
+                let remaining = (locked_until - Utc::now()).num_seconds();
+                if remaining > 0 {
+                    return Err(ThrottledError { retry_after_seconds: remaining }.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // 记录一次失败，触发锁定时返回锁定秒数；已处于锁定期时返回 ThrottledError
+    pub async fn record(pool: &DbPool, policy: ThrottlePolicy, key: &str) -> Result<Option<i64>> {
+        let count = AuthThrottle::increment(
+            pool,
+            policy.action,
+            key,
+            THROTTLE_WINDOW_SECONDS,
+            policy.max_attempts,
+            policy.base_lockout_seconds,
+            policy.max_lockout_seconds,
+        ).await?;
+
+        match count {
+            None => Err(Self::locked_error(pool, policy, key).await),
+            Some(count) if count >= policy.max_attempts => Ok(Some(lockout_seconds(policy, count))),
+            Some(_) => Ok(None),
+        }
+    }
+
+    // 计数并按计数结果决定是否放行，用于发送邮件这类每次请求都要计数的操作；
+    // 检查和计数在同一条语句内完成，并发请求不会同时越过限制
+    pub async fn hit(pool: &DbPool, policy: ThrottlePolicy, key: &str) -> Result<()> {
+        Self::record(pool, policy, key).await?;
+        Ok(())
+    }
+
+    async fn locked_error(pool: &DbPool, policy: ThrottlePolicy, key: &str) -> anyhow::Error {
+        let remaining = match AuthThrottle::find(pool, policy.action, key).await {
+            Ok(Some(AuthThrottle { locked_until: Some(locked_until), .. })) => (locked_until - Utc::now()).num_seconds(),
+            _ => policy.base_lockout_seconds,
+        };
+        ThrottledError { retry_after_seconds: remaining.max(1) }.into()
+    }
+
+    pub async fn clear(pool: &DbPool, policy: ThrottlePolicy, key: &str) -> Result<()> {
+        AuthThrottle::clear(pool, policy.action, key).await
+    }
+
+    pub async fn clean_stale(pool: &DbPool) -> Result<()> {
+        AuthThrottle::delete_stale(pool, THROTTLE_WINDOW_SECONDS).await
+    }
+}
+
+fn lockout_seconds(policy: ThrottlePolicy, failure_count: i32) -> i64 {
+    let exponent = (failure_count - policy.max_attempts).clamp(0, 20) as u32;
+    policy
+        .base_lockout_seconds
+        .saturating_mul(1_i64 << exponent)
+        .min(policy.max_lockout_seconds)
+}
+
+pub fn format_wait(seconds: i64) -> String {
+    if seconds >= 3600 {
+        format!("{}小时", (seconds + 3599) / 3600)
+    } else if seconds >= 60 {
+        format!("{}分钟", (seconds + 59) / 60)
+    } else {
+        format!("{}秒", seconds.max(1))
+    }
+}
+
+// 限流键统一小写，避免大小写绕过
+pub fn email_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: ThrottlePolicy = ThrottlePolicy {
+        action: "test",
+        max_attempts: 5,
+        base_lockout_seconds: 60,
+        max_lockout_seconds: 60 * 60,
+    };
+
+    #[test]
+    fn lockout_doubles_after_max_attempts() {
+        assert_eq!(lockout_seconds(POLICY, 5), 60);
+        assert_eq!(lockout_seconds(POLICY, 6), 120);
+        assert_eq!(lockout_seconds(POLICY, 7), 240);
+        assert_eq!(lockout_seconds(POLICY, 10), 1920);
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        assert_eq!(lockout_seconds(POLICY, 11), 3600);
+        assert_eq!(lockout_seconds(POLICY, 100), 3600);
+        assert_eq!(lockout_seconds(POLICY, i32::MAX), 3600);
+    }
+
+    #[test]
+    fn lockout_before_max_attempts_uses_base() {
+        assert_eq!(lockout_seconds(POLICY, 1), 60);
+    }
+
+    #[test]
+    fn format_wait_rounds_up() {
+        assert_eq!(format_wait(0), "1秒");
+        assert_eq!(format_wait(61), "2分钟");
+        assert_eq!(format_wait(3601), "2小时");
+    }
+}
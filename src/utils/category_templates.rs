@@ -0,0 +1,101 @@
+// 内置分类模板，新建账本时可选
+pub struct BuiltinCategoryTemplate {
+    pub key: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    // (分类名称, 类型, 图标, 颜色)，同一类型内按顺序排序
+    pub categories: &'static [(&'static str, &'static str, &'static str, &'static str)],
+}
+
+pub const DEFAULT_CATEGORY_TEMPLATE: &str = "personal";
+
+pub const BUILTIN_CATEGORY_TEMPLATES: &[BuiltinCategoryTemplate] = &[
+    BuiltinCategoryTemplate {
+        key: "personal",
+        name: "个人日常",
+        description: "衣食住行等个人日常收支",
+        categories: &[
+            ("餐饮美食", "expense", "cup-straw", "#ff6b6b"),
+            ("交通出行", "expense", "car-front", "#4ecdc4"),
+            ("购物消费", "expense", "cart", "#45b7d1"),
+            ("娱乐休闲", "expense", "controller", "#f7b731"),
+            ("医疗健康", "expense", "hospital", "#5f27cd"),
+            ("学习教育", "expense", "book", "#00d2d3"),
+            ("住房租金", "expense", "house-door", "#ff9ff3"),
+            ("水电燃气", "expense", "lightning", "#ff3838"),
+            ("通讯网费", "expense", "phone", "#17c0eb"),
+            ("其他支出", "expense", "three-dots", "#a4b0be"),
+            ("工资收入", "income", "currency-dollar", "#26de81"),
+            ("兼职收入", "income", "cash-coin", "#4b7bec"),
+            ("投资理财", "income", "graph-up-arrow", "#fd79a8"),
+            ("礼金红包", "income", "gift", "#fdcb6e"),
+            ("其他收入", "income", "three-dots", "#6c5ce7"),
+        ],
+    },
+    BuiltinCategoryTemplate {
+        key: "household",
+        name: "家庭账本",
+        description: "适合多人共同记录的家庭开支",
+        categories: &[
+            ("买菜做饭", "expense", "cup-straw", "#ff6b6b"),
+            ("外出就餐", "expense", "cup-straw", "#ff9f43"),
+            ("日用百货", "expense", "cart", "#45b7d1"),
+            ("房租房贷", "expense", "house-door", "#ff9ff3"),
+            ("水电燃气", "expense", "lightning", "#ff3838"),
+            ("物业费用", "expense", "house", "#c56cf0"),
+            ("交通出行", "expense", "car-front", "#4ecdc4"),
+            ("子女教育", "expense", "book", "#00d2d3"),
+            ("医疗健康", "expense", "hospital", "#5f27cd"),
+            ("人情往来", "expense", "gift", "#f7b731"),
+            ("通讯网费", "expense", "phone", "#17c0eb"),
+            ("其他支出", "expense", "three-dots", "#a4b0be"),
+            ("工资收入", "income", "currency-dollar", "#26de81"),
+            ("奖金补贴", "income", "cash-coin", "#4b7bec"),
+            ("投资理财", "income", "graph-up-arrow", "#fd79a8"),
+            ("其他收入", "income", "three-dots", "#6c5ce7"),
+        ],
+    },
+    BuiltinCategoryTemplate {
+        key: "travel",
+        name: "旅行",
+        description: "记录一次旅行的各项花费",
+        categories: &[
+            ("机票车票", "expense", "car-front", "#4ecdc4"),
+            ("住宿酒店", "expense", "house-door", "#ff9ff3"),
+            ("餐饮美食", "expense", "cup-straw", "#ff6b6b"),
+            ("当地交通", "expense", "car-front", "#45b7d1"),
+            ("门票游玩", "expense", "controller", "#f7b731"),
+            ("购物纪念", "expense", "cart", "#c56cf0"),
+            ("签证保险", "expense", "credit-card", "#5f27cd"),
+            ("通讯网费", "expense", "phone", "#17c0eb"),
+            ("其他支出", "expense", "three-dots", "#a4b0be"),
+            ("预算拨款", "income", "cash-coin", "#26de81"),
+            ("退款返现", "income", "credit-card", "#4b7bec"),
+            ("其他收入", "income", "three-dots", "#6c5ce7"),
+        ],
+    },
+    BuiltinCategoryTemplate {
+        key: "small_business",
+        name: "小微经营",
+        description: "个体户、小店或自由职业的经营收支",
+        categories: &[
+            ("进货采购", "expense", "cart", "#45b7d1"),
+            ("房租场地", "expense", "house-door", "#ff9ff3"),
+            ("员工工资", "expense", "currency-dollar", "#ff6b6b"),
+            ("水电网费", "expense", "lightning", "#ff3838"),
+            ("物流快递", "expense", "car-front", "#4ecdc4"),
+            ("推广营销", "expense", "phone", "#f7b731"),
+            ("设备办公", "expense", "controller", "#00d2d3"),
+            ("税费手续费", "expense", "credit-card", "#5f27cd"),
+            ("其他支出", "expense", "three-dots", "#a4b0be"),
+            ("销售收入", "income", "cash-coin", "#26de81"),
+            ("服务收入", "income", "currency-dollar", "#4b7bec"),
+            ("退款返还", "income", "credit-card", "#fdcb6e"),
+            ("其他收入", "income", "three-dots", "#6c5ce7"),
+        ],
+    },
+];
+
+pub fn find_builtin_category_template(key: &str) -> Option<&'static BuiltinCategoryTemplate> {
+    BUILTIN_CATEGORY_TEMPLATES.iter().find(|template| template.key == key)
+}
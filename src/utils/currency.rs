@@ -0,0 +1,27 @@
+// 支持的货币：(代码, 名称, 符号)
+pub const SUPPORTED_CURRENCIES: &[(&str, &str, &str)] = &[
+    ("CNY", "人民币", "¥"),
+    ("USD", "美元", "$"),
+    ("EUR", "欧元", "€"),
+    ("HKD", "港币", "HK$"),
+    ("JPY", "日元", "¥"),
+    ("GBP", "英镑", "£"),
+];
+
+pub fn is_supported_currency(code: &str) -> bool {
+    SUPPORTED_CURRENCIES.iter().any(|(c, _, _)| *c == code)
+}
+
+// 未知货币直接显示货币代码
+pub fn currency_symbol(code: &str) -> String {
+    SUPPORTED_CURRENCIES
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|(_, _, symbol)| symbol.to_string())
+        .unwrap_or_else(|| format!("{} ", code))
+}
+
+// 汇率导入和外币记账接受任意三位大写字母的货币代码
+pub fn is_valid_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.bytes().all(|b| b.is_ascii_uppercase())
+}
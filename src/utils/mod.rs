@@ -1,5 +1,13 @@
 pub mod password;
 pub mod validator;
+pub mod totp;
+pub mod token;
+pub mod currency;
+pub mod category_templates;
 
 pub use password::*;
-pub use validator::*;
\ No newline at end of file
+pub use validator::*;
+pub use totp::*;
+pub use token::*;
+pub use currency::*;
+pub use category_templates::*;
@@ -0,0 +1,40 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+// 令牌只以SHA-256摘要形式入库
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// 个人访问令牌统一前缀，便于和登录JWT区分
+pub const API_TOKEN_PREFIX: &str = "pat_";
+
+pub fn generate_api_token() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+
+    format!("{}{}", API_TOKEN_PREFIX, random)
+}
+
+// 生成形如 abcd-efgh-ijkl 的一次性恢复码
+pub fn generate_recovery_code() -> String {
+    let raw: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        .take(12)
+        .map(char::from)
+        .collect();
+
+    format!("{}-{}-{}", &raw[0..4], &raw[4..8], &raw[8..12])
+}
+
+// 恢复码比对前统一格式：去掉空白和连字符并转为小写
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
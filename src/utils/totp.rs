@@ -0,0 +1,105 @@
+use anyhow::{anyhow, Result};
+use qrcode::{render::svg, QrCode};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const TOTP_ISSUER: &str = "智能记账";
+
+// 生成新的Base32编码TOTP密钥
+pub fn generate_totp_secret() -> String {
+    match Secret::generate_secret().to_encoded() {
+        Secret::Encoded(secret) => secret,
+        Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+    }
+}
+
+// RFC 6238：SHA1、6位数字、30秒步长，允许前后各一个步长的时钟偏差
+fn build_totp(secret: &str, account_name: &str) -> Result<TOTP> {
+    let secret_bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| anyhow!("Invalid TOTP secret: {:?}", e))?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret_bytes,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .map_err(|e| anyhow!("Failed to build TOTP: {}", e))
+}
+
+pub fn totp_provisioning_uri(secret: &str, account_name: &str) -> Result<String> {
+    Ok(build_totp(secret, account_name)?.get_url())
+}
+
+pub fn totp_qr_svg(uri: &str) -> Result<String> {
+    let code = QrCode::new(uri.as_bytes())?;
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+// 校验动态验证码，返回匹配的时间步（Unix时间 / 30秒）。调用方需记录已使用的时间步，
+// 拒绝不晚于上次的时间步，否则同一个验证码在偏差窗口内可以重复使用
+pub fn verify_totp_code(secret: &str, account_name: &str, code: &str) -> Result<Option<u64>> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    matching_step(&build_totp(secret, account_name)?, code, now)
+}
+
+fn matching_step(totp: &TOTP, code: &str, now: u64) -> Result<Option<u64>> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != 6 || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    // 从最新的时间步往前比较，验证码碰巧在多个时间步相同时记下最晚的那个
+    let current = now / totp.step;
+    let skew = totp.skew as u64;
+    Ok((current.saturating_sub(skew)..=current + skew)
+        .rev()
+        .find(|step| constant_time_eq(totp.generate(step * totp.step).as_bytes(), code.as_bytes())))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+
+    #[test]
+    fn matches_codes_within_one_step_of_skew() {
+        let totp = build_totp(SECRET, "alice@example.com").unwrap();
+        let now = 1_700_000_015;
+        let step = now / 30;
+
+        for offset in [-1i64, 0, 1] {
+            let code_step = (step as i64 + offset) as u64;
+            let code = totp.generate(code_step * 30);
+            assert_eq!(matching_step(&totp, &code, now).unwrap(), Some(code_step));
+        }
+
+        let stale = totp.generate((step - 2) * 30);
+        assert_eq!(matching_step(&totp, &stale, now).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        let totp = build_totp(SECRET, "alice@example.com").unwrap();
+        let now = 1_700_000_015;
+        let code = totp.generate(now);
+
+        let spaced = format!("{} {}", &code[..3], &code[3..]);
+        assert_eq!(matching_step(&totp, &spaced, now).unwrap(), Some(now / 30));
+        assert_eq!(matching_step(&totp, &code[..5], now).unwrap(), None);
+        assert_eq!(matching_step(&totp, "12345a", now).unwrap(), None);
+    }
+}
@@ -22,6 +22,93 @@ pub struct LoginForm {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorForm {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTwoFactorForm {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountForm {
+    pub password: String,
+    // 启用两步验证时必填
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeEmailForm {
+    #[validate(email(message = "请输入有效的邮箱地址"))]
+    pub new_email: String,
+
+    #[validate(length(min = 1, message = "请输入当前密码"))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PreferencesForm {
+    #[validate(length(max = 100, message = "显示名称不能超过100字符"))]
+    pub display_name: Option<String>,
+
+    pub timezone: String,
+
+    pub locale: String,
+
+    pub default_currency: String,
+
+    pub date_format: String,
+
+    pub week_start: i8,
+
+    // 为空表示使用上次选择的账本
+    pub default_account_book_id: Option<String>,
+}
+
+// 粘贴或选择文件读取的汇率CSV内容
+#[derive(Debug, Deserialize)]
+pub struct ImportExchangeRatesForm {
+    pub csv: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ApiTokenForm {
+    #[validate(length(min = 1, max = 100, message = "令牌名称长度必须在1-100字符之间"))]
+    pub name: String,
+
+    pub scope: String,
+
+    // 为空表示不限账本
+    pub account_book_id: Option<String>,
+
+    // 为空表示永不过期
+    pub expires_in_days: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordForm {
+    #[validate(email(message = "请输入有效的邮箱地址"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResendVerificationForm {
+    #[validate(email(message = "请输入有效的邮箱地址"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordForm {
+    #[validate(length(min = 6, message = "密码长度至少6个字符"))]
+    pub password: String,
+    
+    #[validate(must_match(other = "password", message = "两次输入的密码不一致"))]
+    pub confirm_password: String,
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct AccountBookForm {
     #[validate(length(min = 1, max = 100, message = "账本名称长度必须在1-100字符之间"))]
@@ -35,6 +122,48 @@ pub struct AccountBookForm {
     
     #[validate(range(min = 1, max = 31, message = "起始日必须在1-31之间"))]
     pub cycle_start_day: i32,
+
+    // 新建账本时选择的分类模板，为空时不预置分类
+    #[serde(default)]
+    pub category_template: String,
+}
+
+// 复制账本，transactions 为 none/all/range，range 时需填写起止日期
+#[derive(Debug, Deserialize, Validate)]
+pub struct DuplicateAccountBookForm {
+    #[validate(length(min = 1, max = 100, message = "账本名称长度必须在1-100字符之间"))]
+    pub name: String,
+
+    pub transactions: String,
+
+    pub start_date: Option<String>,
+
+    pub end_date: Option<String>,
+}
+
+// 永久删除账本前需输入账本名称确认
+#[derive(Debug, Deserialize)]
+pub struct PurgeAccountBookForm {
+    pub confirm_name: String,
+}
+
+// 结账截止日期（含）
+#[derive(Debug, Deserialize)]
+pub struct ClosePeriodForm {
+    pub closed_through: String,
+}
+
+// 重新开放后的结账截止日期，为空时完全取消结账
+#[derive(Debug, Deserialize)]
+pub struct ReopenPeriodForm {
+    pub closed_through: Option<String>,
+}
+
+// 将账本当前分类保存为模板
+#[derive(Debug, Deserialize, Validate)]
+pub struct SaveCategoryTemplateForm {
+    #[validate(length(min = 1, max = 50, message = "模板名称长度必须在1-50字符之间"))]
+    pub name: String,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -49,6 +178,37 @@ pub struct CategoryForm {
     pub color: Option<String>,
 }
 
+// 资金账户，期初余额可以为负（如信用卡欠款）
+#[derive(Debug, Deserialize, Validate)]
+pub struct WalletForm {
+    #[validate(length(min = 1, max = 100, message = "账户名称长度必须在1-100字符之间"))]
+    pub name: String,
+
+    pub wallet_type: String,
+
+    pub color: Option<String>,
+
+    pub opening_balance: Option<String>,
+}
+
+// 资金账户某日的实际余额
+#[derive(Debug, Deserialize, Validate)]
+pub struct WalletSnapshotForm {
+    pub snapshot_date: String,
+
+    pub balance: String,
+
+    #[validate(length(max = 200, message = "备注长度不能超过200字符"))]
+    pub note: Option<String>,
+}
+
+// 重命名标签
+#[derive(Debug, Deserialize, Validate)]
+pub struct TagForm {
+    #[validate(length(min = 1, max = 50, message = "标签名称长度必须在1-50字符之间"))]
+    pub name: String,
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct TransactionForm {
     pub category_id: i64,
@@ -64,4 +224,16 @@ pub struct TransactionForm {
     
     #[validate(length(max = 500, message = "标签长度不能超过500字符"))]
     pub tags: Option<String>,
-}
\ No newline at end of file
+}
+#[derive(Debug, Deserialize, Validate)]
+pub struct InviteMemberForm {
+    #[validate(email(message = "请输入有效的邮箱地址"))]
+    pub email: String,
+
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMemberRoleForm {
+    pub role: String,
+}
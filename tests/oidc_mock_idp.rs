@@ -0,0 +1,250 @@
+// 用本地模拟的身份提供方走完 OIDC 登录流程：发现文档、授权码换取令牌和 ID Token 校验
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use accounting_system::config::OidcProviderConfig;
+use accounting_system::services::{OidcIdentity, OidcService};
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use chrono::Utc;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::{json, Value};
+
+const CLIENT_ID: &str = "accounting-system";
+const AUTH_CODE: &str = "mock-auth-code";
+const KEY_1_PEM: &str = include_str!("fixtures/oidc_signing_key_1.pem");
+const KEY_2_PEM: &str = include_str!("fixtures/oidc_signing_key_2.pem");
+const KEY_1_N: &str = "2na35YL6svdaBmQZGl7eBdOvjbfABH3n0s1vbYkR_kBfHq_OcAOMkW46VgldL154FmLPGPkyNtIn8IAGmbPveNYOndqMIWFH-dbNjt2NrILbOu64cOM0C61fBi86N6uUOqAE29JD83kjaAuM33btwTtXXoFAcn3kY2RXARVs5wRnu1KZNCb4aifsyX4WzRQw4TWECgulIoypqWY8c8MzWLi8sScdJWjyhOHP-PMF5LcxV-3Nk-mOZe1x54ylqO40TC29HlgHDJlJ2lC3xjolWaz3nEdGLkeGHcUP5CMrEVSQFaWHU1Kz-R3biKsOsJM8oWLGYCkNSjfjQskawKwkTQ";
+const KEY_2_N: &str = "qy3Gn28VRvADojeHR2SjRiTTHCN_ZE2gqP67Ogj7yj9mqzeO9EDe6UoGKQPhq179R0lDFkJzYCFuBTYQL5-RbRJGvJU3rEKHDLb5v1QrHEBpbp89d4Ik8NRPGdMznvuJy4EpWray5bw_rCTihWNfJzJL__iGtKKVkrHlk8j6XHlxTPzDZYJ6Uc_Vj-bjhu-z59hBf0g4-PCt3_Ih6J9EhwAoiZb4fM1dfXRajrXMP2KpkPzwJni8KVh5bQ7HIYZ6dWejrxWbJoobvTonp4jrNsdyN0GWx1cpl6K6Hl4mlR7srS11E8kicogWTwrc4cD_0D9iRI8vWpKcvuhAe0qkaw";
+
+// 模拟身份提供方签发 ID Token 的方式
+#[derive(Clone)]
+struct Signing {
+    kid: &'static str,
+    alg: Algorithm,
+    key: EncodingKey,
+    audience: &'static str,
+}
+
+struct MockIdp {
+    issuer: String,
+    nonce: String,
+    signing: Signing,
+    jwks: Vec<Value>,
+    discovery_requests: usize,
+    jwks_requests: usize,
+    token_requests: Vec<HashMap<String, String>>,
+}
+
+type SharedIdp = Arc<Mutex<MockIdp>>;
+
+fn rsa_jwk(kid: &str, n: &str, alg: Option<&str>) -> Value {
+    let mut jwk = json!({ "kty": "RSA", "use": "sig", "kid": kid, "n": n, "e": "AQAB" });
+    if let Some(alg) = alg {
+        jwk["alg"] = json!(alg);
+    }
+    jwk
+}
+
+fn signing(kid: &'static str, pem: &str, alg: Algorithm) -> Signing {
+    Signing {
+        kid,
+        alg,
+        key: EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap(),
+        audience: CLIENT_ID,
+    }
+}
+
+async fn discovery(State(idp): State<SharedIdp>) -> Json<Value> {
+    let mut idp = idp.lock().unwrap();
+    idp.discovery_requests += 1;
+    Json(json!({
+        "issuer": idp.issuer,
+        "authorization_endpoint": format!("{}/authorize", idp.issuer),
+        "token_endpoint": format!("{}/token", idp.issuer),
+        "jwks_uri": format!("{}/jwks", idp.issuer),
+    }))
+}
+
+async fn jwks(State(idp): State<SharedIdp>) -> Json<Value> {
+    let mut idp = idp.lock().unwrap();
+    idp.jwks_requests += 1;
+    Json(json!({ "keys": idp.jwks }))
+}
+
+async fn token(State(idp): State<SharedIdp>, Form(params): Form<HashMap<String, String>>) -> Json<Value> {
+    let mut idp = idp.lock().unwrap();
+    assert_eq!(params.get("code").map(String::as_str), Some(AUTH_CODE));
+    idp.token_requests.push(params);
+
+    let now = Utc::now().timestamp();
+    let claims = json!({
+        "iss": idp.issuer,
+        "aud": idp.signing.audience,
+        "sub": "mock-user-1",
+        "email": "Alice@Example.com",
+        "email_verified": "true",
+        "nonce": idp.nonce,
+        "iat": now,
+        "exp": now + 300,
+    });
+    let mut header = Header::new(idp.signing.alg);
+    header.kid = Some(idp.signing.kid.to_string());
+    let id_token = encode(&header, &claims, &idp.signing.key).unwrap();
+
+    Json(json!({ "access_token": "mock-access-token", "token_type": "Bearer", "id_token": id_token }))
+}
+
+async fn start_idp() -> SharedIdp {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+    let idp = Arc::new(Mutex::new(MockIdp {
+        issuer,
+        nonce: String::new(),
+        signing: signing("key-1", KEY_1_PEM, Algorithm::RS256),
+        jwks: vec![rsa_jwk("key-1", KEY_1_N, Some("RS256"))],
+        discovery_requests: 0,
+        jwks_requests: 0,
+        token_requests: Vec::new(),
+    }));
+
+    let app = Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/token", post(token))
+        .with_state(idp.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    idp
+}
+
+fn service(idp: &SharedIdp, algorithms: Vec<Algorithm>) -> OidcService {
+    let provider = OidcProviderConfig {
+        id: "mock".to_string(),
+        display_name: "Mock IdP".to_string(),
+        issuer: idp.lock().unwrap().issuer.clone(),
+        client_id: CLIENT_ID.to_string(),
+        client_secret: Some("mock-secret".to_string()),
+        scopes: "openid email profile".to_string(),
+        allow_signup: true,
+        algorithms,
+    };
+    OidcService::new(vec![provider], "http://app.test".to_string(), "test-jwt-secret".to_string())
+}
+
+// 模拟浏览器：取授权地址中的 state 和 nonce，身份提供方按 nonce 签发令牌后回调
+async fn login(service: &OidcService, idp: &SharedIdp) -> anyhow::Result<OidcIdentity> {
+    let (authorization_url, flow_token) = service.authorization_url("mock").await?;
+    let url = reqwest::Url::parse(&authorization_url)?;
+    let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    assert_eq!(query["client_id"], CLIENT_ID);
+    assert_eq!(query["redirect_uri"], "http://app.test/auth/oidc/mock/callback");
+    assert_eq!(query["code_challenge_method"], "S256");
+
+    idp.lock().unwrap().nonce = query["nonce"].clone();
+    service.complete("mock", AUTH_CODE, &query["state"], &flow_token).await
+}
+
+#[tokio::test]
+async fn completes_login_against_mock_idp() {
+    let idp = start_idp().await;
+    let service = service(&idp, vec![Algorithm::RS256]);
+
+    let identity = login(&service, &idp).await.unwrap();
+    assert_eq!(identity.provider, "mock");
+    assert_eq!(identity.subject, "mock-user-1");
+    assert_eq!(identity.email.as_deref(), Some("alice@example.com"));
+    assert!(identity.email_verified);
+
+    // 换取令牌时带上 PKCE verifier 和客户端密钥
+    {
+        let idp = idp.lock().unwrap();
+        let params = &idp.token_requests[0];
+        assert_eq!(params["grant_type"], "authorization_code");
+        assert_eq!(params["client_secret"], "mock-secret");
+        assert!(params["code_verifier"].len() >= 43);
+    }
+
+    // 发现文档和 JWKS 都被缓存
+    login(&service, &idp).await.unwrap();
+    let idp = idp.lock().unwrap();
+    assert_eq!(idp.discovery_requests, 1);
+    assert_eq!(idp.jwks_requests, 1);
+}
+
+#[tokio::test]
+async fn refreshes_jwks_when_key_rotates() {
+    let idp = start_idp().await;
+    let service = service(&idp, vec![Algorithm::RS256]);
+    login(&service, &idp).await.unwrap();
+
+    {
+        let mut idp = idp.lock().unwrap();
+        idp.jwks.push(rsa_jwk("key-2", KEY_2_N, None));
+        idp.signing = signing("key-2", KEY_2_PEM, Algorithm::RS256);
+    }
+
+    login(&service, &idp).await.unwrap();
+    assert_eq!(idp.lock().unwrap().jwks_requests, 2);
+}
+
+#[tokio::test]
+async fn rejects_algorithm_outside_allowlist() {
+    let idp = start_idp().await;
+    let service = service(&idp, vec![Algorithm::RS256]);
+
+    // 用公开的模数作为 HMAC 密钥伪造签名
+    idp.lock().unwrap().signing = Signing {
+        kid: "key-1",
+        alg: Algorithm::HS256,
+        key: EncodingKey::from_secret(KEY_1_N.as_bytes()),
+        audience: CLIENT_ID,
+    };
+    assert!(login(&service, &idp).await.is_err());
+
+    idp.lock().unwrap().signing = signing("key-1", KEY_1_PEM, Algorithm::RS384);
+    assert!(login(&service, &idp).await.is_err());
+}
+
+#[tokio::test]
+async fn rejects_algorithm_different_from_key() {
+    let idp = start_idp().await;
+    // 提供方允许 RS384，但 key-1 声明只用于 RS256
+    let service = service(&idp, vec![Algorithm::RS256, Algorithm::RS384]);
+
+    idp.lock().unwrap().signing = signing("key-1", KEY_1_PEM, Algorithm::RS384);
+    assert!(login(&service, &idp).await.is_err());
+}
+
+#[tokio::test]
+async fn rejects_token_for_another_client_or_unknown_key() {
+    let idp = start_idp().await;
+    let service = service(&idp, vec![Algorithm::RS256]);
+
+    idp.lock().unwrap().signing.audience = "another-client";
+    assert!(login(&service, &idp).await.is_err());
+
+    // 用 JWKS 中没有的密钥签名，重新获取后仍找不到
+    idp.lock().unwrap().signing = signing("key-2", KEY_2_PEM, Algorithm::RS256);
+    assert!(login(&service, &idp).await.is_err());
+
+    // 签名密钥与 JWKS 中同 kid 的公钥不匹配
+    idp.lock().unwrap().signing = signing("key-1", KEY_2_PEM, Algorithm::RS256);
+    assert!(login(&service, &idp).await.is_err());
+}
+
+#[tokio::test]
+async fn rejects_mismatched_state() {
+    let idp = start_idp().await;
+    let service = service(&idp, vec![Algorithm::RS256]);
+
+    let (_, flow_token) = service.authorization_url("mock").await.unwrap();
+    assert!(service.complete("mock", AUTH_CODE, "forged-state", &flow_token).await.is_err());
+    assert!(idp.lock().unwrap().token_requests.is_empty());
+}